@@ -0,0 +1,53 @@
+//! Sequential against parallel search scoring across graph sizes, to place
+//! `DEFAULT_PARALLEL_THRESHOLD` at the crossover. Run with `cargo bench --bench search_scoring`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mcp_memory::handler::{GraphService, KnowledgeGraphService};
+
+const SIZES: [usize; 9] = [16, 64, 256, 512, 1024, 2048, 4096, 8192, 16384];
+
+/// A JSONL graph of `size` entities with a few observations each, some mentioning the query
+fn seed(size: usize) -> String {
+    (0..size)
+        .map(|i| {
+            format!(
+                r#"{{"type":"entity","name":"Entity {i}","entityType":"Type {}","observations":["Observation {i} about topic {}","Works with entity {}","{}"]}}"#,
+                i % 7,
+                i % 13,
+                (i + 1) % size,
+                if i % 5 == 0 { "Drinks green tea" } else { "Drinks coffee" }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// An in-memory service whose searches are never cached and switch to parallel scoring
+/// at `threshold` entities
+fn service(seed: &str, threshold: usize) -> KnowledgeGraphService {
+    std::env::set_var("SEARCH_CACHE_SIZE", "0");
+    std::env::set_var("SEARCH_PARALLEL_THRESHOLD", threshold.to_string());
+    KnowledgeGraphService::in_memory(seed).expect("valid seed")
+}
+
+fn scoring(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("runtime");
+    let mut group = c.benchmark_group("search_scoring");
+    for size in SIZES {
+        let seed = seed(size);
+        for (mode, threshold) in [("sequential", usize::MAX), ("parallel", 0)] {
+            let service = service(&seed, threshold);
+            group.bench_with_input(BenchmarkId::new(mode, size), &size, |b, _| {
+                b.iter(|| {
+                    runtime
+                        .block_on(service.search_nodes("tea", None, Default::default()))
+                        .expect("search")
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scoring);
+criterion_main!(benches);
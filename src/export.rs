@@ -0,0 +1,245 @@
+use anyhow::{Context, Result};
+use rmcp::schemars;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::graph::{jsonl_lines, Entity, KnowledgeGraph};
+
+/// Output format of an export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// The `read_graph` shape
+    #[default]
+    Json,
+    /// The memory file format, one entity or relation per line
+    Jsonl,
+    /// A Graphviz digraph
+    Dot,
+    /// One row per entity and per relation
+    Csv,
+}
+
+/// Which part of the graph to export. An entity is included when it matches any criterion;
+/// with no criteria the whole graph is exported.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ExportFilter {
+    #[schemars(description = "Include the entities of these types")]
+    #[serde(default)]
+    pub types: Vec<String>,
+    #[schemars(description = "Include the entities with these names")]
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[schemars(description = "Include every entity matching this search query")]
+    pub query: Option<String>,
+}
+
+impl ExportFilter {
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.names.is_empty() && self.query.is_none()
+    }
+}
+
+/// Separator between an entity's observations in formats with one field for all of them
+pub const DEFAULT_OBSERVATION_DELIMITER: &str = " | ";
+
+/// Serialize a graph in the given format; `delimiter` separates observations in CSV
+pub fn render(graph: &KnowledgeGraph, format: ExportFormat, delimiter: &str) -> Result<String> {
+    match format {
+        ExportFormat::Json => serde_json::to_string(graph).context("Failed to serialize graph"),
+        ExportFormat::Jsonl => {
+            let mut out = String::new();
+            for line in jsonl_lines(graph) {
+                out.push_str(&line?);
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        ExportFormat::Dot => Ok(render_dot(graph, None)),
+        ExportFormat::Csv => {
+            if delimiter.is_empty() {
+                anyhow::bail!("Observation delimiter must not be empty");
+            }
+            Ok(render_csv(graph, delimiter))
+        }
+    }
+}
+
+fn sorted_entities(graph: &KnowledgeGraph) -> Vec<&Entity> {
+    let mut entities: Vec<_> = graph.entities.values().collect();
+    entities.sort_by(|a, b| a.name.cmp(&b.name));
+    entities
+}
+
+fn dot_quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+/// A Graphviz digraph of an entity's neighborhood, drawing `seed` filled and double-circled
+pub fn render_ego_dot(graph: &KnowledgeGraph, seed: &str) -> String {
+    render_dot(graph, Some(seed))
+}
+
+fn render_dot(graph: &KnowledgeGraph, seed: Option<&str>) -> String {
+    let mut out = String::from("digraph memory {\n");
+    for entity in sorted_entities(graph) {
+        let style = if seed == Some(entity.name.as_str()) {
+            ", shape=doublecircle, style=filled, fillcolor=lightblue"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "  {} [label={}{style}];\n",
+            dot_quote(&entity.name),
+            dot_quote(&format!("{}\n({})", entity.name, entity.entity_type))
+        ));
+    }
+    for relation in &graph.relations {
+        out.push_str(&format!(
+            "  {} -> {} [label={}];\n",
+            dot_quote(&relation.from),
+            dot_quote(&relation.to),
+            dot_quote(&relation.relation_type)
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// `values` joined by `delimiter`, with a backslash before every backslash and delimiter
+/// inside a value so the result splits back unambiguously
+fn join_escaped<'a>(values: impl Iterator<Item = &'a str>, delimiter: &str) -> String {
+    let escaped_delimiter = format!("\\{delimiter}");
+    values
+        .map(|value| {
+            value
+                .replace('\\', "\\\\")
+                .replace(delimiter, &escaped_delimiter)
+        })
+        .collect::<Vec<_>>()
+        .join(delimiter)
+}
+
+/// Entities and relations share one table; `type` tells them apart and observations are
+/// separated by `delimiter` within their field
+fn render_csv(graph: &KnowledgeGraph, delimiter: &str) -> String {
+    let mut out = String::from("type,name,entityType,observations,from,to,relationType\n");
+    for entity in sorted_entities(graph) {
+        let observations = join_escaped(
+            entity.observations.iter().map(|o| o.content.as_str()),
+            delimiter,
+        );
+        out.push_str(&format!(
+            "entity,{},{},{},,,\n",
+            csv_field(&entity.name),
+            csv_field(&entity.entity_type),
+            csv_field(&observations)
+        ));
+    }
+    for relation in &graph.relations {
+        out.push_str(&format!(
+            "relation,,,,{},{},{}\n",
+            csv_field(&relation.from),
+            csv_field(&relation.to),
+            csv_field(&relation.relation_type)
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{KnowledgeGraphManager, Relation};
+
+    fn graph() -> KnowledgeGraph {
+        let entities = [("Bob", "Person"), ("Alice \"Al\"", "Person")]
+            .into_iter()
+            .map(|(name, entity_type)| Entity {
+                name: name.to_string(),
+                entity_type: entity_type.to_string(),
+                observations: vec!["Likes tea, coffee".into(), "Lives in Berlin".into()],
+                ..Default::default()
+            })
+            .map(|e| (e.name.clone(), e))
+            .collect();
+        KnowledgeGraph {
+            entities,
+            relations: vec![Relation {
+                from: "Alice \"Al\"".to_string(),
+                to: "Bob".to_string(),
+                relation_type: "knows".to_string(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_render_dot() -> Result<()> {
+        let dot = render(&graph(), ExportFormat::Dot, DEFAULT_OBSERVATION_DELIMITER)?;
+        assert_eq!(
+            dot,
+            "digraph memory {\n  \
+             \"Alice \\\"Al\\\"\" [label=\"Alice \\\"Al\\\"\\n(Person)\"];\n  \
+             \"Bob\" [label=\"Bob\\n(Person)\"];\n  \
+             \"Alice \\\"Al\\\"\" -> \"Bob\" [label=\"knows\"];\n}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_ego_dot() {
+        let dot = render_ego_dot(&graph(), "Bob");
+        assert!(dot.contains(
+            "  \"Bob\" [label=\"Bob\\n(Person)\", shape=doublecircle, style=filled, fillcolor=lightblue];\n"
+        ));
+        assert!(dot.contains("  \"Alice \\\"Al\\\"\" [label=\"Alice \\\"Al\\\"\\n(Person)\"];\n"));
+    }
+
+    #[test]
+    fn test_render_csv() -> Result<()> {
+        let csv = render(&graph(), ExportFormat::Csv, DEFAULT_OBSERVATION_DELIMITER)?;
+        assert_eq!(
+            csv,
+            "type,name,entityType,observations,from,to,relationType\n\
+             entity,\"Alice \"\"Al\"\"\",Person,\"Likes tea, coffee | Lives in Berlin\",,,\n\
+             entity,Bob,Person,\"Likes tea, coffee | Lives in Berlin\",,,\n\
+             relation,,,,\"Alice \"\"Al\"\"\",Bob,knows\n"
+        );
+
+        let csv = render(&graph(), ExportFormat::Csv, "\n")?;
+        assert!(csv.contains("entity,Bob,Person,\"Likes tea, coffee\nLives in Berlin\",,,\n"));
+        assert!(render(&graph(), ExportFormat::Csv, "").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_escaped() {
+        let joined = join_escaped(["a | b", "c\\d", "e"].into_iter(), " | ");
+        assert_eq!(joined, "a\\ | b | c\\\\d | e");
+    }
+
+    #[tokio::test]
+    async fn test_render_json_and_jsonl_round_trip() -> Result<()> {
+        for format in [ExportFormat::Json, ExportFormat::Jsonl] {
+            let rendered = render(&graph(), format, DEFAULT_OBSERVATION_DELIMITER)?;
+            let reloaded = KnowledgeGraphManager::in_memory(&rendered)?;
+            assert_eq!(reloaded.get_stats().await?, (2, 1), "{format:?}");
+        }
+        Ok(())
+    }
+}
@@ -2,28 +2,185 @@ use anyhow::{Context, Result};
 use rmcp::schemars;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::env;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{
+    AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter,
+};
+use tokio::sync::Mutex;
 
-use crate::search::SearchEngine;
+use crate::export::{render, render_ego_dot, ExportFilter, ExportFormat};
+use crate::search::{
+    name_similarity, tokenize, EntityCoverage, ObservationMatch, RankedEntities, SearchConfig,
+    SearchEngine, SearchField, SearchOptions, DEFAULT_SEARCH_LIMIT,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+/// A single fact about an entity.
+/// Serialized as a plain string unless it carries metadata (a source or tags), and accepted in either form.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(from = "ObservationRepr")]
+pub struct Observation {
+    /// The observation text
+    pub content: String,
+    /// Where the observation came from, e.g. a document or conversation id
+    pub source: Option<String>,
+    /// Categorical metadata such as `confidence: high` or `topic: finance`
+    pub tags: BTreeMap<String, String>,
+}
+
+/// Wire format of an observation: a plain string or an object with metadata
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ObservationRepr {
+    Plain(String),
+    Structured {
+        #[schemars(description = "The observation text")]
+        content: String,
+        #[serde(default)]
+        #[schemars(description = "Where the observation came from")]
+        source: Option<String>,
+        #[serde(default)]
+        #[schemars(description = "Key-value metadata, e.g. {\"topic\": \"finance\"}")]
+        tags: BTreeMap<String, String>,
+    },
+}
+
+/// Borrowed structured form used when an observation carries metadata
+#[derive(Serialize)]
+struct StructuredObservation<'a> {
+    content: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'a str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    tags: &'a BTreeMap<String, String>,
+}
+
+impl Observation {
+    /// Create an observation attributed to `source`
+    pub fn with_source(content: impl Into<String>, source: Option<String>) -> Self {
+        Self {
+            content: content.into(),
+            source,
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Attach key-value tags to the observation
+    pub fn with_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.tags = tags;
+        self
+    }
+}
+
+impl From<ObservationRepr> for Observation {
+    fn from(repr: ObservationRepr) -> Self {
+        match repr {
+            ObservationRepr::Plain(content) => content.into(),
+            ObservationRepr::Structured {
+                content,
+                source,
+                tags,
+            } => Self {
+                content,
+                source,
+                tags,
+            },
+        }
+    }
+}
+
+impl From<String> for Observation {
+    fn from(content: String) -> Self {
+        Self::with_source(content, None)
+    }
+}
+
+impl From<&str> for Observation {
+    fn from(content: &str) -> Self {
+        content.to_string().into()
+    }
+}
+
+impl PartialEq<&str> for Observation {
+    fn eq(&self, other: &&str) -> bool {
+        self.content == *other
+    }
+}
+
+impl Serialize for Observation {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.source.is_none() && self.tags.is_empty() {
+            serializer.serialize_str(&self.content)
+        } else {
+            StructuredObservation {
+                content: &self.content,
+                source: self.source.as_deref(),
+                tags: &self.tags,
+            }
+            .serialize(serializer)
+        }
+    }
+}
+
+impl JsonSchema for Observation {
+    fn schema_name() -> String {
+        "Observation".to_string()
+    }
+
+    fn json_schema(generator: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        ObservationRepr::json_schema(generator)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct Entity {
     #[schemars(description = "The name of the entity")]
     pub name: String,
     #[serde(rename = "entityType")]
     #[schemars(description = "The type of the entity")]
     pub entity_type: String,
-    #[schemars(description = "An array of observation contents associated with the entity")]
-    pub observations: Vec<String>,
+    #[schemars(
+        description = "An array of observations associated with the entity, as plain strings or {content, source, tags} objects"
+    )]
+    pub observations: Vec<Observation>,
+    #[serde(rename = "createdAt", default, skip_serializing_if = "Option::is_none")]
+    #[schemars(
+        description = "Unix timestamp (seconds) when the entity was created; set by the server when omitted"
+    )]
+    pub created_at: Option<i64>,
+    #[serde(rename = "updatedAt", default, skip_serializing_if = "Option::is_none")]
+    #[schemars(
+        description = "Unix timestamp (seconds) when the entity was last changed; set by the server when omitted"
+    )]
+    pub updated_at: Option<i64>,
+    #[serde(
+        rename = "sourceFile",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(
+        description = "The memory file the entity was read from; only reported when several files are federated"
+    )]
+    pub source_file: Option<String>,
+    #[serde(
+        rename = "matchedFields",
+        skip_deserializing,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    #[schemars(
+        description = "Which fields matched the query; only reported on search_nodes results"
+    )]
+    pub matched_fields: Vec<SearchField>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct Relation {
     #[schemars(description = "The name of the entity where the relation starts")]
     pub from: String,
@@ -32,9 +189,31 @@ pub struct Relation {
     #[serde(rename = "relationType")]
     #[schemars(description = "The type of the relation")]
     pub relation_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(
+        description = "Notes about the relation itself, such as 'worked together 2019-2021'"
+    )]
+    pub observations: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A relation is identified by its endpoints and type; its observations annotate it
+impl PartialEq for Relation {
+    fn eq(&self, other: &Self) -> bool {
+        self.from == other.from && self.to == other.to && self.relation_type == other.relation_type
+    }
+}
+
+impl Eq for Relation {}
+
+impl std::hash::Hash for Relation {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.from.hash(state);
+        self.to.hash(state);
+        self.relation_type.hash(state);
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct KnowledgeGraph {
     pub entities: std::collections::HashMap<String, Entity>,
     pub relations: Vec<Relation>,
@@ -48,570 +227,8447 @@ enum GraphItem {
     Relation(Relation),
 }
 
-pub struct KnowledgeGraphManager {
-    memory_file_path: PathBuf,
-    search_engine: Arc<SearchEngine>,
+/// Current time as a Unix timestamp in seconds
+pub(crate) fn now_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
 }
 
-impl KnowledgeGraphManager {
-    pub fn new() -> Self {
-        let current_exe = env::current_exe().unwrap_or_else(|_| PathBuf::from("."));
-        let current_dir = current_exe.parent().unwrap_or_else(|| Path::new("."));
-        let default_memory_path = current_dir.join("memory.jsonl");
+/// Outcome of upserting a single entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpsertStatus {
+    /// The entity did not exist and was created
+    Created,
+    /// The entity existed and gained observations or a new type
+    Updated,
+    /// The entity existed and nothing changed
+    Unchanged,
+}
 
-        let memory_file_path = if let Ok(path_env) = env::var("MEMORY_FILE_PATH") {
-            let path = PathBuf::from(path_env);
-            if path.is_absolute() {
-                path
-            } else {
-                current_dir.join(path)
-            }
-        } else {
-            default_memory_path
-        };
+/// The vocabulary of entity and relation types the graph is meant to use
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Ontology {
+    #[serde(default)]
+    #[schemars(description = "Allowed entity types; an empty list allows any type")]
+    pub entity_types: Vec<String>,
+    #[serde(default)]
+    #[schemars(description = "Allowed relation types; an empty list allows any type")]
+    pub relation_types: Vec<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "Reject entities and relations whose types are not listed (default false)"
+    )]
+    pub strict: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(
+        description = "Relation types that hold both ways, such as 'sibling_of'; a relation of one of these types is the same as its reverse, so the reverse isn't stored again"
+    )]
+    pub symmetric_relation_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(
+        description = "Relation types an entity may have at most one of as the source, such as 'reports_to'"
+    )]
+    pub single_valued_relation_types: Vec<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "What a second relation of a single-valued type from the same entity does: 'reject' (default) fails with the conflict, 'replace' removes the existing relation"
+    )]
+    pub on_cardinality_conflict: CardinalityConflict,
+}
 
-        let search_engine = Arc::new(SearchEngine::new());
+/// How `create_relations` handles a second relation of a single-valued type from one entity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CardinalityConflict {
+    /// Fail, naming the relation already held
+    #[default]
+    Reject,
+    /// Remove the relation already held and add the new one
+    Replace,
+}
 
-        Self {
-            memory_file_path,
-            search_engine,
-        }
+impl Ontology {
+    fn is_single_valued(&self, relation: &Relation) -> bool {
+        self.single_valued_relation_types
+            .contains(&relation.relation_type)
     }
 
-    pub fn with_path<P: AsRef<Path>>(path: P) -> Self {
-        let memory_file_path = path.as_ref().to_path_buf();
-
-        let search_engine = Arc::new(SearchEngine::new());
-
-        Self {
-            memory_file_path,
-            search_engine,
+    /// Pairs of a relation already held, stored or earlier in `new`, and a relation from
+    /// `new` of the same single-valued type and source
+    fn cardinality_conflicts<'a>(
+        &self,
+        stored: &'a [Relation],
+        new: &'a [Relation],
+    ) -> Vec<(&'a Relation, &'a Relation)> {
+        let mut held: HashMap<(&str, &str), &Relation> = stored
+            .iter()
+            .filter(|r| self.is_single_valued(r))
+            .map(|r| ((r.from.as_str(), r.relation_type.as_str()), r))
+            .collect();
+        let mut conflicts = Vec::new();
+        for relation in new.iter().filter(|r| self.is_single_valued(r)) {
+            let key = (relation.from.as_str(), relation.relation_type.as_str());
+            if let Some(previous) = held.insert(key, relation) {
+                conflicts.push((previous, relation));
+            }
         }
+        conflicts
     }
 
-    async fn load_graph(&self) -> Result<Box<KnowledgeGraph>> {
-        let _start_time = Instant::now();
-
-        match fs::read_to_string(&self.memory_file_path).await {
-            Ok(data) => {
-                let mut entities = Vec::new();
-                let mut relations = Vec::new();
+    /// Apply `on_cardinality_conflict` to relations about to be added to `stored`: any
+    /// conflict is an error when rejecting, otherwise the last of `new` for a source and type
+    /// wins and the stored relations it displaces are taken out of `stored` and returned
+    fn settle_cardinality(
+        &self,
+        stored: &mut Vec<Relation>,
+        new: &mut Vec<Relation>,
+    ) -> Result<Vec<Relation>> {
+        let conflicts = self.cardinality_conflicts(stored, new);
+        if conflicts.is_empty() {
+            return Ok(Vec::new());
+        }
+        match self.on_cardinality_conflict {
+            CardinalityConflict::Reject => {
+                let described: Vec<String> = conflicts
+                    .iter()
+                    .map(|(held, new)| {
+                        format!(
+                            "'{}' already has a '{}' relation to '{}', so one to '{}' can't be added",
+                            held.from, held.relation_type, held.to, new.to
+                        )
+                    })
+                    .collect();
+                anyhow::bail!("Single-valued relation conflict: {}", described.join("; "));
+            }
+            CardinalityConflict::Replace => {
+                // The last relation given for a source and type wins
+                let mut winners = HashSet::new();
+                new.reverse();
+                new.retain(|r| {
+                    !self.is_single_valued(r)
+                        || winners.insert((r.from.clone(), r.relation_type.clone()))
+                });
+                new.reverse();
+                let (removed, kept) = std::mem::take(stored)
+                    .into_iter()
+                    .partition(|r| winners.contains(&(r.from.clone(), r.relation_type.clone())));
+                *stored = kept;
+                Ok(removed)
+            }
+        }
+    }
 
-                for line in data.lines() {
-                    if line.trim().is_empty() {
-                        continue;
-                    }
+    /// What makes two relations the same: for symmetric types the endpoints are ordered,
+    /// so a relation and its reverse compare equal
+    fn relation_key<'a>(&self, relation: &'a Relation) -> (&'a str, &'a str, &'a str) {
+        let (from, to) = (relation.from.as_str(), relation.to.as_str());
+        let (from, to) = if from > to
+            && self
+                .symmetric_relation_types
+                .contains(&relation.relation_type)
+        {
+            (to, from)
+        } else {
+            (from, to)
+        };
+        (from, to, relation.relation_type.as_str())
+    }
 
-                    let item: GraphItem = serde_json::from_str(line)
-                        .with_context(|| format!("Failed to parse JSON line: {line}"))?;
-                    match item {
-                        GraphItem::Entity(entity) => entities.push(entity),
-                        GraphItem::Relation(relation) => relations.push(relation),
+    /// Drop relations that are the same as an earlier one under `relation_key`, moving
+    /// observations the earlier one lacks onto it
+    fn merge_duplicate_relations(&self, relations: Vec<Relation>) -> Vec<Relation> {
+        let mut merged: Vec<Relation> = Vec::with_capacity(relations.len());
+        let mut positions: HashMap<(String, String, String), usize> = HashMap::new();
+        for relation in relations {
+            let (from, to, relation_type) = self.relation_key(&relation);
+            let key = (from.to_string(), to.to_string(), relation_type.to_string());
+            match positions.get(&key) {
+                Some(&position) => {
+                    let kept = &mut merged[position];
+                    for observation in relation.observations {
+                        if !kept.observations.contains(&observation) {
+                            kept.observations.push(observation);
+                        }
                     }
                 }
-
-                let graph = KnowledgeGraph {
-                    entities: entities.into_iter().map(|e| (e.name.clone(), e)).collect(),
-                    relations,
-                };
-
-                Ok(Box::new(graph))
+                None => {
+                    positions.insert(key, merged.len());
+                    merged.push(relation);
+                }
             }
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Box::new(KnowledgeGraph {
-                entities: HashMap::new(),
-                relations: Vec::new(),
-            })),
-            Err(e) => Err(e).with_context(|| {
-                format!(
-                    "Failed to read graph from {}",
-                    self.memory_file_path.display()
-                )
-            }),
         }
+        merged
     }
 
-    async fn save_graph(&self, graph: &KnowledgeGraph) -> Result<()> {
-        let mut data = String::new();
-
-        for entity in graph.entities.values() {
-            let item = GraphItem::Entity(entity.clone());
-            let line = serde_json::to_string(&item)
-                .with_context(|| format!("Failed to serialize entity {}", entity.name))?;
-            data.push_str(&line);
-            data.push('\n');
+    /// Types used by `entities` and `relations` that the ontology does not allow.
+    /// Always empty unless the ontology is strict.
+    fn violations(
+        &self,
+        entities: &[&Entity],
+        relations: &[Relation],
+    ) -> (Vec<String>, Vec<String>) {
+        if !self.strict {
+            return (Vec::new(), Vec::new());
         }
+        self.unknown_types(entities, relations)
+    }
 
-        for relation in &graph.relations {
-            let item = GraphItem::Relation(relation.clone());
-            let line = serde_json::to_string(&item).with_context(|| {
-                format!(
-                    "Failed to serialize relation {} -> {}",
-                    relation.from, relation.to
-                )
-            })?;
-            data.push_str(&line);
-            data.push('\n');
+    /// Types used by `entities` and `relations` missing from non-empty type lists,
+    /// whether or not the ontology is strict
+    fn unknown_types(
+        &self,
+        entities: &[&Entity],
+        relations: &[Relation],
+    ) -> (Vec<String>, Vec<String>) {
+        fn offenders<'a>(
+            allowed: &[String],
+            used: impl Iterator<Item = &'a String>,
+        ) -> Vec<String> {
+            if allowed.is_empty() {
+                return Vec::new();
+            }
+            let mut offenders: Vec<String> =
+                used.filter(|t| !allowed.contains(t)).cloned().collect();
+            offenders.sort();
+            offenders.dedup();
+            offenders
         }
 
-        let mut file = fs::File::create(&self.memory_file_path)
-            .await
-            .with_context(|| {
-                format!("Failed to create file {}", self.memory_file_path.display())
-            })?;
-        file.write_all(data.as_bytes())
-            .await
-            .with_context(|| "Failed to write graph to file")?;
+        (
+            offenders(&self.entity_types, entities.iter().map(|e| &e.entity_type)),
+            offenders(
+                &self.relation_types,
+                relations.iter().map(|r| &r.relation_type),
+            ),
+        )
+    }
 
-        Ok(())
+    /// Fail with the offending types if anything falls outside a strict ontology
+    fn check(&self, entities: &[&Entity], relations: &[Relation]) -> Result<()> {
+        let (entity_types, relation_types) = self.violations(entities, relations);
+        let mut problems = Vec::new();
+        if !entity_types.is_empty() {
+            problems.push(format!(
+                "entity types not in schema: {}",
+                entity_types.join(", ")
+            ));
+        }
+        if !relation_types.is_empty() {
+            problems.push(format!(
+                "relation types not in schema: {}",
+                relation_types.join(", ")
+            ));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("{}", problems.join("; "))
+        }
     }
+}
 
-    pub async fn create_entities(&self, entities: Vec<Entity>) -> Result<Vec<Entity>> {
-        let mut graph = self.load_graph().await?;
-        let existing_names: HashSet<_> = graph.entities.values().map(|e| &e.name).collect();
+/// An entity name paired with a count
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedCount {
+    pub name: String,
+    pub count: usize,
+}
 
-        let new_entities: Vec<Entity> = entities
-            .into_iter()
-            .filter(|e| !existing_names.contains(&e.name))
-            .collect();
+/// Size, observation volume, and connectivity of the graph
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DetailedStats {
+    pub entities: usize,
+    pub relations: usize,
+    /// Observations across all entities
+    pub observations: usize,
+    pub average_observations_per_entity: f64,
+    /// Entity with the most observations, a candidate for splitting
+    pub most_observations: Option<NamedCount>,
+    /// Mean number of relations touching an entity
+    pub average_degree: f64,
+    /// Entity touched by the most relations
+    pub max_degree: Option<NamedCount>,
+    /// Entities without any relations
+    pub isolated_entities: usize,
+}
 
-        for entity in new_entities.iter() {
-            graph.entities.insert(entity.name.clone(), entity.clone());
+impl DetailedStats {
+    fn from_graph(graph: &KnowledgeGraph) -> Self {
+        // Highest count wins, ties broken by name so results are stable
+        fn top<'a>(counts: impl Iterator<Item = (&'a String, usize)>) -> Option<NamedCount> {
+            counts
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+                .map(|(name, count)| NamedCount {
+                    name: name.clone(),
+                    count,
+                })
         }
-        self.save_graph(&graph).await?;
 
-        Ok(new_entities)
+        let degrees = entity_degrees(graph);
+        let entities = graph.entities.len();
+        let observations: usize = graph.entities.values().map(|e| e.observations.len()).sum();
+        let total_degree: usize = degrees.values().sum();
+        let average = |total: usize| {
+            if entities == 0 {
+                0.0
+            } else {
+                total as f64 / entities as f64
+            }
+        };
+
+        Self {
+            entities,
+            relations: graph.relations.len(),
+            observations,
+            average_observations_per_entity: average(observations),
+            most_observations: top(graph
+                .entities
+                .iter()
+                .map(|(name, e)| (name, e.observations.len()))),
+            average_degree: average(total_degree),
+            max_degree: top(degrees.iter().map(|(name, degree)| (*name, *degree))),
+            isolated_entities: degrees.values().filter(|d| **d == 0).count(),
+        }
     }
+}
 
-    pub async fn create_relations(&self, relations: Vec<Relation>) -> Result<Vec<Relation>> {
-        let mut graph = self.load_graph().await?;
-        let existing_relations: HashSet<_> = graph
-            .relations
-            .iter()
-            .map(|r| (&r.from, &r.to, &r.relation_type))
-            .collect();
+/// Entity count above which `graph_metrics` estimates the diameter unless asked for it exactly
+pub const EXACT_DIAMETER_LIMIT: usize = 1000;
 
-        let new_relations: Vec<Relation> = relations
-            .into_iter()
-            .filter(|r| !existing_relations.contains(&(&r.from, &r.to, &r.relation_type)))
+/// Breadth-first searches started when estimating the diameter
+const DIAMETER_SAMPLES: usize = 32;
+
+/// Shape of the graph, treating relations as undirected links between distinct entities
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GraphMetrics {
+    pub entities: usize,
+    /// Distinct pairs of entities linked by at least one relation
+    pub links: usize,
+    /// Links divided by the links possible between all entities, between 0 and 1
+    pub density: f64,
+    /// Mean share of each entity's neighbor pairs that are linked themselves,
+    /// counting entities with fewer than two neighbors as 0
+    pub average_clustering: f64,
+    /// Groups of entities connected to each other, isolated entities included
+    pub connected_components: usize,
+    /// Entities in the largest group
+    pub largest_component: usize,
+    /// Most links on the shortest path between any two connected entities
+    pub diameter: usize,
+    /// False when the diameter is a lower bound from sampled searches
+    pub diameter_exact: bool,
+}
+
+impl GraphMetrics {
+    fn from_graph(graph: &KnowledgeGraph, exact_diameter: bool) -> Self {
+        let mut names: Vec<&String> = graph.entities.keys().collect();
+        names.sort();
+        let index: HashMap<&String, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (*name, i))
             .collect();
+        let mut neighbors: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); names.len()];
+        for relation in &graph.relations {
+            if let (Some(&from), Some(&to)) = (index.get(&relation.from), index.get(&relation.to)) {
+                if from != to {
+                    neighbors[from].insert(to);
+                    neighbors[to].insert(from);
+                }
+            }
+        }
 
-        graph.relations.extend(new_relations.clone());
-        self.save_graph(&graph).await?;
+        let n = names.len();
+        let links = neighbors.iter().map(BTreeSet::len).sum::<usize>() / 2;
+        let density = if n < 2 {
+            0.0
+        } else {
+            links as f64 / (n * (n - 1) / 2) as f64
+        };
 
-        Ok(new_relations)
-    }
+        let clustering: f64 = neighbors
+            .iter()
+            .map(|adjacent| {
+                let k = adjacent.len();
+                if k < 2 {
+                    return 0.0;
+                }
+                let closed = adjacent
+                    .iter()
+                    .map(|&a| {
+                        adjacent
+                            .range(a + 1..)
+                            .filter(|b| neighbors[a].contains(b))
+                            .count()
+                    })
+                    .sum::<usize>();
+                closed as f64 / (k * (k - 1) / 2) as f64
+            })
+            .sum();
 
-    pub async fn add_observations(
-        &self,
-        observations: Vec<(String, Vec<String>)>,
-    ) -> Result<Vec<(String, Vec<String>)>> {
-        let mut graph = self.load_graph().await?;
-        let mut results = Vec::new();
+        // Distances from `source`, with the farthest entity reached
+        let bfs = |source: usize| -> (usize, usize) {
+            let mut distances = vec![usize::MAX; n];
+            distances[source] = 0;
+            let mut queue = VecDeque::from([source]);
+            let mut farthest = (0, source);
+            while let Some(current) = queue.pop_front() {
+                for &next in &neighbors[current] {
+                    if distances[next] == usize::MAX {
+                        distances[next] = distances[current] + 1;
+                        farthest = farthest.max((distances[next], next));
+                        queue.push_back(next);
+                    }
+                }
+            }
+            farthest
+        };
 
-        for (entity_name, contents) in observations {
-            let entity = graph
-                .entities
-                .get_mut(&entity_name)
-                .with_context(|| format!("Entity with name '{entity_name}' not found"))?;
+        let mut component = vec![usize::MAX; n];
+        let mut sizes = Vec::new();
+        for start in 0..n {
+            if component[start] != usize::MAX {
+                continue;
+            }
+            let id = sizes.len();
+            component[start] = id;
+            let mut stack = vec![start];
+            let mut size = 0;
+            while let Some(current) = stack.pop() {
+                size += 1;
+                for &next in &neighbors[current] {
+                    if component[next] == usize::MAX {
+                        component[next] = id;
+                        stack.push(next);
+                    }
+                }
+            }
+            sizes.push(size);
+        }
 
-            let existing_observations: HashSet<_> = entity.observations.iter().collect();
-            let new_observations: Vec<String> = contents
-                .into_iter()
-                .filter(|content| !existing_observations.contains(content))
-                .collect();
+        // Every entity as a source is exact but quadratic; otherwise a sweep from evenly spread
+        // sources and again from the farthest entity each one reaches gives a lower bound
+        let diameter_exact = exact_diameter || n <= EXACT_DIAMETER_LIMIT;
+        let diameter = if diameter_exact {
+            (0..n).map(|source| bfs(source).0).max().unwrap_or(0)
+        } else {
+            (0..n)
+                .step_by(n.div_ceil(DIAMETER_SAMPLES))
+                .map(|source| {
+                    let (distance, farthest) = bfs(source);
+                    distance.max(bfs(farthest).0)
+                })
+                .max()
+                .unwrap_or(0)
+        };
 
-            entity.observations.extend(new_observations.clone());
-            results.push((entity_name, new_observations));
+        Self {
+            entities: n,
+            links,
+            density,
+            average_clustering: if n == 0 { 0.0 } else { clustering / n as f64 },
+            connected_components: sizes.len(),
+            largest_component: sizes.iter().copied().max().unwrap_or(0),
+            diameter,
+            diameter_exact,
         }
+    }
+}
 
-        self.save_graph(&graph).await?;
-        Ok(results)
+/// Relations touching each entity, counting dangling relations only for the end that exists
+fn entity_degrees(graph: &KnowledgeGraph) -> HashMap<&String, usize> {
+    let mut degrees: HashMap<&String, usize> =
+        graph.entities.keys().map(|name| (name, 0)).collect();
+    for relation in &graph.relations {
+        for name in [&relation.from, &relation.to] {
+            if let Some(degree) = degrees.get_mut(name) {
+                *degree += 1;
+            }
+        }
     }
+    degrees
+}
 
-    pub async fn delete_entities(&self, entity_names: Vec<String>) -> Result<()> {
-        let mut graph = self.load_graph().await?;
-        let names_set: HashSet<_> = entity_names.iter().collect();
+/// Default number of entities returned by `core_concepts`
+pub const DEFAULT_CORE_CONCEPTS: usize = 10;
 
-        graph.entities.retain(|_, e| !names_set.contains(&e.name));
-        graph
-            .relations
-            .retain(|r| !names_set.contains(&r.from) && !names_set.contains(&r.to));
+/// Chance that PageRank's random walk follows a relation rather than jumping to any entity
+const PAGERANK_DAMPING: f64 = 0.85;
+/// PageRank stops iterating once the scores change by less than this in total
+const PAGERANK_TOLERANCE: f64 = 1e-9;
+const PAGERANK_MAX_ITERATIONS: usize = 100;
 
-        self.save_graph(&graph).await?;
-        Ok(())
-    }
+/// How `core_concepts` ranks entities
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Centrality {
+    /// Number of relations touching the entity
+    #[default]
+    Degree,
+    /// PageRank over relations from source to target, favouring entities pointed at by
+    /// other important entities
+    #[serde(rename = "pagerank")]
+    PageRank,
+}
 
-    pub async fn delete_observations(&self, deletions: Vec<(String, Vec<String>)>) -> Result<()> {
-        let mut graph = self.load_graph().await?;
+/// PageRank of every entity, following relations from source to target. Entities without
+/// outgoing relations spread their score evenly; dangling relations are ignored.
+fn pagerank(graph: &KnowledgeGraph) -> HashMap<&String, f64> {
+    let names: Vec<&String> = graph.entities.keys().collect();
+    let n = names.len();
+    if n == 0 {
+        return HashMap::new();
+    }
+    let index: HashMap<&String, usize> = names.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+    let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for relation in &graph.relations {
+        if let (Some(&from), Some(&to)) = (index.get(&relation.from), index.get(&relation.to)) {
+            outgoing[from].push(to);
+        }
+    }
 
-        for (entity_name, observations_to_delete) in deletions {
-            if let Some(entity) = graph.entities.get_mut(&entity_name) {
-                let delete_set: HashSet<_> = observations_to_delete.iter().collect();
-                entity.observations.retain(|o| !delete_set.contains(&o));
+    let mut scores = vec![1.0 / n as f64; n];
+    for _ in 0..PAGERANK_MAX_ITERATIONS {
+        let sink_share: f64 = (0..n)
+            .filter(|&i| outgoing[i].is_empty())
+            .map(|i| scores[i])
+            .sum::<f64>()
+            / n as f64;
+        let mut next = vec![(1.0 - PAGERANK_DAMPING) / n as f64 + PAGERANK_DAMPING * sink_share; n];
+        for (from, targets) in outgoing.iter().enumerate() {
+            let share = PAGERANK_DAMPING * scores[from] / targets.len().max(1) as f64;
+            for &to in targets {
+                next[to] += share;
             }
         }
-
-        self.save_graph(&graph).await?;
-        Ok(())
+        let change: f64 = next.iter().zip(&scores).map(|(a, b)| (a - b).abs()).sum();
+        scores = next;
+        if change < PAGERANK_TOLERANCE {
+            break;
+        }
     }
 
-    pub async fn delete_relations(&self, relations: Vec<Relation>) -> Result<()> {
-        let mut graph = self.load_graph().await?;
-        let relations_to_delete: HashSet<_> = relations
-            .iter()
-            .map(|r| (&r.from, &r.to, &r.relation_type))
-            .collect();
-
-        graph
-            .relations
-            .retain(|r| !relations_to_delete.contains(&(&r.from, &r.to, &r.relation_type)));
+    names.into_iter().zip(scores).collect()
+}
 
-        self.save_graph(&graph).await?;
-        Ok(())
-    }
+/// An entity ranked by `core_concepts`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CoreConcept {
+    pub name: String,
+    #[serde(rename = "entityType")]
+    pub entity_type: String,
+    /// Degree or PageRank, depending on the centrality used
+    pub score: f64,
+}
 
-    pub async fn read_graph(&self) -> Result<Box<KnowledgeGraph>> {
-        self.load_graph().await
-    }
+/// The skeleton of the graph: its most central entities and the relations among them
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CoreConcepts {
+    /// Most central first, ties broken by name
+    pub concepts: Vec<CoreConcept>,
+    /// Relations whose source and target are both among `concepts`
+    pub relations: Vec<Relation>,
+}
 
-    pub async fn open_nodes(&self, names: Vec<String>) -> Result<Box<KnowledgeGraph>> {
-        let graph = self.load_graph().await?;
-        let names_set: HashSet<_> = names.iter().collect();
+impl CoreConcepts {
+    /// The `k` most central entities with at least one relation
+    fn from_graph(graph: &KnowledgeGraph, k: usize, centrality: Centrality) -> Self {
+        let degrees = entity_degrees(graph);
+        let scores: HashMap<&String, f64> = match centrality {
+            Centrality::Degree => degrees.iter().map(|(n, d)| (*n, *d as f64)).collect(),
+            Centrality::PageRank => pagerank(graph),
+        };
 
-        let filtered_entities: Vec<Entity> = graph
+        let mut concepts: Vec<CoreConcept> = graph
             .entities
             .values()
-            .filter(|&e| names_set.contains(&e.name))
-            .cloned()
+            .filter(|e| degrees.get(&e.name).is_some_and(|d| *d > 0))
+            .map(|e| CoreConcept {
+                name: e.name.clone(),
+                entity_type: e.entity_type.clone(),
+                score: scores.get(&e.name).copied().unwrap_or(0.0),
+            })
             .collect();
+        concepts.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        concepts.truncate(k);
 
-        let filtered_entity_names: HashSet<_> = filtered_entities.iter().map(|e| &e.name).collect();
-
-        let filtered_relations: Vec<Relation> = graph
+        let names: HashSet<&str> = concepts.iter().map(|c| c.name.as_str()).collect();
+        let relations = graph
             .relations
-            .into_iter()
-            .filter(|r| {
-                filtered_entity_names.contains(&r.from) && filtered_entity_names.contains(&r.to)
-            })
+            .iter()
+            .filter(|r| names.contains(r.from.as_str()) && names.contains(r.to.as_str()))
+            .cloned()
             .collect();
 
-        Ok(Box::new(KnowledgeGraph {
-            entities: filtered_entities
-                .into_iter()
-                .map(|e| (e.name.clone(), e))
-                .collect(),
-            relations: filtered_relations,
-        }))
+        Self {
+            concepts,
+            relations,
+        }
     }
+}
 
-    pub async fn get_stats(&self) -> Result<(usize, usize)> {
-        let graph = self.load_graph().await?;
-        Ok((graph.entities.len(), graph.relations.len()))
+/// Size of the graph and its entity type distribution
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphCounts {
+    pub entities: usize,
+    pub relations: usize,
+    pub observations: usize,
+    /// Number of entities of each type
+    pub entity_types: BTreeMap<String, usize>,
+}
+
+impl GraphCounts {
+    fn from_graph(graph: &KnowledgeGraph) -> Self {
+        let mut entity_types = BTreeMap::new();
+        for entity in graph.entities.values() {
+            *entity_types.entry(entity.entity_type.clone()).or_insert(0) += 1;
+        }
+        Self {
+            entities: graph.entities.len(),
+            relations: graph.relations.len(),
+            observations: graph.entities.values().map(|e| e.observations.len()).sum(),
+            entity_types,
+        }
     }
+}
 
-    pub async fn search_nodes(
-        &self,
-        query: &str,
-        limit: Option<usize>,
+/// Default number of entities listed per ranking in `overview`
+pub const DEFAULT_OVERVIEW_LIMIT: usize = 5;
+
+/// An entity and when it last changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentEntity {
+    pub name: String,
+    pub entity_type: String,
+    pub updated_at: i64,
+}
+
+/// Snapshot of the graph to orient in an existing memory, from `overview`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphOverview {
+    pub entities: usize,
+    pub relations: usize,
+    pub observations: usize,
+    /// Entities touched by the most relations, most first
+    pub most_connected: Vec<NamedCount>,
+    /// Number of entities of each type
+    pub entity_types: BTreeMap<String, usize>,
+    /// Entities changed most recently, newest first; entities without timestamps are left out
+    pub recently_updated: Vec<RecentEntity>,
+    /// Entities without any relations
+    pub orphans: usize,
+}
+
+impl GraphOverview {
+    /// Rankings hold at most `limit` entities each, ties broken by name
+    fn from_graph(graph: &KnowledgeGraph, limit: usize) -> Self {
+        let degrees = entity_degrees(graph);
+        let mut most_connected: Vec<NamedCount> = degrees
+            .iter()
+            .filter(|(_, degree)| **degree > 0)
+            .map(|(name, degree)| NamedCount {
+                name: (*name).clone(),
+                count: *degree,
+            })
+            .collect();
+        most_connected.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        most_connected.truncate(limit);
+
+        let counts = GraphCounts::from_graph(graph);
+        let mut recently_updated: Vec<RecentEntity> = graph
+            .entities
+            .values()
+            .filter_map(|e| {
+                e.updated_at
+                    .or(e.created_at)
+                    .map(|updated_at| RecentEntity {
+                        name: e.name.clone(),
+                        entity_type: e.entity_type.clone(),
+                        updated_at,
+                    })
+            })
+            .collect();
+        recently_updated.sort_by(|a, b| {
+            b.updated_at
+                .cmp(&a.updated_at)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        recently_updated.truncate(limit);
+
+        Self {
+            entities: counts.entities,
+            relations: counts.relations,
+            observations: counts.observations,
+            most_connected,
+            entity_types: counts.entity_types,
+            recently_updated,
+            orphans: degrees.values().filter(|d| **d == 0).count(),
+        }
+    }
+}
+
+/// Result of `search_nodes`: the matching entities with the relations touching them
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SearchResults {
+    pub entities: HashMap<String, Entity>,
+    pub relations: Vec<Relation>,
+    /// Results dropped as near-duplicates of an entity named exactly like the query
+    #[serde(skip_serializing_if = "is_zero")]
+    pub near_duplicates_suppressed: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// Result of `open_nodes`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OpenedNodes {
+    /// Found entities, in the order they were requested
+    pub entities: Vec<Entity>,
+    /// Relations between the found entities
+    pub relations: Vec<Relation>,
+    /// Requested names that matched no entity
+    pub not_found: Vec<String>,
+    /// Requested names that only matched an entity approximately
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fuzzy_matches: Vec<FuzzyMatch>,
+}
+
+/// A requested name resolved to a differently spelled entity
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FuzzyMatch {
+    pub requested: String,
+    pub matched: String,
+    pub similarity: f32,
+}
+
+/// What `add_observations` did for one entity
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AddedObservations {
+    pub entity_name: String,
+    /// Observations that were stored
+    pub added: Vec<Observation>,
+    /// New observations skipped because the entity already has them
+    pub already_present: Vec<String>,
+    /// New observations skipped because an existing one already contains them
+    pub dropped: Vec<String>,
+    /// Existing observations removed because a new one contains them
+    pub replaced: Vec<String>,
+}
+
+/// Result of `split_entity`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SplitEntity {
+    /// The original entity, without the moved observations
+    pub original: Entity,
+    /// The entity holding the moved observations
+    pub created: Entity,
+    /// Relations now attached to the new entity instead of the original
+    pub relinked: Vec<Relation>,
+}
+
+/// Proposed merge of the entities matching a query, computed without changing the graph
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConsolidationPreview {
+    /// Name the merged entity would keep: the match with the most observations
+    pub canonical_name: String,
+    /// Most common type among the matches
+    pub entity_type: String,
+    /// Matching entities, best match first
+    pub merged: Vec<String>,
+    /// Union of their observations, without duplicates
+    pub observations: Vec<Observation>,
+    /// Their relations, pointed at the canonical name, without duplicates or self-links
+    pub relations: Vec<Relation>,
+}
+
+/// A route between two entities, following relations in either direction
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphPath {
+    /// Entity names from start to end, none repeated
+    pub entities: Vec<String>,
+    /// The relation joining each entity to the next, as stored
+    pub relations: Vec<Relation>,
+}
+
+/// Longest path `find_paths` looks for, whatever the caller asks
+pub const MAX_PATH_LENGTH: usize = 8;
+
+/// Most paths `find_paths` returns, whatever the caller asks
+pub const MAX_PATHS: usize = 100;
+
+/// Depth-first walk collecting simple paths of one exact length
+struct PathSearch<'a> {
+    /// Neighbors of each entity with the relation reaching them, sorted by name and type
+    edges: HashMap<&'a str, Vec<(&'a str, &'a Relation)>>,
+    /// Fewest relations from each entity to `to`; entities missing can't reach it
+    distances: HashMap<&'a str, usize>,
+    to: &'a str,
+    max_paths: usize,
+    paths: Vec<GraphPath>,
+}
+
+impl<'a> PathSearch<'a> {
+    fn walk(
+        &mut self,
+        remaining: usize,
+        entities: &mut Vec<&'a str>,
+        relations: &mut Vec<&'a Relation>,
+    ) {
+        let current = entities[entities.len() - 1];
+        if remaining == 0 {
+            if current == self.to {
+                self.paths.push(GraphPath {
+                    entities: entities.iter().map(|e| e.to_string()).collect(),
+                    relations: relations.iter().map(|&r| r.clone()).collect(),
+                });
+            }
+            return;
+        }
+
+        let Some(neighbors) = self.edges.get(current).cloned() else {
+            return;
+        };
+        for (next, relation) in neighbors {
+            if self.paths.len() >= self.max_paths {
+                return;
+            }
+            // The end may only be reached by the last step, and only from entities close enough
+            if entities.contains(&next)
+                || (next == self.to && remaining > 1)
+                || self.distances.get(next).is_none_or(|d| *d > remaining - 1)
+            {
+                continue;
+            }
+            entities.push(next);
+            relations.push(relation);
+            self.walk(remaining - 1, entities, relations);
+            entities.pop();
+            relations.pop();
+        }
+    }
+}
+
+/// An entity reached from a seed by following relations in either direction
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RelatedEntity {
+    #[serde(flatten)]
+    pub entity: Entity,
+    /// Hops from the seed
+    pub distance: usize,
+    /// Relations touching the entity
+    pub degree: usize,
+}
+
+/// An entity whose observations share terms with a seed entity's
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimilarEntity {
+    #[serde(flatten)]
+    pub entity: Entity,
+    /// Jaccard similarity of the two entities' observation terms, between 0 and 1
+    pub similarity: f32,
+    /// Terms found in the observations of both, alphabetically
+    pub shared_terms: Vec<String>,
+}
+
+/// Number of relations touching an entity, by direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Degree {
+    pub incoming: usize,
+    pub outgoing: usize,
+}
+
+/// An entity with its relations summarized, ready to narrate
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntityDescription {
+    #[serde(flatten)]
+    pub entity: Entity,
+    pub observation_count: usize,
+    /// Targets of the entity's relations, by relation type
+    pub outgoing: BTreeMap<String, Vec<String>>,
+    /// Sources of relations pointing at the entity, by relation type
+    pub incoming: BTreeMap<String, Vec<String>>,
+    /// Every entity related in either direction, sorted by name
+    pub neighbors: Vec<String>,
+}
+
+/// When the graph last changed, for clients syncing incrementally
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModificationInfo {
+    /// Number of saves since the server started
+    pub version: u64,
+    /// Unix timestamp (seconds) of the last save, or of the memory file's last write
+    pub last_modified: Option<i64>,
+}
+
+/// Default number of mutations kept in the audit log
+pub const DEFAULT_AUDIT_LOG_SIZE: usize = 1000;
+
+/// A mutation of the graph, as recorded in the audit log
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEntry {
+    /// Unix timestamp of the change
+    pub timestamp: i64,
+    /// The manager operation that made the change, e.g. `create_entities`
+    pub operation: &'static str,
+    /// Names of the entities created, changed, or removed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<String>,
+    /// Relations created, changed, or removed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub relations: Vec<Relation>,
+}
+
+/// What a destructive operation removed, or would remove when run as a dry run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeletionReport {
+    /// Names of the removed entities
+    pub entities: Vec<String>,
+    /// Removed relations, including those cascaded from removed entities
+    pub relations: Vec<Relation>,
+    /// Removed observation contents, grouped by entity name
+    pub observations: Vec<(String, Vec<String>)>,
+    /// Relations kept although an entity they connect was removed (`keep_relations`)
+    pub dangling_relations: Vec<Relation>,
+}
+
+/// Structural problems found in the stored graph
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// True when no problems were found
+    pub valid: bool,
+    /// Relations whose `from` or `to` entity does not exist
+    pub dangling_relations: Vec<Relation>,
+    /// Extra copies of relations stored more than once
+    pub duplicate_relations: Vec<Relation>,
+    /// Number of entities with an empty or whitespace-only name
+    pub empty_entity_names: usize,
+    /// Names stored more than once; only the last record survives loading
+    pub duplicate_entity_names: Vec<String>,
+    /// Entities holding empty or whitespace-only observations
+    pub entities_with_empty_observations: Vec<String>,
+}
+
+impl ValidationReport {
+    /// Inspect raw entity and relation records for structural problems
+    fn from_items(entities: &[Entity], relations: &[Relation]) -> Self {
+        let mut report = ValidationReport::default();
+
+        let mut seen_names = HashSet::new();
+        let mut duplicate_names = HashSet::new();
+        for entity in entities {
+            if entity.name.trim().is_empty() {
+                report.empty_entity_names += 1;
+            }
+            if !seen_names.insert(entity.name.as_str())
+                && duplicate_names.insert(entity.name.as_str())
+            {
+                report.duplicate_entity_names.push(entity.name.clone());
+            }
+            if entity
+                .observations
+                .iter()
+                .any(|o| o.content.trim().is_empty())
+            {
+                report
+                    .entities_with_empty_observations
+                    .push(entity.name.clone());
+            }
+        }
+
+        let mut seen_relations = HashSet::new();
+        for relation in relations {
+            if !seen_names.contains(relation.from.as_str())
+                || !seen_names.contains(relation.to.as_str())
+            {
+                report.dangling_relations.push(relation.clone());
+            }
+            if !seen_relations.insert((&relation.from, &relation.to, &relation.relation_type)) {
+                report.duplicate_relations.push(relation.clone());
+            }
+        }
+
+        report.valid = report.dangling_relations.is_empty()
+            && report.duplicate_relations.is_empty()
+            && report.empty_entity_names == 0
+            && report.duplicate_entity_names.is_empty()
+            && report.entities_with_empty_observations.is_empty();
+
+        report
+    }
+}
+
+/// Borrowed counterpart of `GraphItem`, so serialization doesn't clone the graph
+#[derive(Serialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+enum GraphItemRef<'a> {
+    Entity(&'a Entity),
+    Relation(&'a Relation),
+}
+
+/// Parse one JSONL line into the matching collection, skipping blank lines
+fn parse_line(line: &str, entities: &mut Vec<Entity>, relations: &mut Vec<Relation>) -> Result<()> {
+    if line.trim().is_empty() {
+        return Ok(());
+    }
+
+    let item: GraphItem =
+        serde_json::from_str(line).with_context(|| format!("Failed to parse JSON line: {line}"))?;
+    match item {
+        GraphItem::Entity(entity) => entities.push(entity),
+        GraphItem::Relation(relation) => relations.push(relation),
+    }
+
+    Ok(())
+}
+
+/// Parse the JSONL memory file format, one `GraphItem` per line
+fn parse_items(data: &str) -> Result<(Vec<Entity>, Vec<Relation>)> {
+    let mut entities = Vec::new();
+    let mut relations = Vec::new();
+
+    for line in data.lines() {
+        parse_line(line, &mut entities, &mut relations)?;
+    }
+
+    Ok((entities, relations))
+}
+
+/// Lazily serialize the graph as JSONL lines (without trailing newline), entities first
+pub(crate) fn jsonl_lines(graph: &KnowledgeGraph) -> impl Iterator<Item = Result<String>> + '_ {
+    let entities = graph.entities.values().map(|entity| {
+        serde_json::to_string(&GraphItemRef::Entity(entity))
+            .with_context(|| format!("Failed to serialize entity {}", entity.name))
+    });
+    let relations = graph.relations.iter().map(|relation| {
+        serde_json::to_string(&GraphItemRef::Relation(relation)).with_context(|| {
+            format!(
+                "Failed to serialize relation {} -> {}",
+                relation.from, relation.to
+            )
+        })
+    });
+
+    entities.chain(relations)
+}
+
+/// The entities accepted by `keep`, with the relations among them
+fn subgraph(graph: KnowledgeGraph, keep: impl Fn(&Entity) -> bool) -> KnowledgeGraph {
+    let entities: HashMap<String, Entity> = graph
+        .entities
+        .into_iter()
+        .filter(|(_, entity)| keep(entity))
+        .collect();
+    let relations = graph
+        .relations
+        .into_iter()
+        .filter(|r| entities.contains_key(&r.from) && entities.contains_key(&r.to))
+        .collect();
+
+    KnowledgeGraph {
+        entities,
+        relations,
+    }
+}
+
+/// Write the graph as JSONL one line at a time
+async fn write_jsonl<W: AsyncWrite + Unpin>(graph: &KnowledgeGraph, writer: &mut W) -> Result<()> {
+    for line in jsonl_lines(graph) {
+        let mut line = line?;
+        line.push('\n');
+        writer
+            .write_all(line.as_bytes())
+            .await
+            .with_context(|| "Failed to write graph to file")?;
+    }
+
+    writer
+        .flush()
+        .await
+        .with_context(|| "Failed to write graph to file")
+}
+
+/// Remove the relations whose source or target entity doesn't exist, returning them
+fn remove_dangling_relations(graph: &mut KnowledgeGraph) -> Vec<Relation> {
+    let mut removed = Vec::new();
+    let entities = &graph.entities;
+    graph.relations.retain(|r| {
+        let dangling = !entities.contains_key(&r.from) || !entities.contains_key(&r.to);
+        if dangling {
+            removed.push(r.clone());
+        }
+        !dangling
+    });
+    removed
+}
+
+/// Collect raw records into a graph; later entities replace earlier ones with the same name
+fn graph_from_items(entities: Vec<Entity>, relations: Vec<Relation>) -> KnowledgeGraph {
+    KnowledgeGraph {
+        entities: entities.into_iter().map(|e| (e.name.clone(), e)).collect(),
+        relations,
+    }
+}
+
+/// Parse either a serialized `KnowledgeGraph` JSON document or the JSONL memory file format
+fn parse_graph(data: &str) -> Result<KnowledgeGraph> {
+    match serde_json::from_str::<KnowledgeGraph>(data) {
+        Ok(graph) => Ok(graph),
+        Err(_) => {
+            let (entities, relations) = parse_items(data)?;
+            Ok(graph_from_items(entities, relations))
+        }
+    }
+}
+
+/// Where `add_observations` places new observations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ObservationOrder {
+    /// After the existing observations
+    #[default]
+    Append,
+    /// Before the existing observations, keeping the most recent first
+    Prepend,
+}
+
+/// Which timestamp `entities_in_range` compares
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeField {
+    /// When the entity was created
+    #[default]
+    CreatedAt,
+    /// When the entity was last changed
+    UpdatedAt,
+}
+
+impl TimeField {
+    fn of(self, entity: &Entity) -> Option<i64> {
+        match self {
+            TimeField::CreatedAt => entity.created_at,
+            TimeField::UpdatedAt => entity.updated_at,
+        }
+    }
+}
+
+/// How `sort_observations` reorders an entity's observations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ObservationSort {
+    /// By content
+    #[default]
+    Alphabetical,
+    /// Reverse the stored order, turning oldest-first into most-recent-first and back
+    Reverse,
+}
+
+/// What `create_entities` does with an entity whose name matches an existing one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// Leave the existing entity alone and drop the new one
+    #[default]
+    Skip,
+    /// Add the new entity's observations to the existing one
+    Merge,
+}
+
+impl DuplicatePolicy {
+    /// Parse a `MEMORY_DUPLICATE_POLICY` value, defaulting to `Skip`
+    pub fn from_env_value(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_lowercase()).as_deref() {
+            Some("merge") => DuplicatePolicy::Merge,
+            _ => DuplicatePolicy::Skip,
+        }
+    }
+}
+
+/// A created entity whose name matched an existing entity under the name policy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NameCollision {
+    /// The name given for the new entity
+    pub requested: String,
+    /// The name of the entity it matched
+    pub existing: String,
+    /// What was done about it
+    pub policy: DuplicatePolicy,
+    /// Observations merged into the existing entity
+    pub observations_added: usize,
+}
+
+/// What `validate_entities` found about one entity of a batch
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntityValidation {
+    /// The name given, unchanged
+    pub name: String,
+    /// False when there are errors
+    pub valid: bool,
+    /// Problems to fix first: the write would fail or store a broken entity
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// Things `create_entities` handles but that may not be intended
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// The stored or earlier batch entity this one collides with under the name policy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+}
+
+/// Result of `create_entities`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CreateReport {
+    pub created: Vec<Entity>,
+    pub collisions: Vec<NameCollision>,
+}
+
+/// Result of `add_relation_observations`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AddedRelationObservations {
+    /// The relation as stored, with all its observations after the change
+    pub relation: Relation,
+    pub added: Vec<String>,
+    pub already_present: Vec<String>,
+}
+
+/// Result of `create_relations`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CreatedRelations {
+    /// Relations added, leaving out those already present
+    pub created: Vec<Relation>,
+    /// Relations of single-valued types removed to make room for the new ones
+    pub replaced: Vec<Relation>,
+}
+
+/// Result of `connect_or_create`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConnectReport {
+    /// Entities that were missing and have been created
+    pub created_entities: Vec<Entity>,
+    /// Names of the entities that already existed, left unchanged
+    pub existing_entities: Vec<String>,
+    /// The relation, with the entity names as stored
+    pub relation: Relation,
+    /// False when the relation was already present
+    pub relation_created: bool,
+    /// Relations of single-valued types removed to make room for the new one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub replaced: Vec<Relation>,
+}
+
+/// Base directory used to resolve a relative `MEMORY_FILE_PATH`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathBase {
+    /// The current working directory
+    #[default]
+    Cwd,
+    /// The directory containing the executable
+    Exe,
+}
+
+impl PathBase {
+    /// Parse a `MEMORY_PATH_BASE` value, defaulting to the working directory
+    pub fn from_env_value(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_lowercase()).as_deref() {
+            Some("exe") => PathBase::Exe,
+            _ => PathBase::Cwd,
+        }
+    }
+}
+
+/// How entity names are normalized before they are compared
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NamePolicy {
+    /// Names match only when identical
+    None,
+    /// Leading and trailing whitespace is ignored
+    #[default]
+    Trim,
+    /// Whitespace is trimmed and inner runs of whitespace count as a single space
+    TrimCollapseWhitespace,
+    /// Like `TrimCollapseWhitespace`, and letter case is ignored
+    Lowercase,
+}
+
+impl NamePolicy {
+    /// Parse a `MEMORY_NAME_POLICY` value, defaulting to `Trim`
+    pub fn from_env_value(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_lowercase()).as_deref() {
+            Some("none") => NamePolicy::None,
+            Some("trim_collapse_whitespace") | Some("collapse") => {
+                NamePolicy::TrimCollapseWhitespace
+            }
+            Some("lowercase") => NamePolicy::Lowercase,
+            _ => NamePolicy::Trim,
+        }
+    }
+
+    /// The form used to decide whether two names refer to the same entity
+    pub fn canonical(&self, name: &str) -> String {
+        match self {
+            NamePolicy::None => name.to_string(),
+            NamePolicy::Trim => name.trim().to_string(),
+            NamePolicy::TrimCollapseWhitespace => {
+                name.split_whitespace().collect::<Vec<_>>().join(" ")
+            }
+            NamePolicy::Lowercase => name
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase(),
+        }
+    }
+
+    /// The form stored for a newly created entity
+    fn display(&self, name: &str) -> String {
+        match self {
+            NamePolicy::None => name.to_string(),
+            _ => name.trim().to_string(),
+        }
+    }
+}
+
+/// Maps canonical entity names to the names they are stored under
+struct NameIndex {
+    policy: NamePolicy,
+    /// Also ignore letter case, whatever the policy
+    ignore_case: bool,
+    names: HashMap<String, String>,
+}
+
+impl NameIndex {
+    fn new(policy: NamePolicy, graph: &KnowledgeGraph) -> Self {
+        Self::with_case(policy, false, graph)
+    }
+
+    /// An index that also treats names differing only in letter case as the same when
+    /// `ignore_case` is set, as `create_entities` does to catch duplicates
+    fn with_case(policy: NamePolicy, ignore_case: bool, graph: &KnowledgeGraph) -> Self {
+        let mut index = Self {
+            policy,
+            ignore_case,
+            names: HashMap::new(),
+        };
+        for name in graph.entities.keys() {
+            index.names.insert(index.key(name), name.clone());
+        }
+        index
+    }
+
+    fn key(&self, name: &str) -> String {
+        let canonical = self.policy.canonical(name);
+        if self.ignore_case {
+            canonical.to_lowercase()
+        } else {
+            canonical
+        }
+    }
+
+    /// The stored name matching `name`, or the name a new entity would be stored under
+    fn resolve(&self, name: &str) -> String {
+        self.names
+            .get(&self.key(name))
+            .cloned()
+            .unwrap_or_else(|| self.policy.display(name))
+    }
+
+    /// Record `name` as stored, returning false if an equivalent name already was
+    fn insert(&mut self, name: &str) -> bool {
+        match self.names.entry(self.key(name)) {
+            std::collections::hash_map::Entry::Occupied(_) => false,
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(name.to_string());
+                true
+            }
+        }
+    }
+
+    fn resolve_relation(&self, relation: Relation) -> Relation {
+        Relation {
+            from: self.resolve(&relation.from),
+            to: self.resolve(&relation.to),
+            ..relation
+        }
+    }
+}
+
+/// Resolve the memory file location. Absolute paths are used as-is, relative paths
+/// are joined to `base`, and without a path `memory.jsonl` next to the executable is used.
+fn resolve_memory_path(
+    path_env: Option<&str>,
+    base: PathBase,
+    cwd: &Path,
+    exe_dir: &Path,
+) -> PathBuf {
+    match path_env {
+        Some(path_env) => {
+            let path = PathBuf::from(path_env);
+            if path.is_absolute() {
+                path
+            } else {
+                match base {
+                    PathBase::Cwd => cwd.join(path),
+                    PathBase::Exe => exe_dir.join(path),
+                }
+            }
+        }
+        None => exe_dir.join("memory.jsonl"),
+    }
+}
+
+/// Backing store for the knowledge graph
+enum GraphStore {
+    /// JSONL file on disk, read and rewritten on every operation
+    File(PathBuf),
+    /// Process-local graph that is never written to disk
+    Memory(Mutex<KnowledgeGraph>),
+}
+
+/// Interpret an environment variable as a boolean flag
+fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// The settings a manager runs with, once environment variables, config files and defaults
+/// are resolved
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EffectiveConfig {
+    /// Memory file, `None` for a graph kept only in memory
+    pub memory_path: Option<PathBuf>,
+    /// Files merged read-only into reads and searches
+    pub federated_paths: Vec<PathBuf>,
+    pub write_back: bool,
+    pub write_debounce_ms: Option<u64>,
+    /// Journal of changes not yet written to the memory file
+    pub journal_path: Option<PathBuf>,
+    pub prune_dangling_on_load: bool,
+    pub io_max_attempts: u32,
+    pub name_policy: NamePolicy,
+    pub duplicate_policy: DuplicatePolicy,
+    pub case_insensitive_duplicates: bool,
+    pub snapshot_dir: Option<PathBuf>,
+    pub schema_path: Option<PathBuf>,
+    /// Whether the ontology rejects types it doesn't list
+    pub strict_schema: bool,
+    pub search_config_path: Option<PathBuf>,
+    /// Ranker settings after the search config file is applied over the defaults
+    pub search: SearchConfig,
+    pub default_search_limit: usize,
+    /// Shorter query terms are not scored
+    pub search_min_term_length: usize,
+    pub search_stop_words: Vec<String>,
+    pub search_parallel_threshold: usize,
+    /// Size of the dedicated search pool, `None` for rayon's global pool
+    pub search_threads: Option<usize>,
+    /// Recent searches whose results are reused until the graph changes
+    pub search_cache_size: usize,
+    pub audit_log_size: usize,
+}
+
+/// Default number of attempts at reading or writing the memory file
+pub const DEFAULT_IO_MAX_ATTEMPTS: u32 = 3;
+
+/// How reads and writes of the memory file are retried after a transient failure,
+/// such as a file briefly locked by an antivirus scanner or a network mount hiccup
+#[derive(Debug, Clone, Copy)]
+struct IoRetry {
+    /// Total attempts, including the first
+    max_attempts: u32,
+    /// Wait before the first retry, doubled for each one after it
+    base_delay: Duration,
+}
+
+impl Default for IoRetry {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_IO_MAX_ATTEMPTS,
+            base_delay: Duration::from_millis(50),
+        }
+    }
+}
+
+impl IoRetry {
+    /// Run `op` until it succeeds, fails with a non-transient error, or runs out of attempts
+    async fn run<T, F, Fut>(&self, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let mut delay = self.base_delay;
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Err(e) if attempt < self.max_attempts && is_transient_io_error(&e) => {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether an error was caused by an IO failure that may go away on its own
+fn is_transient_io_error(error: &anyhow::Error) -> bool {
+    use std::io::ErrorKind;
+
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .any(|e| {
+            matches!(
+                e.kind(),
+                ErrorKind::Interrupted
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::TimedOut
+                    | ErrorKind::PermissionDenied
+                    | ErrorKind::ResourceBusy
+            )
+        })
+}
+
+/// Read the entity and relation records of a JSONL memory file; a missing file is empty
+async fn read_items_file(memory_file_path: &Path) -> Result<(Vec<Entity>, Vec<Relation>)> {
+    let file = match fs::File::open(memory_file_path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), Vec::new())),
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("Failed to read graph from {}", memory_file_path.display())
+            })
+        }
+    };
+
+    let mut entities = Vec::new();
+    let mut relations = Vec::new();
+    let mut lines = BufReader::new(file).lines();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .with_context(|| format!("Failed to read graph from {}", memory_file_path.display()))?
+    {
+        parse_line(&line, &mut entities, &mut relations)?;
+    }
+
+    Ok((entities, relations))
+}
+
+/// Write the graph to a JSONL file, replacing its contents
+async fn write_graph_file(memory_file_path: &Path, graph: &KnowledgeGraph) -> Result<()> {
+    let file = fs::File::create(memory_file_path)
+        .await
+        .with_context(|| format!("Failed to create file {}", memory_file_path.display()))?;
+    write_jsonl(graph, &mut BufWriter::new(file)).await
+}
+
+/// A mutation of the primary graph, appended to the journal before it is held in memory.
+/// Entities and relations are recorded with their full new contents, so replaying an entry
+/// that already reached the memory file leaves the graph unchanged.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JournalEntry {
+    /// Entities created or changed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    entities: Vec<Entity>,
+    /// Names of the removed entities
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    removed_entities: Vec<String>,
+    /// Relations created or changed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    relations: Vec<Relation>,
+    /// Removed relations
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    removed_relations: Vec<Relation>,
+}
+
+impl JournalEntry {
+    /// The changes that turn `previous` into `current`
+    fn between(previous: &KnowledgeGraph, current: &KnowledgeGraph) -> Self {
+        let entities = current
+            .entities
+            .values()
+            .filter(|entity| previous.entities.get(&entity.name) != Some(entity))
+            .cloned()
+            .collect();
+        let removed_entities = previous
+            .entities
+            .keys()
+            .filter(|name| !current.entities.contains_key(*name))
+            .cloned()
+            .collect();
+
+        let previous_relations: HashMap<&Relation, &Relation> =
+            previous.relations.iter().map(|r| (r, r)).collect();
+        let current_relations: HashSet<&Relation> = current.relations.iter().collect();
+        let relations = current
+            .relations
+            .iter()
+            .filter(|relation| {
+                previous_relations
+                    .get(relation)
+                    .is_none_or(|old| old.observations != relation.observations)
+            })
+            .cloned()
+            .collect();
+        let removed_relations = previous
+            .relations
+            .iter()
+            .filter(|relation| !current_relations.contains(relation))
+            .cloned()
+            .collect();
+
+        Self {
+            entities,
+            removed_entities,
+            relations,
+            removed_relations,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+            && self.removed_entities.is_empty()
+            && self.relations.is_empty()
+            && self.removed_relations.is_empty()
+    }
+
+    fn apply(self, graph: &mut KnowledgeGraph) {
+        for name in &self.removed_entities {
+            graph.entities.remove(name);
+        }
+        for entity in self.entities {
+            graph.entities.insert(entity.name.clone(), entity);
+        }
+
+        let removed: HashSet<Relation> = self.removed_relations.into_iter().collect();
+        graph
+            .relations
+            .retain(|relation| !removed.contains(relation));
+        for relation in self.relations {
+            match graph.relations.iter_mut().find(|r| **r == relation) {
+                Some(existing) => *existing = relation,
+                None => graph.relations.push(relation),
+            }
+        }
+    }
+}
+
+/// Append an entry to the journal and sync it to disk. A torn last line, left by a crash in
+/// the middle of an earlier append that was never acknowledged, is cut off first.
+async fn append_journal(journal_path: &Path, entry: &JournalEntry) -> Result<()> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    let write_error = || format!("Failed to write journal {}", journal_path.display());
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .write(true)
+        .truncate(false)
+        .open(journal_path)
+        .await
+        .with_context(|| format!("Failed to open journal {}", journal_path.display()))?;
+    let len = file.metadata().await.with_context(write_error)?.len();
+    if len > 0 {
+        let mut last = [0u8];
+        file.seek(SeekFrom::End(-1))
+            .await
+            .with_context(write_error)?;
+        file.read_exact(&mut last).await.with_context(write_error)?;
+        if last[0] != b'\n' {
+            let data = fs::read(journal_path).await.with_context(write_error)?;
+            let complete = data.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+            file.set_len(complete as u64)
+                .await
+                .with_context(write_error)?;
+        }
+    }
+    file.seek(SeekFrom::End(0))
+        .await
+        .with_context(write_error)?;
+    file.write_all(line.as_bytes())
+        .await
+        .with_context(write_error)?;
+    file.sync_data().await.with_context(write_error)
+}
+
+/// Read the journal's entries, oldest first; a missing journal is empty.
+/// A torn last line, left by a crash in the middle of an append, is skipped and reported
+/// through the returned flag.
+async fn read_journal(journal_path: &Path) -> Result<(Vec<JournalEntry>, bool)> {
+    let data = match fs::read_to_string(journal_path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), false)),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read journal {}", journal_path.display()))
+        }
+    };
+
+    let lines: Vec<&str> = data.lines().filter(|l| !l.trim().is_empty()).collect();
+    let mut entries = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) if i + 1 == lines.len() && !data.ends_with('\n') => return Ok((entries, true)),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Invalid entry in journal {}", journal_path.display())
+                })
+            }
+        }
+    }
+    Ok((entries, false))
+}
+
+/// Empty the journal once the memory file holds every change it records (a checkpoint)
+async fn truncate_journal(journal_path: &Path) -> Result<()> {
+    match fs::metadata(journal_path).await {
+        Ok(_) => fs::write(journal_path, b"")
+            .await
+            .with_context(|| format!("Failed to truncate journal {}", journal_path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => {
+            Err(e).with_context(|| format!("Failed to truncate journal {}", journal_path.display()))
+        }
+    }
+}
+
+/// Changes held in memory until they are written to the memory file
+#[derive(Default)]
+struct PendingWrites {
+    graph: Mutex<Option<KnowledgeGraph>>,
+    /// Bumped on every buffered save, so a debounced flush only fires after the last one
+    generation: AtomicU64,
+    /// Number of times the memory file was written
+    disk_writes: AtomicU64,
+}
+
+impl PendingWrites {
+    async fn write(
+        &self,
+        memory_file_path: &Path,
+        graph: &KnowledgeGraph,
+        retry: IoRetry,
+    ) -> Result<()> {
+        retry
+            .run(|| write_graph_file(memory_file_path, graph))
+            .await?;
+        self.disk_writes.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Write the held graph, if any; it is kept for a retry if the write fails.
+    /// Once written, the journal recording it is truncated.
+    async fn flush(
+        &self,
+        memory_file_path: &Path,
+        journal_path: Option<&Path>,
+        retry: IoRetry,
+    ) -> Result<()> {
+        let mut pending = self.graph.lock().await;
+        if let Some(graph) = pending.as_ref() {
+            self.write(memory_file_path, graph, retry).await?;
+            *pending = None;
+            if let Some(journal_path) = journal_path {
+                truncate_journal(journal_path).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct KnowledgeGraphManager {
+    store: GraphStore,
+    search_engine: Arc<SearchEngine>,
+    /// Hold changes in memory until `flush` instead of writing on every mutation
+    write_back: bool,
+    /// Write the memory file once mutations have paused for this long
+    write_debounce: Option<Duration>,
+    /// Changes not yet written to the memory file (write-back and debounce modes)
+    pending: Arc<PendingWrites>,
+    /// Append-only file recording buffered changes until they reach the memory file
+    journal_path: Option<PathBuf>,
+    /// Drop relations whose endpoints don't exist whenever the graph is loaded
+    prune_dangling_on_load: bool,
+    /// Retry policy for transient memory file IO failures
+    io_retry: IoRetry,
+    /// Additional memory files merged read-only into search and read results
+    federated_paths: Vec<PathBuf>,
+    /// How entity names are compared on create and lookup
+    name_policy: NamePolicy,
+    /// What creating an entity with an existing name does
+    duplicate_policy: DuplicatePolicy,
+    /// Whether `create_entities` treats names differing only in letter case as duplicates
+    case_insensitive_duplicates: bool,
+    /// Directory that alternate memory files read with `scoped` must be inside
+    snapshot_dir: Option<PathBuf>,
+    /// JSON file the ontology is loaded from and saved to
+    schema_path: Option<PathBuf>,
+    /// The ontology, loaded from `schema_path` on first use
+    ontology: Mutex<Option<Ontology>>,
+    /// JSON file the search ranker settings are loaded from and saved to
+    search_config_path: Option<PathBuf>,
+    /// The search settings, loaded from `search_config_path` on first use
+    search_config: Mutex<Option<SearchConfig>>,
+    /// Most recent mutations, oldest first
+    audit_log: Mutex<VecDeque<AuditEntry>>,
+    /// How many mutations `audit_log` keeps; 0 disables it
+    audit_log_size: usize,
+    /// Bumped on every save
+    version: AtomicU64,
+    /// Unix timestamp of the last save, 0 if nothing was saved yet
+    last_saved: AtomicI64,
+    /// Totals of the primary graph with the `data_version` they were taken at; any save or
+    /// outside change to the memory file makes them stale
+    counts: Mutex<Option<(u64, GraphCounts)>>,
+}
+
+impl KnowledgeGraphManager {
+    pub fn new() -> Self {
+        let current_exe = env::current_exe().unwrap_or_else(|_| PathBuf::from("."));
+        let exe_dir = current_exe.parent().unwrap_or_else(|| Path::new("."));
+        let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+
+        let path_env = env::var("MEMORY_FILE_PATH").ok();
+        let base = PathBase::from_env_value(env::var("MEMORY_PATH_BASE").ok().as_deref());
+        let memory_file_path = resolve_memory_path(path_env.as_deref(), base, &cwd, exe_dir);
+        let search_config_path = match env::var("MEMORY_SEARCH_CONFIG_PATH") {
+            Ok(path) => resolve_memory_path(Some(&path), base, &cwd, exe_dir),
+            Err(_) => memory_file_path.with_extension("config.json"),
+        };
+        let federated_paths = env::var_os("MEMORY_FEDERATED_PATHS")
+            .map(|paths| {
+                env::split_paths(&paths)
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .map(|p| resolve_memory_path(p.to_str(), base, &cwd, exe_dir))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self::with_path(memory_file_path)
+            .with_write_back(env_flag("MEMORY_WRITE_BACK"))
+            .with_write_debounce(
+                env::var("MEMORY_WRITE_DEBOUNCE_MS")
+                    .ok()
+                    .and_then(|v| v.trim().parse().ok())
+                    .filter(|ms| *ms > 0)
+                    .map(Duration::from_millis),
+            )
+            .with_journal_path(
+                env::var("MEMORY_JOURNAL_PATH")
+                    .ok()
+                    .map(|path| resolve_memory_path(Some(&path), base, &cwd, exe_dir)),
+            )
+            .with_prune_dangling_on_load(env_flag("MEMORY_PRUNE_DANGLING_ON_LOAD"))
+            .with_io_max_attempts(
+                env::var("MEMORY_IO_MAX_ATTEMPTS")
+                    .ok()
+                    .and_then(|v| v.trim().parse().ok())
+                    .unwrap_or(DEFAULT_IO_MAX_ATTEMPTS),
+            )
+            .with_federated_paths(federated_paths)
+            .with_name_policy(NamePolicy::from_env_value(
+                env::var("MEMORY_NAME_POLICY").ok().as_deref(),
+            ))
+            .with_duplicate_policy(DuplicatePolicy::from_env_value(
+                env::var("MEMORY_DUPLICATE_POLICY").ok().as_deref(),
+            ))
+            .with_case_insensitive_duplicates(
+                env::var("MEMORY_CASE_INSENSITIVE_DUPLICATES").map_or(true, |v| {
+                    !matches!(v.trim().to_lowercase().as_str(), "0" | "false" | "no")
+                }),
+            )
+            .with_schema_path(
+                env::var("MEMORY_SCHEMA_PATH")
+                    .ok()
+                    .map(|path| resolve_memory_path(Some(&path), base, &cwd, exe_dir)),
+            )
+            .with_search_config_path(Some(search_config_path))
+            .with_snapshot_dir(
+                env::var("MEMORY_SNAPSHOT_DIR")
+                    .ok()
+                    .map(|path| resolve_memory_path(Some(&path), base, &cwd, exe_dir)),
+            )
+            .with_audit_log_size(
+                env::var("MEMORY_AUDIT_LOG_SIZE")
+                    .ok()
+                    .and_then(|v| v.trim().parse().ok())
+                    .unwrap_or(DEFAULT_AUDIT_LOG_SIZE),
+            )
+    }
+
+    pub fn with_path<P: AsRef<Path>>(path: P) -> Self {
+        let memory_file_path = path.as_ref().to_path_buf();
+
+        let search_engine = Arc::new(SearchEngine::new());
+
+        Self {
+            search_config_path: Some(memory_file_path.with_extension("config.json")),
+            snapshot_dir: memory_file_path.parent().map(|dir| {
+                if dir.as_os_str().is_empty() {
+                    PathBuf::from(".")
+                } else {
+                    dir.to_path_buf()
+                }
+            }),
+            store: GraphStore::File(memory_file_path),
+            search_engine,
+            write_back: false,
+            write_debounce: None,
+            pending: Arc::default(),
+            journal_path: None,
+            prune_dangling_on_load: false,
+            io_retry: IoRetry::default(),
+            federated_paths: Vec::new(),
+            name_policy: NamePolicy::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            case_insensitive_duplicates: true,
+            schema_path: None,
+            ontology: Mutex::new(None),
+            search_config: Mutex::new(None),
+            audit_log: Mutex::default(),
+            audit_log_size: DEFAULT_AUDIT_LOG_SIZE,
+            version: AtomicU64::new(0),
+            last_saved: AtomicI64::new(0),
+            counts: Mutex::new(None),
+        }
+    }
+
+    /// Keep mutations in memory until `flush` is called rather than rewriting the file each time.
+    /// Ignored when a write debounce is set, which writes the file once mutations pause.
+    pub fn with_write_back(mut self, write_back: bool) -> Self {
+        self.write_back = write_back;
+        self
+    }
+
+    /// Coalesce bursts of mutations into one write of the memory file after `delay` of inactivity.
+    /// Changes are visible immediately; `flush` writes them without waiting. Takes precedence
+    /// over write-back.
+    pub fn with_write_debounce(mut self, delay: Option<Duration>) -> Self {
+        self.write_debounce = delay;
+        self
+    }
+
+    /// Record changes held back in write-back or debounce mode in this journal before
+    /// acknowledging them, so `recover` can restore them after a crash
+    pub fn with_journal_path(mut self, journal_path: Option<PathBuf>) -> Self {
+        self.journal_path = journal_path;
+        self
+    }
+
+    /// Leave relations whose source or target entity doesn't exist out of every loaded graph,
+    /// so they are dropped from the memory file on the next write. This includes relations
+    /// kept by `delete_entities` with `keep_relations`.
+    pub fn with_prune_dangling_on_load(mut self, prune: bool) -> Self {
+        self.prune_dangling_on_load = prune;
+        self
+    }
+
+    /// Try reading and writing the memory file up to `max_attempts` times when it fails
+    /// transiently, backing off exponentially between attempts
+    pub fn with_io_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.io_retry.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Merge these memory files into reads and searches; writes still go only to the primary file
+    pub fn with_federated_paths(mut self, paths: Vec<PathBuf>) -> Self {
+        self.federated_paths = paths;
+        self
+    }
+
+    /// Compare entity names using `policy` when creating and looking up entities
+    pub fn with_name_policy(mut self, name_policy: NamePolicy) -> Self {
+        self.name_policy = name_policy;
+        self
+    }
+
+    /// Skip or merge entities created under an existing name
+    pub fn with_duplicate_policy(mut self, duplicate_policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = duplicate_policy;
+        self
+    }
+
+    /// Treat names differing only in letter case, like "alice" and "Alice", as the same
+    /// entity when creating entities, whatever the name policy (on by default)
+    pub fn with_case_insensitive_duplicates(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive_duplicates = case_insensitive;
+        self
+    }
+
+    /// Load the ontology from, and save it to, this JSON file
+    pub fn with_schema_path(mut self, schema_path: Option<PathBuf>) -> Self {
+        self.schema_path = schema_path;
+        self
+    }
+
+    /// Where search settings are persisted; defaults to `<memory file>.config.json`
+    pub fn with_search_config_path(mut self, search_config_path: Option<PathBuf>) -> Self {
+        self.search_config_path = search_config_path;
+        self
+    }
+
+    /// Allow `scoped` to read memory files inside this directory; `None` leaves the
+    /// default, the directory of the memory file
+    pub fn with_snapshot_dir(mut self, snapshot_dir: Option<PathBuf>) -> Self {
+        if snapshot_dir.is_some() {
+            self.snapshot_dir = snapshot_dir;
+        }
+        self
+    }
+
+    /// Keep the last `size` mutations in the audit log; 0 disables it
+    pub fn with_audit_log_size(mut self, size: usize) -> Self {
+        self.audit_log_size = size;
+        self
+    }
+
+    /// Create a manager whose graph lives only in memory, seeded from a
+    /// JSON (`read_graph` output) or JSONL (memory file) string.
+    /// Mutations are visible for the lifetime of the process but are never persisted.
+    pub fn in_memory(seed: &str) -> Result<Self> {
+        let graph = parse_graph(seed)?;
+
+        Ok(Self {
+            store: GraphStore::Memory(Mutex::new(graph)),
+            search_engine: Arc::new(SearchEngine::new()),
+            write_back: false,
+            write_debounce: None,
+            pending: Arc::default(),
+            journal_path: None,
+            prune_dangling_on_load: false,
+            io_retry: IoRetry::default(),
+            federated_paths: Vec::new(),
+            name_policy: NamePolicy::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            case_insensitive_duplicates: true,
+            snapshot_dir: None,
+            schema_path: None,
+            ontology: Mutex::new(None),
+            search_config_path: None,
+            search_config: Mutex::new(None),
+            audit_log: Mutex::default(),
+            audit_log_size: DEFAULT_AUDIT_LOG_SIZE,
+            version: AtomicU64::new(0),
+            last_saved: AtomicI64::new(0),
+            counts: Mutex::new(None),
+        })
+    }
+
+    /// Load the current graph and return an in-memory manager seeded with it
+    pub async fn into_in_memory(self) -> Result<Self> {
+        let graph = self.load_graph().await?;
+
+        Ok(Self {
+            store: GraphStore::Memory(Mutex::new(*graph)),
+            search_engine: self.search_engine,
+            write_back: false,
+            write_debounce: None,
+            pending: Arc::default(),
+            journal_path: None,
+            prune_dangling_on_load: self.prune_dangling_on_load,
+            io_retry: self.io_retry,
+            federated_paths: Vec::new(),
+            name_policy: self.name_policy,
+            duplicate_policy: self.duplicate_policy,
+            case_insensitive_duplicates: self.case_insensitive_duplicates,
+            snapshot_dir: self.snapshot_dir,
+            schema_path: self.schema_path,
+            ontology: self.ontology,
+            search_config_path: self.search_config_path,
+            search_config: self.search_config,
+            audit_log: self.audit_log,
+            audit_log_size: self.audit_log_size,
+            version: self.version,
+            last_saved: self.last_saved,
+            counts: self.counts,
+        })
+    }
+
+    async fn load_graph(&self) -> Result<Box<KnowledgeGraph>> {
+        let _start_time = Instant::now();
+
+        let mut graph = self.load_stored_graph().await?;
+        if self.prune_dangling_on_load {
+            remove_dangling_relations(&mut graph);
+        }
+        Ok(Box::new(graph))
+    }
+
+    /// The primary graph as stored, dangling relations included
+    async fn load_stored_graph(&self) -> Result<KnowledgeGraph> {
+        if let Some(graph) = self.cached_graph().await {
+            return Ok(graph);
+        }
+
+        let (entities, relations) = self.load_items().await?;
+        Ok(graph_from_items(entities, relations))
+    }
+
+    /// The graph held in memory, if it is newer than (or replaces) the memory file
+    async fn cached_graph(&self) -> Option<KnowledgeGraph> {
+        match &self.store {
+            GraphStore::Memory(graph) => Some(graph.lock().await.clone()),
+            GraphStore::File(_) => self.pending.graph.lock().await.clone(),
+        }
+    }
+
+    /// Read the stored entity and relation records without collapsing duplicate names
+    async fn load_items(&self) -> Result<(Vec<Entity>, Vec<Relation>)> {
+        if let Some(graph) = self.cached_graph().await {
+            return Ok((graph.entities.into_values().collect(), graph.relations));
+        }
+
+        match &self.store {
+            GraphStore::File(memory_file_path) => {
+                self.io_retry
+                    .run(|| read_items_file(memory_file_path))
+                    .await
+            }
+            GraphStore::Memory(_) => Ok((Vec::new(), Vec::new())),
+        }
+    }
+
+    /// Load the graph used for reads: the primary graph merged with any federated files.
+    /// Entities keep the first definition seen, with the primary graph taking precedence.
+    async fn load_view(&self) -> Result<Box<KnowledgeGraph>> {
+        let mut graph = self.load_graph().await?;
+        if self.federated_paths.is_empty() {
+            return Ok(graph);
+        }
+
+        if let GraphStore::File(memory_file_path) = &self.store {
+            let origin = memory_file_path.display().to_string();
+            for entity in graph.entities.values_mut() {
+                entity.source_file = Some(origin.clone());
+            }
+        }
+
+        let mut seen_relations: HashSet<Relation> = graph.relations.iter().cloned().collect();
+        for path in &self.federated_paths {
+            let (entities, relations) = self.io_retry.run(|| read_items_file(path)).await?;
+            let origin = path.display().to_string();
+            for mut entity in entities {
+                entity.source_file = Some(origin.clone());
+                graph.entities.entry(entity.name.clone()).or_insert(entity);
+            }
+            for relation in relations {
+                if seen_relations.insert(relation.clone()) {
+                    graph.relations.push(relation);
+                }
+            }
+        }
+
+        Ok(graph)
+    }
+
+    async fn save_graph(&self, graph: &KnowledgeGraph) -> Result<()> {
+        self.store_graph(graph).await?;
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.last_saved.store(now_timestamp(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn store_graph(&self, graph: &KnowledgeGraph) -> Result<()> {
+        let memory_file_path = match &self.store {
+            GraphStore::File(path) => path,
+            GraphStore::Memory(stored) => {
+                *stored.lock().await = graph.clone();
+                return Ok(());
+            }
+        };
+
+        if self.write_back || self.write_debounce.is_some() {
+            let mut pending = self.pending.graph.lock().await;
+            if let Some(journal_path) = &self.journal_path {
+                // Held under the pending lock so a concurrent flush can't truncate the
+                // journal between this entry being written and its graph being held
+                let entry = match pending.as_ref() {
+                    Some(previous) => JournalEntry::between(previous, graph),
+                    None => {
+                        let (entities, relations) = self
+                            .io_retry
+                            .run(|| read_items_file(memory_file_path))
+                            .await?;
+                        JournalEntry::between(&graph_from_items(entities, relations), graph)
+                    }
+                };
+                if !entry.is_empty() {
+                    append_journal(journal_path, &entry).await?;
+                }
+            }
+            *pending = Some(graph.clone());
+            drop(pending);
+            let generation = self.pending.generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+            if let Some(delay) = self.write_debounce {
+                let pending = Arc::clone(&self.pending);
+                let memory_file_path = memory_file_path.clone();
+                let journal_path = self.journal_path.clone();
+                let retry = self.io_retry;
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    if pending.generation.load(Ordering::SeqCst) == generation {
+                        // A failed write stays pending for the next flush or shutdown
+                        let _ = pending
+                            .flush(&memory_file_path, journal_path.as_deref(), retry)
+                            .await;
+                    }
+                });
+            }
+            return Ok(());
+        }
+
+        self.pending
+            .write(memory_file_path, graph, self.io_retry)
+            .await
+    }
+
+    /// Append a mutation to the audit log, dropping the oldest entry once it is full.
+    /// Operations that changed nothing are not recorded.
+    async fn record(
+        &self,
+        operation: &'static str,
+        entities: Vec<String>,
+        relations: Vec<Relation>,
+    ) {
+        if self.audit_log_size == 0 || (entities.is_empty() && relations.is_empty()) {
+            return;
+        }
+        let mut log = self.audit_log.lock().await;
+        while log.len() >= self.audit_log_size {
+            log.pop_front();
+        }
+        log.push_back(AuditEntry {
+            timestamp: now_timestamp(),
+            operation,
+            entities,
+            relations,
+        });
+    }
+
+    /// The most recent mutations, newest first
+    pub async fn get_audit_log(&self, limit: Option<usize>) -> Result<Vec<AuditEntry>> {
+        let log = self.audit_log.lock().await;
+        Ok(log
+            .iter()
+            .rev()
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect())
+    }
+
+    /// An in-memory copy of another memory file, such as an archived snapshot, for reading
+    /// it without touching the primary graph. The file must lie inside the snapshot directory;
+    /// relative paths are resolved against it. Name policy and search settings are shared.
+    pub async fn scoped(&self, file: &str) -> Result<Self> {
+        let base = self
+            .snapshot_dir
+            .as_ref()
+            .context("No snapshot directory is configured")?;
+        let base = fs::canonicalize(base)
+            .await
+            .with_context(|| format!("Snapshot directory {} not found", base.display()))?;
+        let path = fs::canonicalize(base.join(file))
+            .await
+            .with_context(|| format!("Memory file {file} not found"))?;
+        if !path.starts_with(&base) {
+            anyhow::bail!(
+                "Memory file {file} is outside the snapshot directory {}",
+                base.display()
+            );
+        }
+
+        let search_config = self.get_search_config().await?;
+        let scoped = Self::with_path(path)
+            .with_name_policy(self.name_policy)
+            .with_search_config_path(None)
+            .with_audit_log_size(0);
+        *scoped.search_config.lock().await = Some(search_config);
+        scoped.into_in_memory().await
+    }
+
+    /// The settings in effect, loading the search config and ontology files if not yet read
+    pub async fn get_config(&self) -> Result<EffectiveConfig> {
+        let search_config = self.get_search_config().await?;
+        Ok(EffectiveConfig {
+            memory_path: match &self.store {
+                GraphStore::File(path) => Some(path.clone()),
+                GraphStore::Memory(_) => None,
+            },
+            federated_paths: self.federated_paths.clone(),
+            write_back: self.write_back,
+            write_debounce_ms: self.write_debounce.map(|d| d.as_millis() as u64),
+            journal_path: self.journal_path.clone(),
+            prune_dangling_on_load: self.prune_dangling_on_load,
+            io_max_attempts: self.io_retry.max_attempts,
+            name_policy: self.name_policy,
+            duplicate_policy: self.duplicate_policy,
+            case_insensitive_duplicates: self.case_insensitive_duplicates,
+            snapshot_dir: self.snapshot_dir.clone(),
+            schema_path: self.schema_path.clone(),
+            strict_schema: self.get_ontology().await?.strict,
+            search_config_path: self.search_config_path.clone(),
+            search: self.search_engine.effective_config(&search_config),
+            default_search_limit: DEFAULT_SEARCH_LIMIT,
+            search_min_term_length: self.search_engine.min_term_length(),
+            search_stop_words: self.search_engine.stop_words(),
+            search_parallel_threshold: self.search_engine.parallel_threshold(),
+            search_threads: self.search_engine.search_threads(),
+            search_cache_size: self.search_engine.query_cache_size(),
+            audit_log_size: self.audit_log_size,
+        })
+    }
+
+    /// Write any changes held back in write-back or debounce mode to the memory file
+    pub async fn flush(&self) -> Result<()> {
+        let GraphStore::File(memory_file_path) = &self.store else {
+            return Ok(());
+        };
+
+        self.pending
+            .flush(
+                memory_file_path,
+                self.journal_path.as_deref(),
+                self.io_retry,
+            )
+            .await
+    }
+
+    /// Replay changes recorded in the journal but not yet written to the memory file,
+    /// e.g. because the process died before a write-back flush, then write the memory file
+    /// and truncate the journal. Returns the number of journal entries replayed.
+    pub async fn recover(&self) -> Result<usize> {
+        let (GraphStore::File(memory_file_path), Some(journal_path)) =
+            (&self.store, &self.journal_path)
+        else {
+            return Ok(0);
+        };
+
+        let mut pending = self.pending.graph.lock().await;
+        let (entries, torn) = read_journal(journal_path).await?;
+        if entries.is_empty() {
+            // Don't leave a torn line for the next append to be glued onto
+            if torn {
+                truncate_journal(journal_path).await?;
+            }
+            return Ok(0);
+        }
+
+        // Entries are appended under the pending lock, so the memory file plus the journal
+        // is always at least as new as any graph still held in memory
+        let (entities, relations) = self
+            .io_retry
+            .run(|| read_items_file(memory_file_path))
+            .await?;
+        let mut graph = graph_from_items(entities, relations);
+        let replayed = entries.len();
+        for entry in entries {
+            entry.apply(&mut graph);
+        }
+
+        self.pending
+            .write(memory_file_path, &graph, self.io_retry)
+            .await?;
+        *pending = None;
+        truncate_journal(journal_path).await?;
+        drop(pending);
+
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.last_saved.store(now_timestamp(), Ordering::SeqCst);
+        Ok(replayed)
+    }
+
+    /// Serialize the graph as JSONL split into chunks of at most `chunk_size` lines
+    pub async fn export_jsonl_chunks(
+        &self,
+        filter: &ExportFilter,
+        chunk_size: usize,
+    ) -> Result<Vec<String>> {
+        let graph = self.export_subgraph(filter).await?;
+        let chunk_size = chunk_size.max(1);
+
+        let mut chunks = Vec::new();
+        let mut chunk = String::new();
+        let mut lines_in_chunk = 0;
+        for line in jsonl_lines(&graph) {
+            chunk.push_str(&line?);
+            chunk.push('\n');
+            lines_in_chunk += 1;
+            if lines_in_chunk == chunk_size {
+                chunks.push(std::mem::take(&mut chunk));
+                lines_in_chunk = 0;
+            }
+        }
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+
+        Ok(chunks)
+    }
+
+    /// The stored graph, or the slice selected by `filter`, serialized as `format`,
+    /// with `delimiter` between observations where they share a field
+    pub async fn export(
+        &self,
+        filter: &ExportFilter,
+        format: ExportFormat,
+        delimiter: &str,
+    ) -> Result<String> {
+        render(&*self.export_subgraph(filter).await?, format, delimiter)
+    }
+
+    /// The stored entities matching any criterion of `filter`, with the relations among them
+    pub async fn export_subgraph(&self, filter: &ExportFilter) -> Result<Box<KnowledgeGraph>> {
+        let graph = self.load_graph().await?;
+        if filter.is_empty() {
+            return Ok(graph);
+        }
+
+        let index = NameIndex::new(self.name_policy, &graph);
+        let mut wanted: HashSet<String> = filter
+            .names
+            .iter()
+            .map(|name| index.resolve(name))
+            .collect();
+        if let Some(query) = &filter.query {
+            let options = SearchOptions {
+                ranker_config: Some(self.get_search_config().await?),
+                ..Default::default()
+            };
+            let hits = self
+                .search_engine
+                .enhanced_text_search(&graph, query, Some(usize::MAX), &options)
+                .await?;
+            // Structural bonuses alone don't make an entity a match
+            wanted.extend(
+                hits.into_iter()
+                    .filter(|e| !e.matched_fields.is_empty())
+                    .map(|e| e.name),
+            );
+        }
+
+        Ok(Box::new(subgraph(*graph, |entity| {
+            wanted.contains(&entity.name) || filter.types.contains(&entity.entity_type)
+        })))
+    }
+
+    /// The current ontology, read from the schema file the first time it is needed
+    pub async fn get_ontology(&self) -> Result<Ontology> {
+        let mut ontology = self.ontology.lock().await;
+        if let Some(ontology) = ontology.as_ref() {
+            return Ok(ontology.clone());
+        }
+
+        let loaded = match &self.schema_path {
+            Some(path) => match fs::read_to_string(path).await {
+                Ok(data) => serde_json::from_str(&data)
+                    .with_context(|| format!("Failed to parse schema {}", path.display()))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ontology::default(),
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to read schema {}", path.display()))
+                }
+            },
+            None => Ontology::default(),
+        };
+        *ontology = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    /// The persisted search settings, read from `search_config_path` on first use
+    pub async fn get_search_config(&self) -> Result<SearchConfig> {
+        self.load_search_config(&mut *self.search_config.lock().await)
+            .await
+    }
+
+    /// `get_search_config` for a caller already holding the lock on `config`
+    async fn load_search_config(&self, config: &mut Option<SearchConfig>) -> Result<SearchConfig> {
+        if let Some(config) = config.as_ref() {
+            return Ok(*config);
+        }
+
+        let loaded = match &self.search_config_path {
+            Some(path) => match fs::read_to_string(path).await {
+                Ok(data) => serde_json::from_str(&data)
+                    .with_context(|| format!("Failed to parse search config {}", path.display()))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => SearchConfig::default(),
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to read search config {}", path.display())
+                    })
+                }
+            },
+            None => SearchConfig::default(),
+        };
+        *config = Some(loaded);
+        Ok(loaded)
+    }
+
+    /// Merge `update` into the search settings and persist them, returning the result.
+    /// The lock is held throughout so concurrent updates can't drop each other's fields.
+    pub async fn set_search_config(&self, update: SearchConfig) -> Result<SearchConfig> {
+        update.validate()?;
+        let mut current = self.search_config.lock().await;
+        let merged = self.load_search_config(&mut current).await?.merged(update);
+        if let Some(path) = &self.search_config_path {
+            let data = serde_json::to_string_pretty(&merged)?;
+            fs::write(path, data)
+                .await
+                .with_context(|| format!("Failed to write search config {}", path.display()))?;
+        }
+        *current = Some(merged);
+        Ok(merged)
+    }
+
+    /// Replace the ontology, writing it to the schema file when one is configured
+    pub async fn set_ontology(&self, ontology: Ontology) -> Result<()> {
+        let mut current = self.ontology.lock().await;
+        if let Some(path) = &self.schema_path {
+            let data = serde_json::to_string_pretty(&ontology)?;
+            fs::write(path, data)
+                .await
+                .with_context(|| format!("Failed to write schema {}", path.display()))?;
+        }
+        *current = Some(ontology);
+        Ok(())
+    }
+
+    /// Create entities, handling names that match existing entities under the name policy
+    /// with `policy` (the configured duplicate policy when `None`) and reporting each collision
+    pub async fn create_entities(
+        &self,
+        entities: Vec<Entity>,
+        policy: Option<DuplicatePolicy>,
+    ) -> Result<CreateReport> {
+        let ontology = self.get_ontology().await?;
+        let policy = policy.unwrap_or(self.duplicate_policy);
+
+        let mut graph = self.load_graph().await?;
+        let mut names =
+            NameIndex::with_case(self.name_policy, self.case_insensitive_duplicates, &graph);
+
+        let now = now_timestamp();
+        let mut created = Vec::new();
+        let mut collisions = Vec::new();
+        for mut entity in entities {
+            entity.name = self.name_policy.display(&entity.name);
+            if names.insert(&entity.name) {
+                entity.created_at.get_or_insert(now);
+                entity.updated_at.get_or_insert(now);
+                created.push(entity.name.clone());
+                graph.entities.insert(entity.name.clone(), entity);
+                continue;
+            }
+
+            let existing = names.resolve(&entity.name);
+            let mut observations_added = 0;
+            if policy == DuplicatePolicy::Merge {
+                if let Some(target) = graph.entities.get_mut(&existing) {
+                    for observation in entity.observations {
+                        if !target
+                            .observations
+                            .iter()
+                            .any(|o| o.content == observation.content)
+                        {
+                            target.observations.push(observation);
+                            observations_added += 1;
+                        }
+                    }
+                    if observations_added > 0 {
+                        target.updated_at = Some(now);
+                    }
+                }
+            }
+            collisions.push(NameCollision {
+                requested: entity.name,
+                existing,
+                policy,
+                observations_added,
+            });
+        }
+        // Only entities that are actually created need an allowed type; skipped or merged
+        // ones keep the type already stored
+        let new_entities: Vec<&Entity> = created
+            .iter()
+            .filter_map(|name| graph.entities.get(name))
+            .collect();
+        ontology.check(&new_entities, &[])?;
+        self.save_graph(&graph).await?;
+        let merged = collisions
+            .iter()
+            .filter(|c| c.observations_added > 0)
+            .map(|c| c.existing.clone());
+        self.record(
+            "create_entities",
+            created.iter().cloned().chain(merged).collect(),
+            Vec::new(),
+        )
+        .await;
+
+        Ok(CreateReport {
+            created: created
+                .iter()
+                .filter_map(|name| graph.entities.get(name).cloned())
+                .collect(),
+            collisions,
+        })
+    }
+
+    /// Check `entities` the way `create_entities` would treat them, without writing anything:
+    /// empty names and observations, types outside the ontology, and name collisions with
+    /// stored entities or earlier ones in the batch
+    pub async fn validate_entities(
+        &self,
+        entities: &[Entity],
+        policy: Option<DuplicatePolicy>,
+    ) -> Result<Vec<EntityValidation>> {
+        let ontology = self.get_ontology().await?;
+        let policy = policy.unwrap_or(self.duplicate_policy);
+        let graph = self.load_graph().await?;
+        let mut names =
+            NameIndex::with_case(self.name_policy, self.case_insensitive_duplicates, &graph);
+
+        Ok(entities
+            .iter()
+            .map(|entity| {
+                let mut errors = Vec::new();
+                let mut warnings = Vec::new();
+                if entity.name.trim().is_empty() {
+                    errors.push("name is empty".to_string());
+                }
+                if entity.entity_type.trim().is_empty() {
+                    warnings.push("entity type is empty".to_string());
+                }
+                let empty_observations = entity
+                    .observations
+                    .iter()
+                    .filter(|o| o.content.trim().is_empty())
+                    .count();
+                if empty_observations > 0 {
+                    errors.push(format!("{empty_observations} empty observation(s)"));
+                }
+
+                let display = self.name_policy.display(&entity.name);
+                let duplicate_of = if names.insert(&display) {
+                    None
+                } else {
+                    let existing = names.resolve(&display);
+                    warnings.push(match policy {
+                        DuplicatePolicy::Skip => {
+                            format!("duplicate of '{existing}', would be skipped")
+                        }
+                        DuplicatePolicy::Merge => format!(
+                            "duplicate of '{existing}', observations would be merged into it"
+                        ),
+                    });
+                    Some(existing)
+                };
+
+                // A duplicate is skipped or merged and keeps the stored type
+                if duplicate_of.is_none() {
+                    let (unknown_types, _) = ontology.unknown_types(&[entity], &[]);
+                    for entity_type in unknown_types {
+                        let problem = format!("entity type '{entity_type}' not in schema");
+                        if ontology.strict {
+                            errors.push(problem);
+                        } else {
+                            warnings.push(problem);
+                        }
+                    }
+                }
+
+                EntityValidation {
+                    name: entity.name.clone(),
+                    valid: errors.is_empty(),
+                    errors,
+                    warnings,
+                    duplicate_of,
+                }
+            })
+            .collect())
+    }
+
+    /// Create missing entities and merge observations into existing ones.
+    /// The type of an existing entity is only replaced when `update_type` is set.
+    pub async fn upsert_entities(
+        &self,
+        entities: Vec<Entity>,
+        update_type: bool,
+    ) -> Result<Vec<(String, UpsertStatus)>> {
+        self.get_ontology()
+            .await?
+            .check(&entities.iter().collect::<Vec<_>>(), &[])?;
+        let mut graph = self.load_graph().await?;
+        let mut names = NameIndex::new(self.name_policy, &graph);
+        let now = now_timestamp();
+        let mut results = Vec::new();
+
+        for mut incoming in entities {
+            incoming.name = names.resolve(&incoming.name);
+            let status = match graph.entities.get_mut(&incoming.name) {
+                Some(existing) => {
+                    let mut changed = false;
+                    for observation in incoming.observations {
+                        if !existing
+                            .observations
+                            .iter()
+                            .any(|o| o.content == observation.content)
+                        {
+                            existing.observations.push(observation);
+                            changed = true;
+                        }
+                    }
+                    if update_type && existing.entity_type != incoming.entity_type {
+                        existing.entity_type = incoming.entity_type;
+                        changed = true;
+                    }
+                    if changed {
+                        existing.updated_at = Some(now);
+                        UpsertStatus::Updated
+                    } else {
+                        UpsertStatus::Unchanged
+                    }
+                }
+                None => {
+                    let mut seen = HashSet::new();
+                    incoming
+                        .observations
+                        .retain(|o| seen.insert(o.content.clone()));
+                    incoming.created_at.get_or_insert(now);
+                    incoming.updated_at.get_or_insert(now);
+                    names.insert(&incoming.name);
+                    graph
+                        .entities
+                        .insert(incoming.name.clone(), incoming.clone());
+                    UpsertStatus::Created
+                }
+            };
+            results.push((incoming.name, status));
+        }
+
+        self.save_graph(&graph).await?;
+        let changed = results
+            .iter()
+            .filter(|(_, status)| *status != UpsertStatus::Unchanged)
+            .map(|(name, _)| name.clone())
+            .collect();
+        self.record("upsert_entities", changed, Vec::new()).await;
+        Ok(results)
+    }
+
+    pub async fn create_relations(&self, relations: Vec<Relation>) -> Result<CreatedRelations> {
+        let ontology = self.get_ontology().await?;
+        ontology.check(&[], &relations)?;
+        let mut graph = self.load_graph().await?;
+        let names = NameIndex::new(self.name_policy, &graph);
+        let relations: Vec<Relation> = relations
+            .into_iter()
+            .map(|r| names.resolve_relation(r))
+            .collect();
+
+        let mut seen: HashSet<_> = graph
+            .relations
+            .iter()
+            .map(|r| ontology.relation_key(r))
+            .collect();
+        let mut new_relations: Vec<Relation> = relations
+            .iter()
+            .filter(|r| seen.insert(ontology.relation_key(r)))
+            .cloned()
+            .collect();
+
+        let replaced = ontology.settle_cardinality(&mut graph.relations, &mut new_relations)?;
+
+        graph.relations.extend(new_relations.clone());
+        self.save_graph(&graph).await?;
+        self.record("delete_relations", Vec::new(), replaced.clone())
+            .await;
+        self.record("create_relations", Vec::new(), new_relations.clone())
+            .await;
+
+        Ok(CreatedRelations {
+            created: new_relations,
+            replaced,
+        })
+    }
+
+    /// Make sure both entities exist and `from` is related to `to`, in a single save.
+    /// Missing entities are created as given; existing ones are left as they are.
+    pub async fn connect_or_create(
+        &self,
+        from: Entity,
+        to: Entity,
+        relation_type: String,
+    ) -> Result<ConnectReport> {
+        let relation = Relation {
+            from: from.name.clone(),
+            to: to.name.clone(),
+            relation_type,
+            ..Default::default()
+        };
+        let ontology = self.get_ontology().await?;
+        ontology.check(&[&from, &to], std::slice::from_ref(&relation))?;
+
+        let mut graph = self.load_graph().await?;
+        let mut names = NameIndex::new(self.name_policy, &graph);
+        let now = now_timestamp();
+        let mut created = Vec::new();
+        let mut existing_entities = Vec::new();
+        for mut entity in [from, to] {
+            entity.name = self.name_policy.display(&entity.name);
+            if names.insert(&entity.name) {
+                entity.created_at.get_or_insert(now);
+                entity.updated_at.get_or_insert(now);
+                created.push(entity.name.clone());
+                graph.entities.insert(entity.name.clone(), entity);
+            } else {
+                let name = names.resolve(&entity.name);
+                if !existing_entities.contains(&name) && !created.contains(&name) {
+                    existing_entities.push(name);
+                }
+            }
+        }
+
+        let relation = names.resolve_relation(relation);
+        let key = ontology.relation_key(&relation);
+        let relation_created = !graph
+            .relations
+            .iter()
+            .any(|r| ontology.relation_key(r) == key);
+        let mut replaced = Vec::new();
+        if relation_created {
+            let mut new = vec![relation.clone()];
+            replaced = ontology.settle_cardinality(&mut graph.relations, &mut new)?;
+            graph.relations.push(relation.clone());
+        }
+
+        if relation_created || !created.is_empty() {
+            self.save_graph(&graph).await?;
+            self.record("delete_relations", Vec::new(), replaced.clone())
+                .await;
+            let relations = if relation_created {
+                vec![relation.clone()]
+            } else {
+                Vec::new()
+            };
+            self.record("connect_or_create", created.clone(), relations)
+                .await;
+        }
+
+        Ok(ConnectReport {
+            created_entities: created
+                .iter()
+                .filter_map(|name| graph.entities.get(name).cloned())
+                .collect(),
+            existing_entities,
+            relation,
+            relation_created,
+            replaced,
+        })
+    }
+
+    /// Add the observations of `relation` to the stored relation with the same endpoints and
+    /// type, skipping ones it already has
+    pub async fn add_relation_observations(
+        &self,
+        relation: Relation,
+    ) -> Result<AddedRelationObservations> {
+        let ontology = self.get_ontology().await?;
+        let mut graph = self.load_graph().await?;
+        let relation = NameIndex::new(self.name_policy, &graph).resolve_relation(relation);
+        let key = ontology.relation_key(&relation);
+        let stored = graph
+            .relations
+            .iter_mut()
+            .find(|r| ontology.relation_key(r) == key)
+            .with_context(|| {
+                format!(
+                    "Relation {} -[{}]-> {} not found",
+                    relation.from, relation.relation_type, relation.to
+                )
+            })?;
+
+        let mut added = Vec::new();
+        let mut already_present = Vec::new();
+        for observation in relation.observations {
+            if stored.observations.contains(&observation) {
+                already_present.push(observation);
+            } else {
+                stored.observations.push(observation.clone());
+                added.push(observation);
+            }
+        }
+        let stored = stored.clone();
+
+        if !added.is_empty() {
+            self.save_graph(&graph).await?;
+            self.record(
+                "add_relation_observations",
+                Vec::new(),
+                vec![stored.clone()],
+            )
+            .await;
+        }
+        Ok(AddedRelationObservations {
+            relation: stored,
+            added,
+            already_present,
+        })
+    }
+
+    /// Add observations to existing entities, skipping exact duplicates.
+    /// With `dedup_substrings`, a new observation contained in an existing one is dropped and
+    /// existing observations contained in a new one are replaced by it, ignoring case.
+    pub async fn add_observations(
+        &self,
+        observations: Vec<(String, Vec<Observation>)>,
+        order: ObservationOrder,
+        dedup_substrings: bool,
+    ) -> Result<Vec<AddedObservations>> {
+        let mut graph = self.load_graph().await?;
+        let mut results = Vec::new();
+
+        let names = NameIndex::new(self.name_policy, &graph);
+
+        for (entity_name, contents) in observations {
+            let entity_name = names.resolve(&entity_name);
+            let entity = graph
+                .entities
+                .get_mut(&entity_name)
+                .with_context(|| format!("Entity with name '{entity_name}' not found"))?;
+
+            let mut existing_observations: HashSet<_> = entity
+                .observations
+                .iter()
+                .map(|o| o.content.clone())
+                .collect();
+            let mut result = AddedObservations {
+                entity_name,
+                ..Default::default()
+            };
+            for observation in contents {
+                if !existing_observations.insert(observation.content.clone()) {
+                    result.already_present.push(observation.content);
+                    continue;
+                }
+                if dedup_substrings {
+                    let new_lower = observation.content.to_lowercase();
+                    if entity
+                        .observations
+                        .iter()
+                        .chain(&result.added)
+                        .any(|o| o.content.to_lowercase().contains(&new_lower))
+                    {
+                        result.dropped.push(observation.content);
+                        continue;
+                    }
+                    let subsumed = |o: &Observation| new_lower.contains(&o.content.to_lowercase());
+                    result.replaced.extend(
+                        entity
+                            .observations
+                            .iter()
+                            .filter(|o| subsumed(o))
+                            .map(|o| o.content.clone()),
+                    );
+                    entity.observations.retain(|o| !subsumed(o));
+                    result.dropped.extend(
+                        result
+                            .added
+                            .iter()
+                            .filter(|o| subsumed(o))
+                            .map(|o| o.content.clone()),
+                    );
+                    result.added.retain(|o| !subsumed(o));
+                }
+                result.added.push(observation);
+            }
+
+            if !result.added.is_empty() {
+                entity.updated_at = Some(now_timestamp());
+            }
+            match order {
+                ObservationOrder::Append => entity.observations.extend(result.added.clone()),
+                ObservationOrder::Prepend => {
+                    entity
+                        .observations
+                        .splice(0..0, result.added.iter().cloned());
+                }
+            }
+            results.push(result);
+        }
+
+        self.save_graph(&graph).await?;
+        let changed = results
+            .iter()
+            .filter(|r| !r.added.is_empty() || !r.replaced.is_empty())
+            .map(|r| r.entity_name.clone())
+            .collect();
+        self.record("add_observations", changed, Vec::new()).await;
+        Ok(results)
+    }
+
+    /// Reorder the observations of an entity in place
+    pub async fn sort_observations(&self, name: String, sort: ObservationSort) -> Result<Entity> {
+        let mut graph = self.load_graph().await?;
+        let name = NameIndex::new(self.name_policy, &graph).resolve(&name);
+        let entity = graph
+            .entities
+            .get_mut(&name)
+            .with_context(|| format!("Entity with name '{name}' not found"))?;
+
+        match sort {
+            ObservationSort::Alphabetical => entity
+                .observations
+                .sort_by(|a, b| a.content.cmp(&b.content)),
+            ObservationSort::Reverse => entity.observations.reverse(),
+        }
+        entity.updated_at = Some(now_timestamp());
+        let sorted = entity.clone();
+
+        self.save_graph(&graph).await?;
+        self.record("sort_observations", vec![name], Vec::new())
+            .await;
+        Ok(sorted)
+    }
+
+    /// Mark an entity as still relevant by bumping its update time without changing its data
+    pub async fn touch_entity(&self, name: String) -> Result<Entity> {
+        let mut graph = self.load_graph().await?;
+        let name = NameIndex::new(self.name_policy, &graph).resolve(&name);
+        let entity = graph
+            .entities
+            .get_mut(&name)
+            .with_context(|| format!("Entity with name '{name}' not found"))?;
+
+        entity.updated_at = Some(now_timestamp());
+        let touched = entity.clone();
+
+        self.save_graph(&graph).await?;
+        self.record("touch_entity", vec![name], Vec::new()).await;
+        Ok(touched)
+    }
+
+    /// Move some of an entity's observations into a new entity.
+    /// The new entity takes the original's type unless `entity_type` is given, and each
+    /// relation in `relink` has its endpoint moved from the original to the new entity.
+    pub async fn split_entity(
+        &self,
+        name: String,
+        new_name: String,
+        observations_to_move: Vec<String>,
+        entity_type: Option<String>,
+        relink: Vec<Relation>,
+    ) -> Result<SplitEntity> {
+        let ontology = self.get_ontology().await?;
+        let mut graph = self.load_graph().await?;
+        let mut names = NameIndex::new(self.name_policy, &graph);
+        let name = names.resolve(&name);
+        let new_name = self.name_policy.display(&new_name);
+
+        let entity = graph
+            .entities
+            .get(&name)
+            .with_context(|| format!("Entity with name '{name}' not found"))?;
+        if !names.insert(&new_name) {
+            anyhow::bail!("Entity with name '{new_name}' already exists");
+        }
+        let missing: Vec<&str> = observations_to_move
+            .iter()
+            .filter(|content| !entity.observations.iter().any(|o| &o.content == *content))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "Entity '{name}' has no observations: {}",
+                missing.join(", ")
+            );
+        }
+
+        let now = now_timestamp();
+        let mut created = Entity {
+            name: new_name.clone(),
+            entity_type: entity_type.unwrap_or_else(|| entity.entity_type.clone()),
+            created_at: Some(now),
+            updated_at: Some(now),
+            ..Default::default()
+        };
+        ontology.check(&[&created], &[])?;
+
+        let mut relinked = Vec::new();
+        for relation in relink {
+            let relation = names.resolve_relation(relation);
+            if relation.from != name && relation.to != name {
+                anyhow::bail!(
+                    "Relation {} -[{}]-> {} does not involve '{name}'",
+                    relation.from,
+                    relation.relation_type,
+                    relation.to
+                );
+            }
+            let key = ontology.relation_key(&relation);
+            let existing = graph
+                .relations
+                .iter_mut()
+                .find(|r| ontology.relation_key(r) == key)
+                .with_context(|| {
+                    format!(
+                        "Relation {} -[{}]-> {} not found",
+                        relation.from, relation.relation_type, relation.to
+                    )
+                })?;
+            for endpoint in [&mut existing.from, &mut existing.to] {
+                if *endpoint == name {
+                    *endpoint = new_name.clone();
+                }
+            }
+            relinked.push(existing.clone());
+        }
+        if !relinked.is_empty() {
+            graph.relations =
+                ontology.merge_duplicate_relations(std::mem::take(&mut graph.relations));
+        }
+
+        let original = graph
+            .entities
+            .get_mut(&name)
+            .expect("entity was looked up above");
+        let moving: HashSet<&str> = observations_to_move.iter().map(String::as_str).collect();
+        let (moved, kept) = std::mem::take(&mut original.observations)
+            .into_iter()
+            .partition(|o| moving.contains(o.content.as_str()));
+        original.observations = kept;
+        original.updated_at = Some(now);
+        created.observations = moved;
+        let original = original.clone();
+
+        graph.entities.insert(new_name.clone(), created.clone());
+        self.save_graph(&graph).await?;
+        self.record("split_entity", vec![name, new_name], relinked.clone())
+            .await;
+
+        Ok(SplitEntity {
+            original,
+            created,
+            relinked,
+        })
+    }
+
+    /// Delete entities and the relations touching them, or with `keep_relations` leave those
+    /// relations in place, dangling, to be repointed later; they are reported separately.
+    /// With `dry_run` the graph is left untouched and the report describes what would go.
+    pub async fn delete_entities(
+        &self,
+        entity_names: Vec<String>,
+        dry_run: bool,
+        keep_relations: bool,
+    ) -> Result<DeletionReport> {
+        let mut graph = self.load_graph().await?;
+        let names = NameIndex::new(self.name_policy, &graph);
+        let names_set: HashSet<_> = entity_names.iter().map(|n| names.resolve(n)).collect();
+        let mut report = DeletionReport::default();
+
+        graph.entities.retain(|name, _| {
+            let delete = names_set.contains(name);
+            if delete {
+                report.entities.push(name.clone());
+            }
+            !delete
+        });
+        report.entities.sort();
+        graph.relations.retain(|r| {
+            if !names_set.contains(&r.from) && !names_set.contains(&r.to) {
+                return true;
+            }
+            if keep_relations {
+                report.dangling_relations.push(r.clone());
+            } else {
+                report.relations.push(r.clone());
+            }
+            keep_relations
+        });
+
+        if !dry_run {
+            self.save_graph(&graph).await?;
+            self.record(
+                "delete_entities",
+                report.entities.clone(),
+                report.relations.clone(),
+            )
+            .await;
+        }
+        Ok(report)
+    }
+
+    /// Delete observations by content; `dry_run` reports without persisting
+    pub async fn delete_observations(
+        &self,
+        deletions: Vec<(String, Vec<String>)>,
+        dry_run: bool,
+    ) -> Result<DeletionReport> {
+        let mut graph = self.load_graph().await?;
+        let mut report = DeletionReport::default();
+
+        let names = NameIndex::new(self.name_policy, &graph);
+
+        for (entity_name, observations_to_delete) in deletions {
+            let entity_name = names.resolve(&entity_name);
+            if let Some(entity) = graph.entities.get_mut(&entity_name) {
+                let delete_set: HashSet<_> = observations_to_delete.iter().collect();
+                let mut removed = Vec::new();
+                entity.observations.retain(|o| {
+                    let delete = delete_set.contains(&o.content);
+                    if delete {
+                        removed.push(o.content.clone());
+                    }
+                    !delete
+                });
+                if !removed.is_empty() {
+                    entity.updated_at = Some(now_timestamp());
+                    report.observations.push((entity_name, removed));
+                }
+            }
+        }
+
+        if !dry_run {
+            self.save_graph(&graph).await?;
+            let changed = report
+                .observations
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect();
+            self.record("delete_observations", changed, Vec::new())
+                .await;
+        }
+        Ok(report)
+    }
+
+    /// Remove every observation of an entity, returning how many were removed
+    pub async fn clear_observations(&self, entity_name: String) -> Result<usize> {
+        let mut graph = self.load_graph().await?;
+        let entity_name = NameIndex::new(self.name_policy, &graph).resolve(&entity_name);
+        let entity = graph
+            .entities
+            .get_mut(&entity_name)
+            .with_context(|| format!("Entity with name '{entity_name}' not found"))?;
+
+        let removed = std::mem::take(&mut entity.observations).len();
+        if removed > 0 {
+            entity.updated_at = Some(now_timestamp());
+            self.save_graph(&graph).await?;
+            self.record("clear_observations", vec![entity_name], Vec::new())
+                .await;
+        }
+        Ok(removed)
+    }
+
+    /// Delete exact relations, either direction of a symmetric one;
+    /// `dry_run` reports without persisting
+    pub async fn delete_relations(
+        &self,
+        relations: Vec<Relation>,
+        dry_run: bool,
+    ) -> Result<DeletionReport> {
+        let ontology = self.get_ontology().await?;
+        let mut graph = self.load_graph().await?;
+        let names = NameIndex::new(self.name_policy, &graph);
+        let relations: Vec<Relation> = relations
+            .into_iter()
+            .map(|r| names.resolve_relation(r))
+            .collect();
+        let relations_to_delete: HashSet<_> =
+            relations.iter().map(|r| ontology.relation_key(r)).collect();
+        let mut report = DeletionReport::default();
+
+        graph.relations.retain(|r| {
+            let delete = relations_to_delete.contains(&ontology.relation_key(r));
+            if delete {
+                report.relations.push(r.clone());
+            }
+            !delete
+        });
+
+        if !dry_run {
+            self.save_graph(&graph).await?;
+            self.record("delete_relations", Vec::new(), report.relations.clone())
+                .await;
+        }
+        Ok(report)
+    }
+
+    /// Remove relations whose source or target entity no longer exists, e.g. after a bad
+    /// rename or a hand edit of the memory file. Returns how many were removed.
+    pub async fn prune_dangling_relations(&self) -> Result<usize> {
+        let mut graph = self.load_stored_graph().await?;
+        let removed = remove_dangling_relations(&mut graph);
+        if removed.is_empty() {
+            return Ok(0);
+        }
+
+        let count = removed.len();
+        self.save_graph(&graph).await?;
+        self.record("prune_dangling_relations", Vec::new(), removed)
+            .await;
+        Ok(count)
+    }
+
+    /// Remove every entity and relation; `dry_run` reports without persisting
+    pub async fn clear_graph(&self, dry_run: bool) -> Result<DeletionReport> {
+        let graph = self.load_graph().await?;
+        let mut entities: Vec<String> = graph.entities.keys().cloned().collect();
+        entities.sort();
+        let report = DeletionReport {
+            entities,
+            relations: graph.relations,
+            ..Default::default()
+        };
+
+        if !dry_run {
+            self.save_graph(&KnowledgeGraph::default()).await?;
+            self.record(
+                "clear_graph",
+                report.entities.clone(),
+                report.relations.clone(),
+            )
+            .await;
+        }
+        Ok(report)
+    }
+
+    /// Change the type of every entity typed `from` to `to`, returning how many changed
+    pub async fn rename_entity_type(
+        &self,
+        from: String,
+        to: String,
+        case_insensitive: bool,
+    ) -> Result<usize> {
+        let mut graph = self.load_graph().await?;
+        let from_lower = from.to_lowercase();
+        let now = now_timestamp();
+        let mut changed = Vec::new();
+
+        for entity in graph.entities.values_mut() {
+            let matches = if case_insensitive {
+                entity.entity_type.to_lowercase() == from_lower
+            } else {
+                entity.entity_type == from
+            };
+            if matches && entity.entity_type != to {
+                entity.entity_type = to.clone();
+                entity.updated_at = Some(now);
+                changed.push(entity.name.clone());
+            }
+        }
+
+        let count = changed.len();
+        if count > 0 {
+            changed.sort();
+            self.save_graph(&graph).await?;
+            self.record("rename_entity_type", changed, Vec::new()).await;
+        }
+        Ok(count)
+    }
+
+    /// Replace every occurrence of `find` with `replace` in the observations of the named
+    /// entities, or of all entities, returning how many observations changed. Observations
+    /// that end up identical to an earlier one of the same entity are dropped.
+    pub async fn replace_in_observations(
+        &self,
+        find: String,
+        replace: String,
+        entity_names: Option<Vec<String>>,
+    ) -> Result<usize> {
+        if find.is_empty() {
+            anyhow::bail!("The text to find must not be empty");
+        }
+        let mut graph = self.load_graph().await?;
+        let targets: Option<HashSet<String>> = match entity_names {
+            Some(names) => {
+                let index = NameIndex::new(self.name_policy, &graph);
+                let mut targets = HashSet::new();
+                for name in names {
+                    let name = index.resolve(&name);
+                    if !graph.entities.contains_key(&name) {
+                        anyhow::bail!("Entity with name '{name}' not found");
+                    }
+                    targets.insert(name);
+                }
+                Some(targets)
+            }
+            None => None,
+        };
+
+        let now = now_timestamp();
+        let mut count = 0;
+        let mut changed = Vec::new();
+        for entity in graph.entities.values_mut() {
+            if targets.as_ref().is_some_and(|t| !t.contains(&entity.name)) {
+                continue;
+            }
+            let before = count;
+            for observation in entity.observations.iter_mut() {
+                if observation.content.contains(&find) {
+                    observation.content = observation.content.replace(&find, &replace);
+                    count += 1;
+                }
+            }
+            if count > before {
+                let mut seen = HashSet::new();
+                entity
+                    .observations
+                    .retain(|o| seen.insert(o.content.clone()));
+                entity.updated_at = Some(now);
+                changed.push(entity.name.clone());
+            }
+        }
+
+        if count > 0 {
+            changed.sort();
+            self.save_graph(&graph).await?;
+            self.record("replace_in_observations", changed, Vec::new())
+                .await;
+        }
+        Ok(count)
+    }
+
+    /// Change the type of every relation typed `from` to `to`, returning how many changed.
+    /// Relations that become the same as an existing one, in either direction for a symmetric
+    /// `to`, are dropped and their observations merged into it.
+    pub async fn rename_relation_type(&self, from: String, to: String) -> Result<usize> {
+        let ontology = self.get_ontology().await?;
+        let mut graph = self.load_graph().await?;
+        let mut changed = Vec::new();
+
+        for relation in graph.relations.iter_mut() {
+            if relation.relation_type == from && from != to {
+                relation.relation_type = to.clone();
+                changed.push(relation.clone());
+            }
+        }
+
+        let count = changed.len();
+        if count > 0 {
+            graph.relations =
+                ontology.merge_duplicate_relations(std::mem::take(&mut graph.relations));
+            self.save_graph(&graph).await?;
+            self.record("rename_relation_type", Vec::new(), changed)
+                .await;
+        }
+        Ok(count)
+    }
+
+    /// The memory file line stored for an entity, for debugging serialization
+    pub async fn raw_entity_line(&self, name: String) -> Result<Option<String>> {
+        let graph = self.load_graph().await?;
+        let name = NameIndex::new(self.name_policy, &graph).resolve(&name);
+        graph
+            .entities
+            .get(&name)
+            .map(|entity| {
+                serde_json::to_string(&GraphItemRef::Entity(entity))
+                    .with_context(|| format!("Failed to serialize entity {name}"))
+            })
+            .transpose()
+    }
+
+    pub async fn read_graph(&self) -> Result<Box<KnowledgeGraph>> {
+        self.load_view().await
+    }
+
+    /// The endpoints of the given relations together with every edge among them
+    pub async fn relations_subgraph(&self, relations: &[Relation]) -> Result<Box<KnowledgeGraph>> {
+        let mut names: Vec<String> = relations
+            .iter()
+            .flat_map(|r| [r.from.clone(), r.to.clone()])
+            .collect();
+        names.sort();
+        names.dedup();
+        let opened = self.open_nodes(names, None).await?;
+
+        Ok(Box::new(KnowledgeGraph {
+            entities: opened
+                .entities
+                .into_iter()
+                .map(|e| (e.name.clone(), e))
+                .collect(),
+            relations: opened.relations,
+        }))
+    }
+
+    /// Fetch entities in the order requested, with the relations among them
+    /// and the requested names that matched no entity.
+    /// With `fuzzy`, a name matching no entity resolves to the most similar entity name
+    /// scoring at least that threshold (see `name_similarity`).
+    pub async fn open_nodes(&self, names: Vec<String>, fuzzy: Option<f32>) -> Result<OpenedNodes> {
+        let mut graph = self.load_view().await?;
+        let index = NameIndex::new(self.name_policy, &graph);
+        let all_names: Vec<String> = match fuzzy {
+            Some(_) => graph.entities.keys().cloned().collect(),
+            None => Vec::new(),
+        };
+
+        let mut entities = Vec::new();
+        let mut not_found = Vec::new();
+        let mut fuzzy_matches = Vec::new();
+        for name in names {
+            let mut resolved = index.resolve(&name);
+            if !all_names.contains(&resolved) {
+                let closest = fuzzy.and_then(|threshold| {
+                    all_names
+                        .iter()
+                        .map(|candidate| (candidate, name_similarity(&name, candidate)))
+                        .filter(|(_, similarity)| *similarity >= threshold)
+                        .max_by(|a, b| {
+                            a.1.partial_cmp(&b.1)
+                                .unwrap_or(std::cmp::Ordering::Equal)
+                                .then_with(|| b.0.cmp(a.0))
+                        })
+                });
+                if let Some((matched, similarity)) = closest {
+                    resolved = matched.clone();
+                    fuzzy_matches.push(FuzzyMatch {
+                        requested: name.clone(),
+                        matched: matched.clone(),
+                        similarity,
+                    });
+                }
+            }
+
+            match graph.entities.remove(&resolved) {
+                Some(entity) => entities.push(entity),
+                // Already returned under an equivalent or repeated name
+                None if entities.iter().any(|e| e.name == resolved) => {}
+                None => not_found.push(name),
+            }
+        }
+
+        let found_names: HashSet<_> = entities.iter().map(|e| &e.name).collect();
+        let relations = graph
+            .relations
+            .into_iter()
+            .filter(|r| found_names.contains(&r.from) && found_names.contains(&r.to))
+            .collect();
+
+        Ok(OpenedNodes {
+            entities,
+            relations,
+            not_found,
+            fuzzy_matches,
+        })
+    }
+
+    /// The entities matching any of `names` or having any of `types`, with the relations among them
+    pub async fn fetch(
+        &self,
+        names: Vec<String>,
+        types: Vec<String>,
+    ) -> Result<Box<KnowledgeGraph>> {
+        let graph = self.load_view().await?;
+        let index = NameIndex::new(self.name_policy, &graph);
+        let wanted: HashSet<String> = names.iter().map(|name| index.resolve(name)).collect();
+        let types: HashSet<String> = types.into_iter().collect();
+
+        Ok(Box::new(subgraph(*graph, |entity| {
+            wanted.contains(&entity.name) || types.contains(&entity.entity_type)
+        })))
+    }
+
+    /// An entity together with its relations grouped by type and its neighbors
+    pub async fn describe_entity(&self, name: String) -> Result<EntityDescription> {
+        let mut graph = self.load_view().await?;
+        let name = NameIndex::new(self.name_policy, &graph).resolve(&name);
+        let entity = graph
+            .entities
+            .remove(&name)
+            .with_context(|| format!("Entity with name '{name}' not found"))?;
+
+        let mut outgoing: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut incoming: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut neighbors = BTreeSet::new();
+        for relation in graph.relations {
+            if relation.from == name {
+                neighbors.insert(relation.to.clone());
+                outgoing
+                    .entry(relation.relation_type)
+                    .or_default()
+                    .push(relation.to);
+            } else if relation.to == name {
+                neighbors.insert(relation.from.clone());
+                incoming
+                    .entry(relation.relation_type)
+                    .or_default()
+                    .push(relation.from);
+            }
+        }
+        for names in outgoing.values_mut().chain(incoming.values_mut()) {
+            names.sort();
+        }
+
+        Ok(EntityDescription {
+            observation_count: entity.observations.len(),
+            entity,
+            outgoing,
+            incoming,
+            neighbors: neighbors.into_iter().collect(),
+        })
+    }
+
+    /// Incoming and outgoing relation counts of the named entities, across the whole graph
+    pub async fn degrees(&self, names: &[String]) -> Result<HashMap<String, Degree>> {
+        let graph = self.load_view().await?;
+        let index = NameIndex::new(self.name_policy, &graph);
+
+        let mut degrees: HashMap<String, Degree> = names
+            .iter()
+            .map(|name| index.resolve(name))
+            .filter(|name| graph.entities.contains_key(name))
+            .map(|name| (name, Degree::default()))
+            .collect();
+        for relation in &graph.relations {
+            if let Some(degree) = degrees.get_mut(&relation.from) {
+                degree.outgoing += 1;
+            }
+            if let Some(degree) = degrees.get_mut(&relation.to) {
+                degree.incoming += 1;
+            }
+        }
+
+        Ok(degrees)
+    }
+
+    /// A page of an entity's observations and its total observation count
+    pub async fn get_observations(
+        &self,
+        name: String,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<Observation>, usize)> {
+        let graph = self.load_view().await?;
+        let name = NameIndex::new(self.name_policy, &graph).resolve(&name);
+        let entity = graph
+            .entities
+            .get(&name)
+            .with_context(|| format!("Entity with name '{name}' not found"))?;
+
+        let total = entity.observations.len();
+        let page: Vec<Observation> = entity
+            .observations
+            .iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit.unwrap_or(total))
+            .cloned()
+            .collect();
+
+        Ok((page, total))
+    }
+
+    /// Count, case-insensitively, the entities with an observation containing `term`
+    /// and the term's total occurrences across all observations
+    pub async fn term_frequency(&self, term: String) -> Result<(usize, usize)> {
+        let term = term.to_lowercase();
+        if term.trim().is_empty() {
+            anyhow::bail!("Term must not be empty");
+        }
+
+        let graph = self.load_view().await?;
+        let mut entities = 0;
+        let mut occurrences = 0;
+        for entity in graph.entities.values() {
+            let count: usize = entity
+                .observations
+                .iter()
+                .map(|o| o.content.to_lowercase().matches(&term).count())
+                .sum();
+            if count > 0 {
+                entities += 1;
+                occurrences += count;
+            }
+        }
+
+        Ok((entities, occurrences))
+    }
+
+    /// Map each entity to the sorted, distinct names its relations point to,
+    /// optionally considering only relations of `relation_type`
+    pub async fn adjacency(
+        &self,
+        relation_type: Option<String>,
+    ) -> Result<HashMap<String, Vec<String>>> {
+        let graph = self.load_view().await?;
+        let mut adjacency: HashMap<String, Vec<String>> = graph
+            .entities
+            .keys()
+            .map(|name| (name.clone(), Vec::new()))
+            .collect();
+
+        for relation in graph.relations {
+            if relation_type
+                .as_ref()
+                .is_some_and(|t| *t != relation.relation_type)
+            {
+                continue;
+            }
+            adjacency
+                .entry(relation.from)
+                .or_default()
+                .push(relation.to);
+        }
+        for neighbors in adjacency.values_mut() {
+            neighbors.sort();
+            neighbors.dedup();
+        }
+
+        Ok(adjacency)
+    }
+
+    /// Whether the exact relation is stored, so clients can tell new relations from existing ones.
+    /// Either direction of a symmetric relation counts.
+    pub async fn relation_exists(
+        &self,
+        from: String,
+        to: String,
+        relation_type: String,
+    ) -> Result<bool> {
+        let ontology = self.get_ontology().await?;
+        let graph = self.load_view().await?;
+        let relation = NameIndex::new(self.name_policy, &graph).resolve_relation(Relation {
+            from,
+            to,
+            relation_type,
+            ..Default::default()
+        });
+        let key = ontology.relation_key(&relation);
+        Ok(graph
+            .relations
+            .iter()
+            .any(|r| ontology.relation_key(r) == key))
+    }
+
+    /// Entities whose observations use the same terms as those of `name`, most similar first,
+    /// whether or not any relation links them. Entities sharing no term are left out.
+    pub async fn similar_entities(
+        &self,
+        name: String,
+        limit: Option<usize>,
+    ) -> Result<Vec<SimilarEntity>> {
+        let graph = self.load_view().await?;
+        let seed = NameIndex::new(self.name_policy, &graph).resolve(&name);
+        let Some(seed_entity) = graph.entities.get(&seed) else {
+            anyhow::bail!("Entity with name '{seed}' not found");
+        };
+
+        let terms = |entity: &Entity| -> BTreeSet<String> {
+            entity
+                .observations
+                .iter()
+                .flat_map(|o| tokenize(&o.content))
+                .collect()
+        };
+        let seed_terms = terms(seed_entity);
+
+        let mut similar: Vec<SimilarEntity> = graph
+            .entities
+            .values()
+            .filter(|entity| entity.name != seed)
+            .filter_map(|entity| {
+                let other_terms = terms(entity);
+                let shared_terms: Vec<String> =
+                    seed_terms.intersection(&other_terms).cloned().collect();
+                if shared_terms.is_empty() {
+                    return None;
+                }
+                let union = seed_terms.union(&other_terms).count();
+                Some(SimilarEntity {
+                    entity: entity.clone(),
+                    similarity: shared_terms.len() as f32 / union as f32,
+                    shared_terms,
+                })
+            })
+            .collect();
+        similar.sort_by(|a, b| {
+            b.similarity
+                .total_cmp(&a.similarity)
+                .then_with(|| a.entity.name.cmp(&b.entity.name))
+        });
+        if let Some(limit) = limit {
+            similar.truncate(limit);
+        }
+        Ok(similar)
+    }
+
+    /// Entities within `depth` hops of `name`, closest first, then most connected, then by name
+    pub async fn related_entities(
+        &self,
+        name: String,
+        depth: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<RelatedEntity>> {
+        let mut graph = self.load_view().await?;
+        let seed = NameIndex::new(self.name_policy, &graph).resolve(&name);
+        if !graph.entities.contains_key(&seed) {
+            anyhow::bail!("Entity with name '{seed}' not found");
+        }
+
+        let mut neighbors: HashMap<&str, Vec<&str>> = HashMap::new();
+        for relation in &graph.relations {
+            neighbors
+                .entry(&relation.from)
+                .or_default()
+                .push(&relation.to);
+            neighbors
+                .entry(&relation.to)
+                .or_default()
+                .push(&relation.from);
+        }
+
+        let mut distances: HashMap<&str, usize> = HashMap::from([(seed.as_str(), 0)]);
+        let mut queue = VecDeque::from([seed.as_str()]);
+        while let Some(current) = queue.pop_front() {
+            let distance = distances[current];
+            if distance == depth {
+                continue;
+            }
+            for next in neighbors.get(current).into_iter().flatten() {
+                if !distances.contains_key(next) {
+                    distances.insert(next, distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut ranked: Vec<(String, usize, usize)> = distances
+            .into_iter()
+            .filter(|(name, distance)| *distance > 0 && graph.entities.contains_key(*name))
+            .map(|(name, distance)| {
+                let degree = neighbors.get(name).map_or(0, Vec::len);
+                (name.to_string(), distance, degree)
+            })
+            .collect();
+        ranked.sort_by(|a, b| {
+            a.1.cmp(&b.1)
+                .then_with(|| b.2.cmp(&a.2))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        if let Some(limit) = limit {
+            ranked.truncate(limit);
+        }
+
+        Ok(ranked
+            .into_iter()
+            .filter_map(|(name, distance, degree)| {
+                graph.entities.remove(&name).map(|entity| RelatedEntity {
+                    entity,
+                    distance,
+                    degree,
+                })
+            })
+            .collect())
+    }
+
+    /// A Graphviz digraph of `name` and the entities within `depth` hops of it,
+    /// with the relations among them and the seed highlighted
+    pub async fn export_ego_dot(&self, name: String, depth: usize) -> Result<String> {
+        let related = self.related_entities(name.clone(), depth, None).await?;
+        let graph = self.load_view().await?;
+        let seed = NameIndex::new(self.name_policy, &graph).resolve(&name);
+        let mut wanted: HashSet<String> = related.into_iter().map(|r| r.entity.name).collect();
+        wanted.insert(seed.clone());
+
+        let ego = subgraph(*graph, |entity| wanted.contains(&entity.name));
+        Ok(render_ego_dot(&ego, &seed))
+    }
+
+    /// Up to `max_paths` simple paths from `from` to `to` of at most `max_length` relations,
+    /// following relations in either direction, shortest first. Empty when `to` is out of reach.
+    /// Both limits are capped by `MAX_PATHS` and `MAX_PATH_LENGTH`, and the search runs on a
+    /// blocking thread.
+    pub async fn find_paths(
+        &self,
+        from: String,
+        to: String,
+        max_paths: usize,
+        max_length: usize,
+    ) -> Result<Vec<GraphPath>> {
+        let graph = self.load_view().await?;
+        let names = NameIndex::new(self.name_policy, &graph);
+        let (from, to) = (names.resolve(&from), names.resolve(&to));
+        for name in [&from, &to] {
+            if !graph.entities.contains_key(name) {
+                anyhow::bail!("Entity with name '{name}' not found");
+            }
+        }
+
+        let (max_paths, max_length) = (max_paths.min(MAX_PATHS), max_length.min(MAX_PATH_LENGTH));
+        let paths = tokio::task::spawn_blocking(move || {
+            let mut edges: HashMap<&str, Vec<(&str, &Relation)>> = HashMap::new();
+            for relation in &graph.relations {
+                edges
+                    .entry(&relation.from)
+                    .or_default()
+                    .push((&relation.to, relation));
+                edges
+                    .entry(&relation.to)
+                    .or_default()
+                    .push((&relation.from, relation));
+            }
+            for neighbors in edges.values_mut() {
+                neighbors.sort_by(|a, b| {
+                    a.0.cmp(b.0)
+                        .then_with(|| a.1.relation_type.cmp(&b.1.relation_type))
+                });
+            }
+
+            // Breadth-first from the end, no further than a path may be long
+            let mut distances = HashMap::from([(to.as_str(), 0)]);
+            let mut frontier = VecDeque::from([to.as_str()]);
+            while let Some(current) = frontier.pop_front() {
+                let distance = distances[current];
+                if distance == max_length {
+                    continue;
+                }
+                for (next, _) in edges.get(current).into_iter().flatten() {
+                    if !distances.contains_key(next) {
+                        distances.insert(next, distance + 1);
+                        frontier.push_back(next);
+                    }
+                }
+            }
+
+            // Iterative deepening keeps memory to one path while yielding the shortest paths
+            // first; it starts at the shortest length that can reach the end
+            let mut search = PathSearch {
+                edges,
+                distances,
+                to: &to,
+                max_paths,
+                paths: Vec::new(),
+            };
+            let Some(&shortest) = search.distances.get(from.as_str()) else {
+                return Vec::new();
+            };
+            for length in shortest.max(1)..=max_length {
+                if search.paths.len() >= max_paths {
+                    break;
+                }
+                search.walk(length, &mut vec![from.as_str()], &mut Vec::new());
+            }
+            search.paths
+        })
+        .await?;
+        Ok(paths)
+    }
+
+    /// Count the relations connecting `from` and `to`, in either direction unless `directed`
+    pub async fn relation_count_between(
+        &self,
+        from: String,
+        to: String,
+        directed: bool,
+    ) -> Result<usize> {
+        let graph = self.load_view().await?;
+        let names = NameIndex::new(self.name_policy, &graph);
+        let (from, to) = (names.resolve(&from), names.resolve(&to));
+
+        Ok(graph
+            .relations
+            .iter()
+            .filter(|r| {
+                (r.from == from && r.to == to) || (!directed && r.from == to && r.to == from)
+            })
+            .count())
+    }
+
+    /// The save counter and the time of the last change.
+    /// Before the first save, the memory file's modification time is reported.
+    pub async fn modification_info(&self) -> Result<ModificationInfo> {
+        let version = self.version.load(Ordering::SeqCst);
+        let last_saved = self.last_saved.load(Ordering::SeqCst);
+        let last_modified = match (&self.store, last_saved) {
+            (_, saved) if saved > 0 => Some(saved),
+            (GraphStore::File(path), _) => match fs::metadata(path).await {
+                Ok(metadata) => metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to read metadata of {}", path.display()))
+                }
+            },
+            (GraphStore::Memory(_), _) => None,
+        };
+
+        Ok(ModificationInfo {
+            version,
+            last_modified,
+        })
+    }
+
+    /// Entities ranked by observation count, most first unless `ascending`; ties go by name
+    pub async fn entities_by_observation_count(
+        &self,
+        limit: Option<usize>,
+        ascending: bool,
+    ) -> Result<Vec<Entity>> {
+        let graph = self.load_view().await?;
+        let mut entities: Vec<Entity> = graph.entities.into_values().collect();
+        entities.sort_by(|a, b| {
+            let by_count = a.observations.len().cmp(&b.observations.len());
+            if ascending {
+                by_count
+            } else {
+                by_count.reverse()
+            }
+            .then_with(|| a.name.cmp(&b.name))
+        });
+        if let Some(limit) = limit {
+            entities.truncate(limit);
+        }
+        Ok(entities)
+    }
+
+    /// Entities created or updated at or after `since` (Unix seconds), oldest change first.
+    /// Timestamps only have second resolution, so a change in the same second as `since`
+    /// is reported again rather than missed. Entities without timestamps are never reported.
+    pub async fn entities_changed_since(&self, since: i64) -> Result<Vec<Entity>> {
+        let graph = self.load_view().await?;
+        let mut changed: Vec<Entity> = graph
+            .entities
+            .into_values()
+            .filter(|e| e.updated_at.or(e.created_at).is_some_and(|t| t >= since))
+            .collect();
+        changed.sort_by(|a, b| {
+            a.updated_at
+                .or(a.created_at)
+                .cmp(&b.updated_at.or(b.created_at))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(changed)
+    }
+
+    /// Entities whose `field` timestamp lies between `start` and `end` (Unix seconds, both
+    /// inclusive, either open when `None`), oldest first. Entities without that timestamp,
+    /// such as ones loaded from files written before timestamps existed, are left out.
+    pub async fn entities_in_range(
+        &self,
+        start: Option<i64>,
+        end: Option<i64>,
+        field: TimeField,
+    ) -> Result<Vec<Entity>> {
+        if let (Some(start), Some(end)) = (start, end) {
+            if start > end {
+                anyhow::bail!("Range start {start} is after its end {end}");
+            }
+        }
+
+        let graph = self.load_view().await?;
+        let mut found: Vec<Entity> = graph
+            .entities
+            .into_values()
+            .filter(|e| {
+                field.of(e).is_some_and(|t| {
+                    start.is_none_or(|start| t >= start) && end.is_none_or(|end| t <= end)
+                })
+            })
+            .collect();
+        found.sort_by(|a, b| {
+            field
+                .of(a)
+                .cmp(&field.of(b))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(found)
+    }
+
+    /// Density, clustering, components and diameter of the graph. Above
+    /// `EXACT_DIAMETER_LIMIT` entities the diameter is estimated unless `exact_diameter` is set.
+    pub async fn graph_metrics(&self, exact_diameter: bool) -> Result<GraphMetrics> {
+        let graph = self.load_view().await?;
+        Ok(GraphMetrics::from_graph(&graph, exact_diameter))
+    }
+
+    /// The `k` most central entities by `centrality`, with the relations among them, as a
+    /// compact map of the graph. Entities without relations are never included.
+    pub async fn core_concepts(&self, k: usize, centrality: Centrality) -> Result<CoreConcepts> {
+        let graph = self.load_view().await?;
+        Ok(CoreConcepts::from_graph(&graph, k, centrality))
+    }
+
+    pub async fn get_detailed_stats(&self) -> Result<DetailedStats> {
+        let graph = self.load_view().await?;
+        Ok(DetailedStats::from_graph(&graph))
+    }
+
+    /// Counts, rankings and type distribution in one pass over the graph
+    pub async fn overview(&self, limit: usize) -> Result<GraphOverview> {
+        let graph = self.load_view().await?;
+        Ok(GraphOverview::from_graph(&graph, limit))
+    }
+
+    /// Entity, relation, observation and per-type totals. They are cached until the graph
+    /// changes, so repeated calls without changes in between don't rescan it; the first call
+    /// after a save, or after another process edits the memory file, counts again. Mutations
+    /// themselves never count. Federated files can change underneath the server and are
+    /// counted on every call.
+    pub async fn counts(&self) -> Result<GraphCounts> {
+        if !self.federated_paths.is_empty() {
+            return Ok(GraphCounts::from_graph(&*self.load_view().await?));
+        }
+
+        let version = self.data_version().await;
+        let mut counts = self.counts.lock().await;
+        if let Some((counted_at, counts)) = counts.as_ref() {
+            if *counted_at == version {
+                return Ok(counts.clone());
+            }
+        }
+        let computed = GraphCounts::from_graph(&*self.load_graph().await?);
+        *counts = Some((version, computed.clone()));
+        Ok(computed)
+    }
+
+    /// Identifies the contents of the primary graph for caching: the save counter combined
+    /// with the memory file's size and modification time, so that writes by another process
+    /// or a hand edit are noticed too
+    async fn data_version(&self) -> u64 {
+        let version = self.version.load(Ordering::SeqCst);
+        let GraphStore::File(memory_file_path) = &self.store else {
+            return version;
+        };
+
+        let mut hasher = DefaultHasher::new();
+        version.hash(&mut hasher);
+        fs::metadata(memory_file_path)
+            .await
+            .ok()
+            .map(|metadata| (metadata.len(), metadata.modified().ok()))
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub async fn get_stats(&self) -> Result<(usize, usize)> {
+        let counts = self.counts().await?;
+        Ok((counts.entities, counts.relations))
+    }
+
+    pub async fn search_nodes(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+        options: &SearchOptions,
+    ) -> Result<SearchResults> {
+        // Taken before loading, so a concurrent edit can only make the cached results newer
+        let data_version = self.data_version().await;
+        let graph = self.load_view().await?;
+        let options = SearchOptions {
+            ranker_config: Some(self.get_search_config().await?),
+            // Federated files aren't tracked by the version
+            graph_version: self.federated_paths.is_empty().then_some(data_version),
+            ..options.clone()
+        };
+
+        let RankedEntities {
+            entities,
+            near_duplicates_suppressed,
+        } = self
+            .search_engine
+            .ranked_search(&graph, query, limit, &options)
+            .await?;
+
+        let filtered_entity_names: HashSet<String> =
+            entities.iter().map(|e| e.name.clone()).collect();
+
+        let filtered_relations = self.search_engine.filter_relations_smart(
+            &graph.relations,
+            &filtered_entity_names,
+            false,
+            options.include_related_entities,
+            options.related_entity_threshold,
+        );
+
+        let mut entities: HashMap<String, Entity> =
+            entities.into_iter().map(|e| (e.name.clone(), e)).collect();
+
+        // Pull in neighbors reached through the included relations
+        for relation in &filtered_relations {
+            for name in [&relation.from, &relation.to] {
+                if !entities.contains_key(name) {
+                    if let Some(entity) = graph.entities.get(name) {
+                        entities.insert(name.clone(), entity.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(SearchResults {
+            entities,
+            relations: filtered_relations,
+            near_duplicates_suppressed,
+        })
+    }
+
+    /// Relations touching any entity matching `entity_query`, optionally only those of
+    /// `relation_type`, with the entities at both ends. Matching entities report their
+    /// `matchedFields`; entities only reached through a relation don't.
+    pub async fn relations_matching(
+        &self,
+        entity_query: String,
+        relation_type: Option<String>,
     ) -> Result<Box<KnowledgeGraph>> {
-        let graph = self.load_graph().await?;
+        let graph = self.load_view().await?;
+        let options = SearchOptions {
+            ranker_config: Some(self.get_search_config().await?),
+            ..Default::default()
+        };
+        let mut entities: HashMap<String, Entity> = self
+            .search_engine
+            .enhanced_text_search(&graph, &entity_query, Some(usize::MAX), &options)
+            .await?
+            .into_iter()
+            // Structural bonuses alone don't make an entity a match
+            .filter(|e| !e.matched_fields.is_empty())
+            .map(|e| (e.name.clone(), e))
+            .collect();
+
+        let relations: Vec<Relation> = graph
+            .relations
+            .iter()
+            .filter(|r| relation_type.as_ref().is_none_or(|t| &r.relation_type == t))
+            .filter(|r| entities.contains_key(&r.from) || entities.contains_key(&r.to))
+            .cloned()
+            .collect();
+        for relation in &relations {
+            for name in [&relation.from, &relation.to] {
+                if !entities.contains_key(name) {
+                    if let Some(entity) = graph.entities.get(name) {
+                        entities.insert(name.clone(), entity.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(Box::new(KnowledgeGraph {
+            entities,
+            relations,
+        }))
+    }
+
+    pub async fn validate(&self) -> Result<ValidationReport> {
+        let (entities, relations) = self.load_items().await?;
+        Ok(ValidationReport::from_items(&entities, &relations))
+    }
+
+    /// Entities holding observations from `source`, restricted to those observations
+    pub async fn find_by_source(&self, source: String) -> Result<Vec<Entity>> {
+        let graph = self.load_view().await?;
+
+        let mut entities: Vec<Entity> = graph
+            .entities
+            .into_values()
+            .filter_map(|mut entity| {
+                entity
+                    .observations
+                    .retain(|o| o.source.as_deref() == Some(source.as_str()));
+                (!entity.observations.is_empty()).then_some(entity)
+            })
+            .collect();
+        entities.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(entities)
+    }
+
+    /// Entities holding observations tagged `key`, with `value` if given,
+    /// restricted to those observations
+    pub async fn search_by_tag(&self, key: String, value: Option<String>) -> Result<Vec<Entity>> {
+        let graph = self.load_view().await?;
+
+        let mut entities: Vec<Entity> = graph
+            .entities
+            .into_values()
+            .filter_map(|mut entity| {
+                entity.observations.retain(|o| {
+                    o.tags
+                        .get(&key)
+                        .is_some_and(|tagged| value.as_ref().is_none_or(|v| v == tagged))
+                });
+                (!entity.observations.is_empty()).then_some(entity)
+            })
+            .collect();
+        entities.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(entities)
+    }
+
+    pub async fn entities_with_observation(
+        &self,
+        substring: String,
+        case_sensitive: bool,
+    ) -> Result<Vec<Entity>> {
+        let graph = self.load_view().await?;
+        Ok(self
+            .search_engine
+            .entities_with_observation(&graph, &substring, case_sensitive))
+    }
+
+    /// Entities ranked by how many of `terms` their observations mention, those covering
+    /// every term first. Blank and repeated terms are ignored.
+    pub async fn entities_covering(&self, terms: Vec<String>) -> Result<Vec<EntityCoverage>> {
+        let mut seen = HashSet::new();
+        let terms: Vec<String> = terms
+            .into_iter()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty() && seen.insert(t.to_lowercase()))
+            .collect();
+        if terms.is_empty() {
+            anyhow::bail!("At least one term is required");
+        }
+
+        let graph = self.load_view().await?;
+        Ok(self.search_engine.entities_covering(&graph, &terms))
+    }
+
+    /// Propose merging the entities matching `query` into one, without applying it
+    pub async fn consolidation_preview(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<ConsolidationPreview> {
+        let graph = self.load_view().await?;
+        let matches: Vec<Entity> = self
+            .search_engine
+            .enhanced_text_search(
+                &graph,
+                query,
+                limit,
+                &SearchOptions {
+                    ranker_config: Some(self.get_search_config().await?),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .into_iter()
+            .filter(|e| !e.matched_fields.is_empty())
+            .collect();
+
+        // Earlier (better ranked) entities win ties
+        let canonical = matches
+            .iter()
+            .rev()
+            .max_by_key(|e| e.observations.len())
+            .with_context(|| format!("No entities match '{query}'"))?;
+        let mut type_counts: Vec<(&String, usize)> = Vec::new();
+        for entity in &matches {
+            match type_counts
+                .iter_mut()
+                .find(|(t, _)| *t == &entity.entity_type)
+            {
+                Some((_, count)) => *count += 1,
+                None => type_counts.push((&entity.entity_type, 1)),
+            }
+        }
+        let entity_type = type_counts
+            .iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map(|(t, _)| (*t).clone())
+            .unwrap_or_default();
+
+        let mut seen = HashSet::new();
+        let observations = matches
+            .iter()
+            .flat_map(|e| &e.observations)
+            .filter(|o| seen.insert(o.content.clone()))
+            .cloned()
+            .collect();
+
+        let merged: HashSet<&String> = matches.iter().map(|e| &e.name).collect();
+        let redirect = |name: &String| {
+            if merged.contains(name) {
+                canonical.name.clone()
+            } else {
+                name.clone()
+            }
+        };
+        let mut seen = HashSet::new();
+        let relations = graph
+            .relations
+            .iter()
+            .filter(|r| merged.contains(&r.from) || merged.contains(&r.to))
+            .map(|r| Relation {
+                from: redirect(&r.from),
+                to: redirect(&r.to),
+                relation_type: r.relation_type.clone(),
+                observations: r.observations.clone(),
+            })
+            .filter(|r| r.from != r.to && seen.insert(r.clone()))
+            .collect();
+
+        Ok(ConsolidationPreview {
+            canonical_name: canonical.name.clone(),
+            entity_type,
+            merged: matches.iter().map(|e| e.name.clone()).collect(),
+            observations,
+            relations,
+        })
+    }
+
+    pub async fn search_observations(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<ObservationMatch>> {
+        let graph = self.load_view().await?;
+        Ok(self.search_engine.observation_search(&graph, query, limit))
+    }
+}
+
+impl Default for KnowledgeGraphManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entity(name: &str, entity_type: &str, observations: &[&str]) -> Entity {
+        Entity {
+            name: name.to_string(),
+            entity_type: entity_type.to_string(),
+            observations: observations.iter().map(|o| (*o).into()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn relation(from: &str, to: &str, relation_type: &str) -> Relation {
+        Relation {
+            from: from.to_string(),
+            to: to.to_string(),
+            relation_type: relation_type.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_and_read_entities() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = vec![Entity {
+            name: "Alice".to_string(),
+            entity_type: "Person".to_string(),
+            observations: vec!["Likes coffee".into()],
+            ..Default::default()
+        }];
+
+        let created = manager
+            .create_entities(entities.clone(), None)
+            .await?
+            .created;
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].name, "Alice");
+
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.entities.len(), 1);
+        let alice = graph.entities.get("Alice").unwrap();
+        assert_eq!(alice.name, "Alice");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_boxed_manager() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = Box::new(KnowledgeGraphManager::with_path(&temp_path));
+
+        let entities = vec![Entity {
+            name: "BoxedEntity".to_string(),
+            entity_type: "Test".to_string(),
+            observations: vec!["Created with Box".into()],
+            ..Default::default()
+        }];
+
+        let created = manager.create_entities(entities, None).await?.created;
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].name, "BoxedEntity");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_large_graph_in_heap() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_large_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let mut large_entities = Vec::new();
+        for i in 0..10000 {
+            large_entities.push(Entity {
+                name: format!("LargeEntity_{i}"),
+                entity_type: "TestLarge".to_string(),
+                observations: vec![
+                    format!("Observation 1 for {}", i).into(),
+                    format!("Observation 2 for {}", i).into(),
+                    format!("Large data set with ID {}", i).into(),
+                ],
+                ..Default::default()
+            });
+        }
+
+        let created = manager.create_entities(large_entities, None).await?.created;
+        assert_eq!(created.len(), 10000);
+
+        let (entity_count, relation_count) = manager.get_stats().await?;
+        assert_eq!(entity_count, 10000);
+        assert_eq!(relation_count, 0);
+
+        let search_result = manager
+            .search_nodes("LargeEntity_9999", None, &SearchOptions::default())
+            .await?;
+        // Enhanced search may return multiple similar entities
+        assert!(
+            !search_result.entities.is_empty(),
+            "Should find at least LargeEntity_9999"
+        );
+        assert!(
+            search_result.entities.contains_key("LargeEntity_9999"),
+            "Should find LargeEntity_9999"
+        );
+        let large_entity = search_result.entities.get("LargeEntity_9999").unwrap();
+        assert_eq!(large_entity.name, "LargeEntity_9999");
+
+        let full_graph = manager.read_graph().await?;
+        assert_eq!(full_graph.entities.len(), 10000);
+
+        println!(
+            "Successfully handled {} entities in heap",
+            full_graph.entities.len()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_arc_manager() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = std::sync::Arc::new(KnowledgeGraphManager::with_path(&temp_path));
+        let manager_clone = manager.clone();
+
+        assert_eq!(std::sync::Arc::strong_count(&manager), 2);
+
+        let entities = vec![Entity {
+            name: "ArcEntity".to_string(),
+            entity_type: "Test".to_string(),
+            observations: vec!["Created with Arc".into()],
+            ..Default::default()
+        }];
+
+        let created = manager_clone.create_entities(entities, None).await?.created;
+        assert_eq!(created.len(), 1);
+
+        let graph = manager.read_graph().await?; // Box<KnowledgeGraph>
+        assert_eq!(graph.entities.len(), 1);
+        let arc_entity = graph.entities.get("ArcEntity").unwrap();
+        assert_eq!(arc_entity.name, "ArcEntity");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_relations() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        // Ensure we start with a clean graph
+        let initial_graph = manager.read_graph().await?;
+        assert_eq!(initial_graph.relations.len(), 0);
+
+        let entities = vec![
+            Entity {
+                name: "Alice".to_string(),
+                entity_type: "Person".to_string(),
+                observations: vec![],
+                ..Default::default()
+            },
+            Entity {
+                name: "Bob".to_string(),
+                entity_type: "Person".to_string(),
+                observations: vec![],
+                ..Default::default()
+            },
+        ];
+
+        manager.create_entities(entities, None).await?;
+
+        // Verify entities were created
+        let graph_after_entities = manager.read_graph().await?;
+        assert_eq!(graph_after_entities.entities.len(), 2);
+        assert_eq!(graph_after_entities.relations.len(), 0);
+
+        let relations = vec![Relation {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            relation_type: "knows".to_string(),
+            ..Default::default()
+        }];
+
+        let created_relations = manager.create_relations(relations.clone()).await?.created;
+
+        // Debug information for CI failures
+        if created_relations.len() != 1 {
+            let current_graph = manager.read_graph().await?;
+            panic!(
+                "Expected 1 created relation, got {}. Current graph has {} relations: {:?}. Trying to create: {:?}",
+                created_relations.len(),
+                current_graph.relations.len(),
+                current_graph.relations,
+                relations
+            );
+        }
+
+        assert_eq!(created_relations.len(), 1);
+
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.relations.len(), 1);
+        assert_eq!(graph.relations[0].from, "Alice");
+        assert_eq!(graph.relations[0].to, "Bob");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_split_entity() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![
+                    Entity {
+                        name: "Alice".to_string(),
+                        entity_type: "Person".to_string(),
+                        observations: vec!["Likes tea".into(), "Works at Acme".into()],
+                        ..Default::default()
+                    },
+                    Entity {
+                        name: "Acme".to_string(),
+                        entity_type: "Company".to_string(),
+                        observations: vec![],
+                        ..Default::default()
+                    },
+                ],
+                None,
+            )
+            .await?;
+        let works_at = Relation {
+            from: "Alice".to_string(),
+            to: "Acme".to_string(),
+            relation_type: "works_at".to_string(),
+            ..Default::default()
+        };
+        manager.create_relations(vec![works_at.clone()]).await?;
+
+        let error = manager
+            .split_entity(
+                "Alice".to_string(),
+                "Acme".to_string(),
+                vec![],
+                None,
+                vec![],
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("already exists"));
+        let error = manager
+            .split_entity(
+                "Alice".to_string(),
+                "Alice at work".to_string(),
+                vec!["Likes coffee".to_string()],
+                None,
+                vec![],
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Likes coffee"));
+
+        let split = manager
+            .split_entity(
+                "Alice".to_string(),
+                "Alice at work".to_string(),
+                vec!["Works at Acme".to_string()],
+                None,
+                vec![works_at],
+            )
+            .await?;
+        assert_eq!(split.original.observations, vec!["Likes tea"]);
+        assert_eq!(split.created.observations, vec!["Works at Acme"]);
+        assert_eq!(split.created.entity_type, "Person");
+        assert_eq!(split.relinked[0].from, "Alice at work");
+
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.entities.len(), 3);
+        assert_eq!(graph.entities["Alice"].observations, vec!["Likes tea"]);
+        assert_eq!(graph.relations, split.relinked);
+
+        // Relinking both directions of a symmetric relation, stored before the type was
+        // declared symmetric, leaves one relation
+        manager
+            .create_entities(vec![entity("Bob", "Person", &[])], None)
+            .await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "sibling_of"),
+                relation("Bob", "Alice", "sibling_of"),
+            ])
+            .await?;
+        manager
+            .set_ontology(Ontology {
+                symmetric_relation_types: vec!["sibling_of".to_string()],
+                ..Default::default()
+            })
+            .await?;
+        manager
+            .split_entity(
+                "Alice".to_string(),
+                "Alice junior".to_string(),
+                vec![],
+                None,
+                vec![
+                    relation("Alice", "Bob", "sibling_of"),
+                    relation("Bob", "Alice", "sibling_of"),
+                ],
+            )
+            .await?;
+        let siblings: Vec<Relation> = manager
+            .read_graph()
+            .await?
+            .relations
+            .into_iter()
+            .filter(|r| r.relation_type == "sibling_of")
+            .collect();
+        assert_eq!(siblings, [relation("Alice junior", "Bob", "sibling_of")]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raw_entity_line() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec!["Says \"hi\"".into()],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        let line = manager.raw_entity_line("Alice".to_string()).await?.unwrap();
+        let stored = fs::read_to_string(&temp_path).await?;
+        assert!(stored.lines().any(|l| l == line));
+        assert!(line.contains(r#""type":"entity""#));
+        assert!(line.contains(r#""entityType":"Person""#));
+        assert!(line.contains(r#""Says \"hi\"""#));
+
+        assert_eq!(manager.raw_entity_line("Bob".to_string()).await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_entities_by_observation_count() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![
+                    entity("Alice", "Person", &["a", "b"]),
+                    entity("Bob", "Person", &[]),
+                    entity("Carol", "Person", &["a", "b", "c"]),
+                    entity("Dave", "Person", &[]),
+                ],
+                None,
+            )
+            .await?;
+        let names = |entities: Vec<Entity>| -> Vec<String> {
+            entities.into_iter().map(|e| e.name).collect()
+        };
+
+        assert_eq!(
+            names(manager.entities_by_observation_count(None, false).await?),
+            vec!["Carol", "Alice", "Bob", "Dave"]
+        );
+        assert_eq!(
+            names(manager.entities_by_observation_count(Some(2), true).await?),
+            vec!["Bob", "Dave"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_core_concepts() {
+        // Rust is used by everything, Cargo by two, the rest by nothing
+        let graph = graph_from_items(
+            ["Rust", "Cargo", "Tokio", "Serde", "Rayon", "Unrelated"]
+                .map(|n| entity(n, "Concept", &[]))
+                .to_vec(),
+            vec![
+                relation("Cargo", "Rust", "uses"),
+                relation("Tokio", "Rust", "uses"),
+                relation("Serde", "Rust", "uses"),
+                relation("Rayon", "Rust", "uses"),
+                relation("Tokio", "Cargo", "uses"),
+                relation("Serde", "Cargo", "uses"),
+            ],
+        );
+        let names = |core: &CoreConcepts| -> Vec<String> {
+            core.concepts.iter().map(|c| c.name.clone()).collect()
+        };
+
+        let by_degree = CoreConcepts::from_graph(&graph, 2, Centrality::Degree);
+        assert_eq!(names(&by_degree), vec!["Rust", "Cargo"]);
+        assert_eq!(by_degree.concepts[0].score, 4.0);
+        assert_eq!(by_degree.relations, vec![relation("Cargo", "Rust", "uses")]);
+
+        let by_pagerank = CoreConcepts::from_graph(&graph, 3, Centrality::PageRank);
+        assert_eq!(names(&by_pagerank), vec!["Rust", "Cargo", "Rayon"]);
+        assert!(by_pagerank.concepts[0].score > by_pagerank.concepts[1].score);
+        assert_eq!(
+            by_pagerank.relations,
+            vec![
+                relation("Cargo", "Rust", "uses"),
+                relation("Rayon", "Rust", "uses")
+            ]
+        );
+
+        // Entities without relations are left out however large k is
+        let all = CoreConcepts::from_graph(&graph, 100, Centrality::Degree);
+        assert_eq!(all.concepts.len(), 5);
+        assert_eq!(all.relations.len(), 6);
+    }
+
+    #[test]
+    fn test_graph_metrics() {
+        // A triangle with a tail (A-B-C-A, C-D), a separate pair and an isolated entity
+        let graph = KnowledgeGraph {
+            entities: ["A", "B", "C", "D", "E", "F", "G"]
+                .map(|name| (name.to_string(), entity(name, "Person", &[])))
+                .into_iter()
+                .collect(),
+            relations: vec![
+                relation("A", "B", "knows"),
+                relation("B", "C", "knows"),
+                relation("C", "A", "knows"),
+                relation("A", "C", "knows"),
+                relation("C", "D", "knows"),
+                relation("E", "F", "knows"),
+                relation("G", "G", "knows"),
+            ],
+        };
+
+        let metrics = GraphMetrics::from_graph(&graph, false);
+        assert_eq!(metrics.links, 5);
+        assert_eq!(metrics.density, 5.0 / 21.0);
+        // A and B close their only pair, C one of three
+        assert!((metrics.average_clustering - (1.0 + 1.0 + 1.0 / 3.0) / 7.0).abs() < 1e-9);
+        assert_eq!(metrics.connected_components, 3);
+        assert_eq!(metrics.largest_component, 4);
+        assert_eq!(metrics.diameter, 2);
+        assert!(metrics.diameter_exact);
+
+        assert_eq!(
+            GraphMetrics::from_graph(&KnowledgeGraph::default(), false),
+            GraphMetrics {
+                diameter_exact: true,
+                ..Default::default()
+            }
+        );
+
+        // A long chain is sampled, and the sweep from a far end still finds its length
+        let chain: Vec<String> = (0..EXACT_DIAMETER_LIMIT + 10)
+            .map(|i| format!("{i:05}"))
+            .collect();
+        let graph = KnowledgeGraph {
+            entities: chain
+                .iter()
+                .map(|name| (name.clone(), entity(name, "Person", &[])))
+                .collect(),
+            relations: chain
+                .windows(2)
+                .map(|w| relation(&w[0], &w[1], "knows"))
+                .collect(),
+        };
+        let metrics = GraphMetrics::from_graph(&graph, false);
+        assert!(!metrics.diameter_exact);
+        assert_eq!(metrics.diameter, chain.len() - 1);
+    }
+
+    #[tokio::test]
+    async fn test_detailed_stats() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        assert_eq!(
+            manager.get_detailed_stats().await?,
+            DetailedStats::default()
+        );
+
+        manager
+            .create_entities(
+                vec![
+                    entity("Alice", "Person", &["a", "b", "c"]),
+                    entity("Bob", "Person", &["a"]),
+                    entity("Carol", "Person", &[]),
+                    entity("Dave", "Person", &["a", "b"]),
+                ],
+                None,
+            )
+            .await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Bob", "Dave", "knows"),
+            ])
+            .await?;
+
+        let stats = manager.get_detailed_stats().await?;
+        assert_eq!((stats.entities, stats.relations), (4, 2));
+        assert_eq!(stats.observations, 6);
+        assert_eq!(stats.average_observations_per_entity, 1.5);
+        assert_eq!(
+            stats.most_observations,
+            Some(NamedCount {
+                name: "Alice".to_string(),
+                count: 3
+            })
+        );
+        assert_eq!(stats.average_degree, 1.0);
+        assert_eq!(
+            stats.max_degree,
+            Some(NamedCount {
+                name: "Bob".to_string(),
+                count: 2
+            })
+        );
+        assert_eq!(stats.isolated_entities, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_counts_track_mutations() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        KnowledgeGraphManager::with_path(&temp_path)
+            .create_entities(vec![entity("Alice", "Person", &["a", "b"])], None)
+            .await?;
+
+        // A fresh manager counts the existing file on first use
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+        async fn assert_consistent(manager: &KnowledgeGraphManager) -> Result<()> {
+            let counts = manager.counts().await?;
+            assert_eq!(
+                counts,
+                GraphCounts::from_graph(&*manager.read_graph().await?)
+            );
+            Ok(())
+        }
+        assert_consistent(&manager).await?;
+
+        manager
+            .create_entities(
+                vec![
+                    entity("Bob", "Person", &["c"]),
+                    entity("Acme", "Company", &[]),
+                ],
+                None,
+            )
+            .await?;
+        assert_consistent(&manager).await?;
+        manager
+            .create_entities(
+                vec![entity("Alice", "Person", &["b", "d"])],
+                Some(DuplicatePolicy::Merge),
+            )
+            .await?;
+        assert_consistent(&manager).await?;
+        manager
+            .upsert_entities(vec![entity("Acme", "Organization", &["e"])], true)
+            .await?;
+        assert_consistent(&manager).await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Bob", "Acme", "knows"),
+            ])
+            .await?;
+        assert_consistent(&manager).await?;
+        manager
+            .add_observations(
+                vec![("Bob".to_string(), vec!["f".into()])],
+                ObservationOrder::default(),
+                false,
+            )
+            .await?;
+        assert_consistent(&manager).await?;
+        manager
+            .delete_observations(vec![("Alice".to_string(), vec!["a".to_string()])], false)
+            .await?;
+        assert_consistent(&manager).await?;
+        manager
+            .delete_entities(vec!["Bob".to_string()], true, false)
+            .await?;
+        assert_consistent(&manager).await?;
+        manager
+            .delete_entities(vec!["Bob".to_string()], false, false)
+            .await?;
+        assert_consistent(&manager).await?;
+
+        let counts = manager.counts().await?;
+        assert_eq!(
+            (counts.entities, counts.relations, counts.observations),
+            (2, 0, 3)
+        );
+        assert_eq!(
+            counts.entity_types,
+            BTreeMap::from([("Organization".to_string(), 1), ("Person".to_string(), 1)])
+        );
+
+        manager.clear_graph(false).await?;
+        assert_eq!(manager.counts().await?, GraphCounts::default());
+
+        // Another process writes to the memory file
+        fs::write(
+            &temp_path,
+            r#"{"type":"entity","name":"Carol","entityType":"Person","observations":["g"]}"#,
+        )
+        .await?;
+        assert_eq!(manager.counts().await?.entities, 1);
+        assert_consistent(&manager).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_overview() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let dated = |name: &str, entity_type: &str, updated_at: Option<i64>| Entity {
+            created_at: updated_at,
+            updated_at,
+            ..entity(name, entity_type, &["fact"])
+        };
+        manager
+            .create_entities(
+                vec![
+                    dated("Alice", "Person", Some(300)),
+                    dated("Bob", "Person", Some(100)),
+                    dated("Acme", "Company", Some(200)),
+                    dated("Orphan", "Note", Some(400)),
+                ],
+                None,
+            )
+            .await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Alice", "Acme", "knows"),
+                relation("Bob", "Acme", "knows"),
+                relation("Alice", "Alice", "knows"),
+            ])
+            .await?;
+
+        let overview = manager.overview(2).await?;
+        assert_eq!(
+            (overview.entities, overview.relations, overview.observations),
+            (4, 4, 4)
+        );
+        let connected: Vec<(&str, usize)> = overview
+            .most_connected
+            .iter()
+            .map(|c| (c.name.as_str(), c.count))
+            .collect();
+        assert_eq!(connected, [("Alice", 4), ("Acme", 2)]);
+        assert_eq!(
+            overview.entity_types,
+            BTreeMap::from([
+                ("Company".to_string(), 1),
+                ("Note".to_string(), 1),
+                ("Person".to_string(), 2),
+            ])
+        );
+        let recent: Vec<&str> = overview
+            .recently_updated
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(recent, ["Orphan", "Alice"]);
+        assert_eq!(overview.orphans, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_term_frequency() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![
+                    Entity {
+                        name: "Alice".to_string(),
+                        entity_type: "Person".to_string(),
+                        observations: vec![
+                            "Drinks Coffee".into(),
+                            "Coffee, then more coffee".into(),
+                        ],
+                        ..Default::default()
+                    },
+                    Entity {
+                        name: "Bob".to_string(),
+                        entity_type: "Person".to_string(),
+                        observations: vec!["Sells coffee beans".into()],
+                        ..Default::default()
+                    },
+                    Entity {
+                        name: "Coffee".to_string(),
+                        entity_type: "Drink".to_string(),
+                        observations: vec!["Hot".into()],
+                        ..Default::default()
+                    },
+                ],
+                None,
+            )
+            .await?;
+
+        assert_eq!(manager.term_frequency("coffee".to_string()).await?, (2, 4));
+        assert_eq!(manager.term_frequency("tea".to_string()).await?, (0, 0));
+        assert!(manager.term_frequency(" ".to_string()).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_adjacency() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Carol".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec![],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Alice", "Bob", "mentors"),
+                relation("Alice", "Carol", "knows"),
+                relation("Bob", "Carol", "mentors"),
+            ])
+            .await?;
+
+        let all = manager.adjacency(None).await?;
+        assert_eq!(all["Alice"], vec!["Bob", "Carol"]);
+        assert_eq!(all["Bob"], vec!["Carol"]);
+        assert!(all["Carol"].is_empty());
+
+        let mentors = manager.adjacency(Some("mentors".to_string())).await?;
+        assert_eq!(mentors["Alice"], vec!["Bob"]);
+        assert_eq!(mentors["Bob"], vec!["Carol"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_describe_entity() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = ["Alice", "Bob", "Carol", "Acme"]
+            .into_iter()
+            .map(|name| Entity {
+                name: name.to_string(),
+                entity_type: "Person".to_string(),
+                observations: vec!["Lives in Berlin".into()],
+                ..Default::default()
+            })
+            .collect();
+        manager.create_entities(entities, None).await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Carol", "knows"),
+                relation("Alice", "Bob", "knows"),
+                relation("Alice", "Acme", "works_at"),
+                relation("Bob", "Alice", "knows"),
+                relation("Bob", "Carol", "knows"),
+            ])
+            .await?;
+
+        let description = manager.describe_entity("Alice".to_string()).await?;
+        assert_eq!(description.entity.name, "Alice");
+        assert_eq!(description.observation_count, 1);
+        assert_eq!(description.outgoing["knows"], vec!["Bob", "Carol"]);
+        assert_eq!(description.outgoing["works_at"], vec!["Acme"]);
+        assert_eq!(description.incoming.len(), 1);
+        assert_eq!(description.incoming["knows"], vec!["Bob"]);
+        assert_eq!(description.neighbors, vec!["Acme", "Bob", "Carol"]);
+
+        assert!(manager.describe_entity("Nobody".to_string()).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_degrees() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![
+                    entity("Alice", "Person", &[]),
+                    entity("Bob", "Person", &[]),
+                    entity("Carol", "Person", &[]),
+                ],
+                None,
+            )
+            .await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Carol", "Bob", "knows"),
+                relation("Bob", "Alice", "knows"),
+            ])
+            .await?;
+
+        let degrees = manager
+            .degrees(&["Bob".to_string(), "Nobody".to_string()])
+            .await?;
+        assert_eq!(degrees.len(), 1);
+        assert_eq!(
+            degrees["Bob"],
+            Degree {
+                incoming: 2,
+                outgoing: 1
+            }
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_consolidation_preview() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![
+                    entity("Acme", "Company", &["Makes anvils"]),
+                    entity(
+                        "Acme Corp",
+                        "Company",
+                        &["Makes anvils", "Based in Phoenix"],
+                    ),
+                    entity("ACME Inc", "Organization", &["Founded 1920"]),
+                    entity("Bob", "Person", &["Likes tea"]),
+                ],
+                None,
+            )
+            .await?;
+        manager
+            .create_relations(vec![
+                relation("Bob", "Acme", "works_at"),
+                relation("Bob", "Acme Corp", "works_at"),
+                relation("Acme", "ACME Inc", "works_at"),
+            ])
+            .await?;
+
+        let preview = manager.consolidation_preview("acme", None).await?;
+        assert_eq!(preview.canonical_name, "Acme Corp");
+        assert_eq!(preview.entity_type, "Company");
+        assert_eq!(preview.merged.len(), 3);
+        assert!(!preview.merged.contains(&"Bob".to_string()));
+        assert_eq!(preview.observations.len(), 3);
+        assert_eq!(
+            preview.relations,
+            vec![relation("Bob", "Acme Corp", "works_at")]
+        );
+
+        // Nothing was applied
+        assert_eq!(manager.read_graph().await?.entities.len(), 4);
+        assert!(manager.consolidation_preview("zebra", None).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_open_nodes_fuzzy() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![entity("Alice", "Person", &[]), entity("Bob", "Person", &[])],
+                None,
+            )
+            .await?;
+        let names = || ["Alise", "Bob", "Zed"].map(String::from).to_vec();
+
+        let exact = manager.open_nodes(names(), None).await?;
+        assert_eq!(exact.entities.len(), 1);
+        assert_eq!(exact.not_found, vec!["Alise", "Zed"]);
+        assert!(exact.fuzzy_matches.is_empty());
+
+        let fuzzy = manager.open_nodes(names(), Some(0.75)).await?;
+        let found: Vec<_> = fuzzy.entities.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(found, vec!["Alice", "Bob"]);
+        assert_eq!(fuzzy.not_found, vec!["Zed"]);
+        assert_eq!(
+            fuzzy.fuzzy_matches,
+            vec![FuzzyMatch {
+                requested: "Alise".to_string(),
+                matched: "Alice".to_string(),
+                similarity: 0.8,
+            }]
+        );
+
+        let strict = manager.open_nodes(names(), Some(0.9)).await?;
+        assert_eq!(strict.not_found, vec!["Alise", "Zed"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_similar_entities() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![
+                    entity("Alice", "Person", &["Plays chess", "Lives in Berlin"]),
+                    entity("Bob", "Person", &["Lives in Berlin", "Plays chess online"]),
+                    entity("Carol", "Person", &["Lives in Paris"]),
+                    entity("Dave", "Person", &["Collects stamps"]),
+                ],
+                None,
+            )
+            .await?;
+
+        let similar = manager.similar_entities("Alice".to_string(), None).await?;
+        let ranked: Vec<(&str, f32)> = similar
+            .iter()
+            .map(|s| (s.entity.name.as_str(), s.similarity))
+            .collect();
+        // Alice {berlin, chess, in, lives, plays} vs Bob adds {online}, Carol {in, lives, paris}
+        assert_eq!(ranked, [("Bob", 5.0 / 6.0), ("Carol", 2.0 / 6.0)]);
+        assert_eq!(similar[1].shared_terms, ["in", "lives"]);
+
+        let limited = manager
+            .similar_entities("Alice".to_string(), Some(1))
+            .await?;
+        assert_eq!(limited.len(), 1);
+        assert!(manager
+            .similar_entities("Nobody".to_string(), None)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_related_entities() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                ["Alice", "Bob", "Carol", "Dave", "Erin", "Frank"]
+                    .map(|n| entity(n, "Person", &[]))
+                    .to_vec(),
+                None,
+            )
+            .await?;
+        // Alice - Bob, Alice <- Carol, Carol - Dave, Carol - Erin, Dave - Frank
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Carol", "Alice", "knows"),
+                relation("Carol", "Dave", "knows"),
+                relation("Carol", "Erin", "knows"),
+                relation("Dave", "Frank", "knows"),
+            ])
+            .await?;
+
+        let related = manager
+            .related_entities("Alice".to_string(), 2, None)
+            .await?;
+        let ranked: Vec<_> = related
+            .iter()
+            .map(|r| (r.entity.name.as_str(), r.distance, r.degree))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                ("Carol", 1, 3),
+                ("Bob", 1, 1),
+                ("Dave", 2, 2),
+                ("Erin", 2, 1)
+            ]
+        );
+
+        let limited = manager
+            .related_entities("Alice".to_string(), 3, Some(1))
+            .await?;
+        assert_eq!(limited[0].entity.name, "Carol");
+        assert!(manager
+            .related_entities("Nobody".to_string(), 1, None)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_single_valued_relation_types() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                ["Alice", "Bob", "Carol"]
+                    .map(|n| entity(n, "Person", &[]))
+                    .to_vec(),
+                None,
+            )
+            .await?;
+        manager
+            .set_ontology(Ontology {
+                single_valued_relation_types: vec!["reports_to".to_string()],
+                ..Default::default()
+            })
+            .await?;
+
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "reports_to"),
+                relation("Alice", "Bob", "knows"),
+                relation("Alice", "Carol", "knows"),
+            ])
+            .await?;
+
+        // Repeating the held relation is not a conflict, a different target is
+        let repeated = manager
+            .create_relations(vec![relation("Alice", "Bob", "reports_to")])
+            .await?;
+        assert!(repeated.created.is_empty());
+        let error = manager
+            .create_relations(vec![relation("Alice", "Carol", "reports_to")])
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("'Alice' already has a 'reports_to' relation to 'Bob'"));
+        assert!(manager
+            .create_relations(vec![
+                relation("Bob", "Alice", "reports_to"),
+                relation("Bob", "Carol", "reports_to"),
+            ])
+            .await
+            .is_err());
+        assert_eq!(manager.read_graph().await?.relations.len(), 3);
+
+        manager
+            .set_ontology(Ontology {
+                single_valued_relation_types: vec!["reports_to".to_string()],
+                on_cardinality_conflict: CardinalityConflict::Replace,
+                ..Default::default()
+            })
+            .await?;
+        let report = manager
+            .create_relations(vec![relation("Alice", "Carol", "reports_to")])
+            .await?;
+        assert_eq!(
+            report.created,
+            vec![relation("Alice", "Carol", "reports_to")]
+        );
+        assert_eq!(
+            report.replaced,
+            vec![relation("Alice", "Bob", "reports_to")]
+        );
+
+        // Within one request the last relation wins
+        let report = manager
+            .create_relations(vec![
+                relation("Bob", "Alice", "reports_to"),
+                relation("Bob", "Carol", "reports_to"),
+            ])
+            .await?;
+        assert_eq!(report.created, vec![relation("Bob", "Carol", "reports_to")]);
+        assert!(report.replaced.is_empty());
+
+        let graph = manager.read_graph().await?;
+        let reports_to: Vec<_> = graph
+            .relations
+            .iter()
+            .filter(|r| r.relation_type == "reports_to")
+            .map(|r| (r.from.as_str(), r.to.as_str()))
+            .collect();
+        assert_eq!(reports_to, vec![("Alice", "Carol"), ("Bob", "Carol")]);
+        assert_eq!(graph.relations.len(), 4);
+
+        // connect_or_create follows the same policy
+        let report = manager
+            .connect_or_create(
+                entity("Carol", "Person", &[]),
+                entity("Dave", "Person", &[]),
+                "reports_to".to_string(),
+            )
+            .await?;
+        assert!(report.relation_created && report.replaced.is_empty());
+        let report = manager
+            .connect_or_create(
+                entity("Alice", "Person", &[]),
+                entity("Bob", "Person", &[]),
+                "reports_to".to_string(),
+            )
+            .await?;
+        assert_eq!(
+            report.replaced,
+            vec![relation("Alice", "Carol", "reports_to")]
+        );
+        manager
+            .set_ontology(Ontology {
+                single_valued_relation_types: vec!["reports_to".to_string()],
+                ..Default::default()
+            })
+            .await?;
+        let error = manager
+            .connect_or_create(
+                entity("Alice", "Person", &[]),
+                entity("Erin", "Person", &[]),
+                "reports_to".to_string(),
+            )
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("'Alice' already has a 'reports_to' relation to 'Bob'"));
+        let graph = manager.read_graph().await?;
+        assert!(!graph.entities.contains_key("Erin"));
+        assert_eq!(graph.relations.len(), 5);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_relation_observations() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+        manager
+            .create_entities(
+                ["Alice", "Bob", "Carol"]
+                    .map(|n| entity(n, "Person", &[]))
+                    .to_vec(),
+                None,
+            )
+            .await?;
+        manager
+            .create_relations(vec![
+                Relation {
+                    from: "Alice".to_string(),
+                    to: "Bob".to_string(),
+                    relation_type: "worked_with".to_string(),
+                    observations: vec!["At Acme".to_string()],
+                },
+                Relation {
+                    from: "Alice".to_string(),
+                    to: "Carol".to_string(),
+                    relation_type: "knows".to_string(),
+                    ..Default::default()
+                },
+            ])
+            .await?;
+
+        let result = manager
+            .add_relation_observations(Relation {
+                from: "Alice".to_string(),
+                to: "Bob".to_string(),
+                relation_type: "worked_with".to_string(),
+                observations: vec!["At Acme".to_string(), "2019-2021".to_string()],
+            })
+            .await?;
+        assert_eq!(result.added, vec!["2019-2021"]);
+        assert_eq!(result.already_present, vec!["At Acme"]);
+        assert_eq!(result.relation.observations, vec!["At Acme", "2019-2021"]);
+        assert!(manager
+            .add_relation_observations(Relation {
+                from: "Bob".to_string(),
+                to: "Carol".to_string(),
+                relation_type: "knows".to_string(),
+                observations: vec!["Neighbors".to_string()],
+            })
+            .await
+            .is_err());
+
+        // Observations are only written when present, and survive a reload
+        let data = std::fs::read_to_string(&temp_path)?;
+        assert!(
+            data.contains(r#""relationType":"worked_with","observations":["At Acme","2019-2021"]"#)
+        );
+        assert!(data.contains(r#""relationType":"knows"}"#));
+        let reopened = KnowledgeGraphManager::with_path(&temp_path);
+        let graph = reopened.read_graph().await?;
+        let worked_with = graph
+            .relations
+            .iter()
+            .find(|r| r.relation_type == "worked_with")
+            .unwrap();
+        assert_eq!(worked_with.observations.len(), 2);
+
+        // Relation observations only match when asked for
+        let options = SearchOptions::default();
+        let hits = reopened.search_nodes("2019", None, &options).await?;
+        assert!(hits.entities.values().all(|e| e.matched_fields.is_empty()));
+        let options = SearchOptions {
+            search_relation_observations: true,
+            ..Default::default()
+        };
+        let hits = reopened.search_nodes("2019", None, &options).await?;
+        let mut matched: Vec<_> = hits
+            .entities
+            .values()
+            .filter(|e| e.matched_fields == vec![SearchField::Relation])
+            .map(|e| e.name.as_str())
+            .collect();
+        matched.sort();
+        assert_eq!(matched, vec!["Alice", "Bob"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validate_entities() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+        manager
+            .create_entities(vec![entity("Alice", "person", &[])], None)
+            .await?;
+        manager
+            .set_ontology(Ontology {
+                entity_types: vec!["person".to_string()],
+                strict: true,
+                ..Default::default()
+            })
+            .await?;
+
+        let batch = vec![
+            entity("Bob", "person", &["Likes tea"]),
+            entity(" ", "person", &["  "]),
+            entity("Acme", "company", &[]),
+            entity("Alice", "person", &[]),
+            entity("Bob", "person", &[]),
+        ];
+        let results = manager.validate_entities(&batch, None).await?;
+        let valid: Vec<_> = results.iter().map(|r| r.valid).collect();
+        assert_eq!(valid, vec![true, false, false, true, true]);
+        assert_eq!(
+            results[1].errors,
+            vec!["name is empty", "1 empty observation(s)"]
+        );
+        assert_eq!(
+            results[2].errors,
+            vec!["entity type 'company' not in schema"]
+        );
+        assert_eq!(results[3].duplicate_of.as_deref(), Some("Alice"));
+        // Collisions within the batch count too
+        assert_eq!(results[4].duplicate_of.as_deref(), Some("Bob"));
+        assert!(results[4].warnings[0].ends_with("would be skipped"));
+
+        // Nothing was written
+        assert_eq!(manager.get_stats().await?, (1, 0));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_entities_in_range() -> Result<()> {
+        let manager = KnowledgeGraphManager::in_memory(
+            r#"{"type":"entity","name":"Early","entityType":"Note","observations":[],"createdAt":100,"updatedAt":400}
+{"type":"entity","name":"Middle","entityType":"Note","observations":[],"createdAt":200,"updatedAt":200}
+{"type":"entity","name":"Late","entityType":"Note","observations":[],"createdAt":300}
+{"type":"entity","name":"Untimed","entityType":"Note","observations":[]}"#,
+        )?;
+        let names = |entities: Vec<Entity>| -> Vec<String> {
+            entities.into_iter().map(|e| e.name).collect()
+        };
+
+        // Both ends are inclusive
+        let found = manager
+            .entities_in_range(Some(100), Some(200), TimeField::CreatedAt)
+            .await?;
+        assert_eq!(names(found), vec!["Early", "Middle"]);
+        let found = manager
+            .entities_in_range(Some(101), Some(299), TimeField::CreatedAt)
+            .await?;
+        assert_eq!(names(found), vec!["Middle"]);
+        let found = manager
+            .entities_in_range(Some(300), None, TimeField::CreatedAt)
+            .await?;
+        assert_eq!(names(found), vec!["Late"]);
+
+        // Open ranges still leave out entities without the timestamp
+        let found = manager
+            .entities_in_range(None, None, TimeField::UpdatedAt)
+            .await?;
+        assert_eq!(names(found), vec!["Middle", "Early"]);
+        let found = manager
+            .entities_in_range(None, Some(399), TimeField::UpdatedAt)
+            .await?;
+        assert_eq!(names(found), vec!["Middle"]);
+
+        assert!(manager
+            .entities_in_range(Some(2), Some(1), TimeField::CreatedAt)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_paths() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                ["Alice", "Bob", "Carol", "Dave", "Erin", "Frank"]
+                    .map(|n| entity(n, "Person", &[]))
+                    .to_vec(),
+                None,
+            )
+            .await?;
+        // Alice - Bob - Carol and Alice - Dave - Erin - Carol, with Erin -> Carol pointing back
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Bob", "Carol", "knows"),
+                relation("Alice", "Dave", "knows"),
+                relation("Dave", "Erin", "knows"),
+                relation("Erin", "Carol", "knows"),
+            ])
+            .await?;
+
+        let paths = manager
+            .find_paths("Alice".to_string(), "Carol".to_string(), 5, 4)
+            .await?;
+        let routes: Vec<_> = paths.iter().map(|p| p.entities.join(" > ")).collect();
+        assert_eq!(
+            routes,
+            vec!["Alice > Bob > Carol", "Alice > Dave > Erin > Carol"]
+        );
+        assert_eq!(paths[1].relations[2], relation("Erin", "Carol", "knows"));
+
+        let paths = manager
+            .find_paths("Alice".to_string(), "Carol".to_string(), 5, 2)
+            .await?;
+        assert_eq!(paths.len(), 1);
+        let paths = manager
+            .find_paths("Alice".to_string(), "Carol".to_string(), 1, 4)
+            .await?;
+        assert_eq!(paths[0].entities, vec!["Alice", "Bob", "Carol"]);
+
+        assert!(manager
+            .find_paths("Alice".to_string(), "Frank".to_string(), 5, 4)
+            .await?
+            .is_empty());
+        assert!(manager
+            .find_paths("Alice".to_string(), "Nobody".to_string(), 5, 4)
+            .await
+            .is_err());
+
+        // Whatever is asked, paths are no longer than MAX_PATH_LENGTH and no more than MAX_PATHS
+        let steps: Vec<String> = (0..=MAX_PATH_LENGTH + 1)
+            .map(|i| format!("Step {i}"))
+            .collect();
+        let middles: Vec<String> = (0..MAX_PATHS + 1).map(|i| format!("Middle {i}")).collect();
+        manager
+            .create_entities(
+                steps
+                    .iter()
+                    .chain(&middles)
+                    .map(|n| entity(n, "Person", &[]))
+                    .collect(),
+                None,
+            )
+            .await?;
+        manager
+            .create_relations(
+                steps
+                    .windows(2)
+                    .map(|pair| relation(&pair[0], &pair[1], "knows"))
+                    .chain(middles.iter().map(|m| relation("Frank", m, "knows")))
+                    .chain(middles.iter().map(|m| relation(m, "Alice", "knows")))
+                    .collect(),
+            )
+            .await?;
+        let last = steps[MAX_PATH_LENGTH + 1].clone();
+        assert!(manager
+            .find_paths(steps[0].clone(), last.clone(), 5, usize::MAX)
+            .await?
+            .is_empty());
+        assert_eq!(
+            manager
+                .find_paths(steps[1].clone(), last, 5, usize::MAX)
+                .await?
+                .len(),
+            1
+        );
+        let paths = manager
+            .find_paths("Frank".to_string(), "Alice".to_string(), usize::MAX, 2)
+            .await?;
+        assert_eq!(paths.len(), MAX_PATHS);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_ego_dot() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                ["Alice", "Bob", "Carol", "Dave"]
+                    .map(|n| entity(n, "Person", &[]))
+                    .to_vec(),
+                None,
+            )
+            .await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Bob", "Carol", "knows"),
+            ])
+            .await?;
+
+        let dot = manager.export_ego_dot("Alice".to_string(), 1).await?;
+        assert!(dot.contains("\"Alice\" [label=\"Alice\\n(Person)\", shape=doublecircle"));
+        assert!(dot.contains("\"Bob\" [label=\"Bob\\n(Person)\"];"));
+        assert!(dot.contains("\"Alice\" -> \"Bob\""));
+        assert!(!dot.contains("Carol"));
+
+        // An entity without relations is drawn alone
+        let dot = manager.export_ego_dot("Dave".to_string(), 2).await?;
+        assert_eq!(
+            dot,
+            "digraph memory {\n  \"Dave\" [label=\"Dave\\n(Person)\", shape=doublecircle, style=filled, fillcolor=lightblue];\n}\n"
+        );
+        assert!(manager
+            .export_ego_dot("Nobody".to_string(), 1)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_relation_exists() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![entity("Alice", "Person", &[]), entity("Bob", "Person", &[])],
+                None,
+            )
+            .await?;
+        manager
+            .create_relations(vec![Relation {
+                from: "Alice".to_string(),
+                to: "Bob".to_string(),
+                relation_type: "knows".to_string(),
+                ..Default::default()
+            }])
+            .await?;
+
+        let exists = |from: &str, to: &str, relation_type: &str| {
+            manager.relation_exists(from.to_string(), to.to_string(), relation_type.to_string())
+        };
+        assert!(exists("Alice", "Bob", "knows").await?);
+        assert!(exists(" Alice ", "Bob", "knows").await?);
+        assert!(!exists("Bob", "Alice", "knows").await?);
+        assert!(!exists("Alice", "Bob", "likes").await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_policies() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        // Case is ignored by default, under the default `Trim` name policy
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(vec![entity("Alice", "Person", &["Likes tea"])], None)
+            .await?;
+
+        let report = manager
+            .create_entities(
+                vec![
+                    entity("alice", "Person", &["Plays chess"]),
+                    entity("Bob", "Person", &[]),
+                ],
+                Some(DuplicatePolicy::Skip),
+            )
+            .await?;
+        assert_eq!(report.created.len(), 1);
+        assert_eq!(
+            report.collisions,
+            vec![NameCollision {
+                requested: "alice".to_string(),
+                existing: "Alice".to_string(),
+                policy: DuplicatePolicy::Skip,
+                observations_added: 0,
+            }]
+        );
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.entities.len(), 2);
+        assert_eq!(graph.entities["Alice"].observations, vec!["Likes tea"]);
+
+        let report = manager
+            .create_entities(
+                vec![
+                    entity("ALICE", "Person", &["Plays chess", "Likes tea"]),
+                    entity("Carol", "Person", &["Sings"]),
+                    entity("carol", "Person", &["Dances"]),
+                ],
+                Some(DuplicatePolicy::Merge),
+            )
+            .await?;
+        assert_eq!(report.collisions.len(), 2);
+        assert_eq!(report.collisions[0].observations_added, 1);
+        assert_eq!(report.created.len(), 1);
+        assert_eq!(report.created[0].observations, vec!["Sings", "Dances"]);
+
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.entities.len(), 3);
+        assert_eq!(
+            graph.entities["Alice"].observations,
+            vec!["Likes tea", "Plays chess"]
+        );
+
+        // The configured policy applies when none is given
+        let manager = manager.with_duplicate_policy(DuplicatePolicy::Merge);
+        manager
+            .create_entities(vec![entity("bob", "Person", &["Runs"])], None)
+            .await?;
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.entities["Bob"].observations, vec!["Runs"]);
+
+        // Turned off, names differing in case are distinct entities
+        let manager = manager.with_case_insensitive_duplicates(false);
+        let report = manager
+            .create_entities(vec![entity("BOB", "Person", &[])], None)
+            .await?;
+        assert!(report.collisions.is_empty());
+        assert_eq!(manager.read_graph().await?.entities.len(), 4);
+
+        // A strict ontology only checks the types of entities that get created
+        manager
+            .set_ontology(Ontology {
+                entity_types: vec!["Person".to_string()],
+                strict: true,
+                ..Default::default()
+            })
+            .await?;
+        let report = manager
+            .create_entities(vec![entity("Alice", "Robot", &["Beeps"])], None)
+            .await?;
+        assert_eq!(report.collisions[0].observations_added, 1);
+        assert_eq!(
+            manager.read_graph().await?.entities["Alice"].entity_type,
+            "Person"
+        );
+        assert!(manager
+            .create_entities(
+                vec![entity("Alice", "Robot", &[]), entity("R2", "Robot", &[])],
+                Some(DuplicatePolicy::Skip),
+            )
+            .await
+            .is_err());
+        assert_eq!(manager.read_graph().await?.entities.len(), 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_relation_count_between() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Alice", "Bob", "mentors"),
+                relation("Bob", "Alice", "knows"),
+                relation("Alice", "Carol", "knows"),
+            ])
+            .await?;
+
+        let count = |from: &str, to: &str, directed: bool| {
+            manager.relation_count_between(from.to_string(), to.to_string(), directed)
+        };
+        assert_eq!(count("Alice", "Bob", true).await?, 2);
+        assert_eq!(count("Bob", "Alice", true).await?, 1);
+        assert_eq!(count("Bob", "Alice", false).await?, 3);
+        assert_eq!(count("Bob", "Carol", false).await?, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_open_nodes_order_and_missing() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = ["Alice", "Bob", "Carol", "Dave"]
+            .into_iter()
+            .map(|name| Entity {
+                name: name.to_string(),
+                entity_type: "Person".to_string(),
+                observations: vec![],
+                ..Default::default()
+            })
+            .collect();
+        manager.create_entities(entities, None).await?;
+        manager
+            .create_relations(vec![Relation {
+                from: "Carol".to_string(),
+                to: "Alice".to_string(),
+                relation_type: "knows".to_string(),
+                ..Default::default()
+            }])
+            .await?;
+
+        let opened = manager
+            .open_nodes(
+                ["Carol", "Nobody", "Alice", "Carol", "Dave"]
+                    .map(String::from)
+                    .to_vec(),
+                None,
+            )
+            .await?;
+        let names: Vec<_> = opened.entities.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Carol", "Alice", "Dave"]);
+        assert_eq!(opened.not_found, vec!["Nobody"]);
+        assert_eq!(opened.relations.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = [
+            ("Alice", "Person"),
+            ("Bob", "Person"),
+            ("Carol", "Person"),
+            ("Apollo", "Project"),
+            ("Zeus", "Project"),
+        ]
+        .into_iter()
+        .map(|(name, entity_type)| Entity {
+            name: name.to_string(),
+            entity_type: entity_type.to_string(),
+            observations: vec![],
+            ..Default::default()
+        })
+        .collect();
+        manager.create_entities(entities, None).await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Apollo", "works_on"),
+                relation("Bob", "Zeus", "works_on"),
+                relation("Carol", "Apollo", "works_on"),
+            ])
+            .await?;
+
+        let graph = manager
+            .fetch(
+                vec!["Alice".to_string(), "Nobody".to_string()],
+                vec!["Project".to_string()],
+            )
+            .await?;
+        let mut names: Vec<_> = graph.entities.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["Alice", "Apollo", "Zeus"]);
+        // Only relations with both ends in the slice
+        assert_eq!(
+            graph.relations,
+            vec![relation("Alice", "Apollo", "works_on")]
+        );
+
+        let graph = manager.fetch(vec![], vec![]).await?;
+        assert!(graph.entities.is_empty());
+        assert!(graph.relations.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_relations_subgraph() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = ["Alice", "Bob", "Carol"]
+            .into_iter()
+            .map(|name| Entity {
+                name: name.to_string(),
+                entity_type: "Person".to_string(),
+                observations: vec![],
+                ..Default::default()
+            })
+            .collect();
+        manager.create_entities(entities, None).await?;
+        manager
+            .create_relations(vec![
+                relation("Bob", "Alice", "mentors"),
+                relation("Bob", "Carol", "knows"),
+            ])
+            .await?;
+
+        let created = manager
+            .create_relations(vec![relation("Alice", "Bob", "knows")])
+            .await?
+            .created;
+        let subgraph = manager.relations_subgraph(&created).await?;
+
+        let mut names: Vec<_> = subgraph.entities.keys().cloned().collect();
+        names.sort();
+        assert_eq!(names, vec!["Alice", "Bob"]);
+        assert_eq!(subgraph.relations.len(), 2);
+        assert!(subgraph.relations.iter().all(|r| r.to != "Carol"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_nodes() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = vec![
+            Entity {
+                name: "Alice".to_string(),
+                entity_type: "Person".to_string(),
+                observations: vec!["Likes coffee".into()],
+                ..Default::default()
+            },
+            Entity {
+                name: "Bob".to_string(),
+                entity_type: "Person".to_string(),
+                observations: vec!["Likes tea".into()],
+                ..Default::default()
+            },
+        ];
+
+        manager.create_entities(entities, None).await?;
+
+        let search_result = manager
+            .search_nodes("coffee", None, &SearchOptions::default())
+            .await?;
+        // The enhanced search may return more entities based on relevance scores
+        assert!(
+            !search_result.entities.is_empty(),
+            "Should find at least Alice"
+        );
+        assert!(
+            search_result.entities.contains_key("Alice"),
+            "Should find Alice who likes coffee"
+        );
+        let alice = search_result.entities.get("Alice").unwrap();
+        assert_eq!(alice.name, "Alice");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_entity_not_found_error() {
+        let temp_dir = tempdir().unwrap();
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let result = manager
+            .add_observations(
+                vec![("NonExistent".to_string(), vec!["Some observation".into()])],
+                ObservationOrder::Append,
+                false,
+            )
+            .await;
+
+        assert!(result.is_err());
+        let error_message = result.unwrap_err().to_string();
+        assert!(error_message.contains("NonExistent"));
+        assert!(error_message.contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_stats_and_entity_exists() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let (initial_entities, initial_relations) = manager.get_stats().await?;
+        assert_eq!(initial_entities, 0);
+        assert_eq!(initial_relations, 0);
+
+        let entities = vec![
+            Entity {
+                name: "TestEntity1".to_string(),
+                entity_type: "Test".to_string(),
+                observations: vec![],
+                ..Default::default()
+            },
+            Entity {
+                name: "TestEntity2".to_string(),
+                entity_type: "Test".to_string(),
+                observations: vec![],
+                ..Default::default()
+            },
+        ];
+
+        manager.create_entities(entities, None).await?;
+
+        let (updated_entities, updated_relations) = manager.get_stats().await?;
+        assert_eq!(updated_entities, 2);
+        assert_eq!(updated_relations, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_observations_pagination() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = vec![Entity {
+            name: "Verbose".to_string(),
+            entity_type: "Test".to_string(),
+            observations: (0..5).map(|i| format!("Observation {i}").into()).collect(),
+            ..Default::default()
+        }];
+        manager.create_entities(entities, None).await?;
+
+        let (page, total) = manager
+            .get_observations("Verbose".to_string(), Some(1), Some(2))
+            .await?;
+        assert_eq!(total, 5);
+        assert_eq!(page, vec!["Observation 1", "Observation 2"]);
+
+        let (page, total) = manager
+            .get_observations("Verbose".to_string(), Some(4), None)
+            .await?;
+        assert_eq!(total, 5);
+        assert_eq!(page, vec!["Observation 4"]);
+
+        let (page, _) = manager
+            .get_observations("Verbose".to_string(), Some(10), Some(2))
+            .await?;
+        assert!(page.is_empty());
+
+        let missing = manager
+            .get_observations("Missing".to_string(), None, None)
+            .await;
+        assert!(missing.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_manager() -> Result<()> {
+        let seed = concat!(
+            r#"{"type":"entity","name":"Alice","entityType":"Person","observations":["Likes coffee"]}"#,
+            "\n",
+            r#"{"type":"entity","name":"Bob","entityType":"Person","observations":[]}"#,
+            "\n",
+            r#"{"type":"relation","from":"Alice","to":"Bob","relationType":"knows"}"#,
+            "\n",
+        );
+        let manager = KnowledgeGraphManager::in_memory(seed)?;
+
+        let (entities, relations) = manager.get_stats().await?;
+        assert_eq!((entities, relations), (2, 1));
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Carol".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec![],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+        assert_eq!(manager.get_stats().await?, (3, 1));
+
+        // A serialized KnowledgeGraph is accepted as well
+        let json = serde_json::to_string(&*manager.read_graph().await?)?;
+        let reseeded = KnowledgeGraphManager::in_memory(&json)?;
+        assert_eq!(reseeded.get_stats().await?, (3, 1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_into_in_memory_does_not_write() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec![],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+        let before = std::fs::read_to_string(&temp_path)?;
+
+        let in_memory = manager.into_in_memory().await?;
+        in_memory
+            .delete_entities(vec!["Alice".to_string()], false, false)
+            .await?;
+        assert_eq!(in_memory.get_stats().await?, (0, 0));
+
+        assert_eq!(std::fs::read_to_string(&temp_path)?, before);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validate_reports_defects() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        assert!(manager.validate().await?.valid);
+
+        let data = [
+            r#"{"type":"entity","name":"Alice","entityType":"Person","observations":["Likes coffee",""]}"#,
+            r#"{"type":"entity","name":"Alice","entityType":"Person","observations":[]}"#,
+            r#"{"type":"entity","name":" ","entityType":"Person","observations":[]}"#,
+            r#"{"type":"relation","from":"Alice","to":"Ghost","relationType":"knows"}"#,
+            r#"{"type":"relation","from":"Alice","to":"Alice","relationType":"is"}"#,
+            r#"{"type":"relation","from":"Alice","to":"Alice","relationType":"is"}"#,
+        ]
+        .join("\n");
+        std::fs::write(&temp_path, data)?;
+
+        let report = manager.validate().await?;
+        assert!(!report.valid);
+        assert_eq!(report.duplicate_entity_names, vec!["Alice"]);
+        assert_eq!(report.empty_entity_names, 1);
+        assert_eq!(report.entities_with_empty_observations, vec!["Alice"]);
+        assert_eq!(report.dangling_relations.len(), 1);
+        assert_eq!(report.dangling_relations[0].to, "Ghost");
+        assert_eq!(report.duplicate_relations.len(), 1);
+        assert_eq!(report.duplicate_relations[0].relation_type, "is");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_memory_path() {
+        let cwd = Path::new("/work");
+        let exe_dir = Path::new("/opt/bin");
+        let absolute = std::env::temp_dir().join("memory.jsonl");
+
+        assert_eq!(
+            resolve_memory_path(absolute.to_str(), PathBase::Cwd, cwd, exe_dir),
+            absolute
+        );
+        assert_eq!(
+            resolve_memory_path(absolute.to_str(), PathBase::Exe, cwd, exe_dir),
+            absolute
+        );
+        assert_eq!(
+            resolve_memory_path(Some("data/memory.jsonl"), PathBase::Cwd, cwd, exe_dir),
+            cwd.join("data/memory.jsonl")
+        );
+        assert_eq!(
+            resolve_memory_path(Some("data/memory.jsonl"), PathBase::Exe, cwd, exe_dir),
+            exe_dir.join("data/memory.jsonl")
+        );
+        assert_eq!(
+            resolve_memory_path(None, PathBase::Cwd, cwd, exe_dir),
+            exe_dir.join("memory.jsonl")
+        );
+
+        assert_eq!(PathBase::from_env_value(None), PathBase::Cwd);
+        assert_eq!(PathBase::from_env_value(Some("cwd")), PathBase::Cwd);
+        assert_eq!(PathBase::from_env_value(Some("EXE")), PathBase::Exe);
+    }
+
+    #[tokio::test]
+    async fn test_delete_counts() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![
+                    Entity {
+                        name: "Alice".to_string(),
+                        entity_type: "Person".to_string(),
+                        observations: vec!["Likes coffee".into(), "Likes tea".into()],
+                        ..Default::default()
+                    },
+                    Entity {
+                        name: "Bob".to_string(),
+                        entity_type: "Person".to_string(),
+                        observations: vec![],
+                        ..Default::default()
+                    },
+                ],
+                None,
+            )
+            .await?;
+        let knows = Relation {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            relation_type: "knows".to_string(),
+            ..Default::default()
+        };
+        let likes = Relation {
+            relation_type: "likes".to_string(),
+            ..knows.clone()
+        };
+        manager.create_relations(vec![knows.clone(), likes]).await?;
+
+        let removed = manager
+            .delete_observations(
+                vec![(
+                    "Alice".to_string(),
+                    vec!["Likes tea".to_string(), "Unknown".to_string()],
+                )],
+                false,
+            )
+            .await?;
+        assert_eq!(
+            removed.observations,
+            vec![("Alice".to_string(), vec!["Likes tea".to_string()])]
+        );
+
+        let removed = manager.delete_relations(vec![knows.clone()], false).await?;
+        assert_eq!(removed.relations.len(), 1);
+        let removed = manager.delete_relations(vec![knows], false).await?;
+        assert_eq!(removed.relations.len(), 0);
+
+        let removed = manager
+            .delete_entities(vec!["Bob".to_string(), "Nobody".to_string()], false, false)
+            .await?;
+        assert_eq!((removed.entities.len(), removed.relations.len()), (1, 1));
+        assert_eq!(removed.entities, vec!["Bob"]);
+        let removed = manager
+            .delete_entities(vec!["Bob".to_string()], false, false)
+            .await?;
+        assert_eq!(removed, DeletionReport::default());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_entities_keep_relations() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![
+                    entity("Alice", "Person", &[]),
+                    entity("Bob", "Person", &[]),
+                    entity("Carol", "Person", &[]),
+                ],
+                None,
+            )
+            .await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Carol", "Alice", "knows"),
+            ])
+            .await?;
+
+        // Cascading removes the relations with the entity
+        let removed = manager
+            .delete_entities(vec!["Bob".to_string()], false, false)
+            .await?;
+        assert_eq!(removed.relations, vec![relation("Alice", "Bob", "knows")]);
+        assert!(removed.dangling_relations.is_empty());
+
+        // Keeping them leaves them dangling until repointed
+        let removed = manager
+            .delete_entities(vec!["Alice".to_string()], false, true)
+            .await?;
+        assert_eq!(removed.entities, vec!["Alice"]);
+        assert!(removed.relations.is_empty());
+        assert_eq!(
+            removed.dangling_relations,
+            vec![relation("Carol", "Alice", "knows")]
+        );
+
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.relations, vec![relation("Carol", "Alice", "knows")]);
+        assert_eq!(
+            manager.validate().await?.dangling_relations,
+            vec![relation("Carol", "Alice", "knows")]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prune_dangling_relations() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        fs::write(
+            &temp_path,
+            concat!(
+                r#"{"type":"entity","name":"Alice","entityType":"Person","observations":[]}"#,
+                "\n",
+                r#"{"type":"entity","name":"Bob","entityType":"Person","observations":[]}"#,
+                "\n",
+                r#"{"type":"relation","from":"Alice","to":"Bob","relationType":"knows"}"#,
+                "\n",
+                r#"{"type":"relation","from":"Alice","to":"Ghost","relationType":"knows"}"#,
+                "\n",
+                r#"{"type":"relation","from":"Casper","to":"Bob","relationType":"haunts"}"#,
+                "\n",
+            ),
+        )
+        .await?;
+
+        // Pruning on load hides them from reads without touching the file
+        let pruning =
+            KnowledgeGraphManager::with_path(&temp_path).with_prune_dangling_on_load(true);
+        assert_eq!(pruning.read_graph().await?.relations.len(), 1);
+
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+        assert_eq!(manager.read_graph().await?.relations.len(), 3);
+        assert_eq!(manager.prune_dangling_relations().await?, 2);
+        assert_eq!(manager.prune_dangling_relations().await?, 0);
+
+        let graph = KnowledgeGraphManager::with_path(&temp_path)
+            .read_graph()
+            .await?;
+        assert_eq!(graph.relations.len(), 1);
+        assert_eq!(graph.relations[0].to, "Bob");
+        assert!(manager.validate().await?.dangling_relations.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clear_observations() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec!["Likes coffee".into(), "Likes tea".into()],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        assert_eq!(manager.clear_observations("Alice".to_string()).await?, 2);
+        assert_eq!(manager.clear_observations("Alice".to_string()).await?, 0);
+        let graph = manager.read_graph().await?;
+        assert!(graph.entities["Alice"].observations.is_empty());
+
+        assert!(manager
+            .clear_observations("Nobody".to_string())
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_entity_timestamps() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![
+                    Entity {
+                        name: "Fresh".to_string(),
+                        entity_type: "Test".to_string(),
+                        observations: vec![],
+                        ..Default::default()
+                    },
+                    Entity {
+                        name: "Imported".to_string(),
+                        entity_type: "Test".to_string(),
+                        observations: vec![],
+                        created_at: Some(1),
+                        updated_at: Some(1),
+                        ..Default::default()
+                    },
+                ],
+                None,
+            )
+            .await?;
+
+        let graph = manager.read_graph().await?;
+        let fresh = &graph.entities["Fresh"];
+        assert!(fresh.created_at.is_some());
+        assert_eq!(fresh.created_at, fresh.updated_at);
+        assert_eq!(graph.entities["Imported"].created_at, Some(1));
+
+        manager
+            .add_observations(
+                vec![("Imported".to_string(), vec!["New fact".into()])],
+                ObservationOrder::Append,
+                false,
+            )
+            .await?;
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.entities["Imported"].created_at, Some(1));
+        assert!(graph.entities["Imported"].updated_at > Some(1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_observations_order() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec!["First".into()],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        manager
+            .add_observations(
+                vec![("Alice".to_string(), vec!["Appended".into()])],
+                ObservationOrder::Append,
+                false,
+            )
+            .await?;
+        manager
+            .add_observations(
+                vec![("Alice".to_string(), vec!["Newest".into(), "Newer".into()])],
+                ObservationOrder::Prepend,
+                false,
+            )
+            .await?;
+
+        let graph = manager.read_graph().await?;
+        assert_eq!(
+            graph.entities["Alice"].observations,
+            vec!["Newest", "Newer", "First", "Appended"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_observations_already_present() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec!["Likes coffee".into()],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        let results = manager
+            .add_observations(
+                vec![(
+                    "Alice".to_string(),
+                    vec![
+                        "Likes coffee".into(),
+                        "Likes tea".into(),
+                        "Likes tea".into(),
+                    ],
+                )],
+                ObservationOrder::Append,
+                false,
+            )
+            .await?;
+        assert_eq!(results[0].added, vec!["Likes tea"]);
+        // Both the stored observation and the repeat within the request are reported
+        assert_eq!(
+            results[0].already_present,
+            vec!["Likes coffee", "Likes tea"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_observations_dedup_substrings() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec![
+                        "Alice likes coffee and tea".into(),
+                        "Lives in Paris".into(),
+                    ],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        let results = manager
+            .add_observations(
+                vec![(
+                    "Alice".to_string(),
+                    vec![
+                        "alice likes coffee".into(),
+                        "Lives in Paris, France".into(),
+                        "Plays chess".into(),
+                    ],
+                )],
+                ObservationOrder::Append,
+                true,
+            )
+            .await?;
+        assert_eq!(
+            results[0].added,
+            vec!["Lives in Paris, France", "Plays chess"]
+        );
+        assert_eq!(results[0].dropped, vec!["alice likes coffee"]);
+        assert_eq!(results[0].replaced, vec!["Lives in Paris"]);
+
+        let graph = manager.read_graph().await?;
+        assert_eq!(
+            graph.entities["Alice"].observations,
+            vec![
+                "Alice likes coffee and tea",
+                "Lives in Paris, France",
+                "Plays chess"
+            ]
+        );
+
+        // Without the flag only exact duplicates are skipped
+        let results = manager
+            .add_observations(
+                vec![(
+                    "Alice".to_string(),
+                    vec!["Plays chess".into(), "Plays".into()],
+                )],
+                ObservationOrder::Append,
+                false,
+            )
+            .await?;
+        assert_eq!(results[0].added, vec!["Plays"]);
+        assert!(results[0].dropped.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sort_observations() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec!["Bravo".into(), "Charlie".into(), "Alpha".into()],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        let sorted = manager
+            .sort_observations("Alice".to_string(), ObservationSort::Alphabetical)
+            .await?;
+        assert_eq!(sorted.observations, vec!["Alpha", "Bravo", "Charlie"]);
+
+        manager
+            .sort_observations("Alice".to_string(), ObservationSort::Reverse)
+            .await?;
+        let graph = manager.read_graph().await?;
+        assert_eq!(
+            graph.entities["Alice"].observations,
+            vec!["Charlie", "Bravo", "Alpha"]
+        );
+
+        assert!(manager
+            .sort_observations("Nobody".to_string(), ObservationSort::Reverse)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path).with_audit_log_size(3);
+
+        let entities = ["Alice", "Bob"]
+            .into_iter()
+            .map(|name| Entity {
+                name: name.to_string(),
+                entity_type: "Person".to_string(),
+                observations: vec![],
+                ..Default::default()
+            })
+            .collect();
+        manager.create_entities(entities, None).await?;
+        let knows = Relation {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            relation_type: "knows".to_string(),
+            ..Default::default()
+        };
+        manager.create_relations(vec![knows.clone()]).await?;
+        // Changes nothing, so nothing is recorded
+        manager.create_relations(vec![knows.clone()]).await?;
+        // Dry runs are not recorded either
+        manager
+            .delete_entities(vec!["Bob".to_string()], true, false)
+            .await?;
+
+        let log = manager.get_audit_log(None).await?;
+        let operations: Vec<_> = log.iter().map(|e| e.operation).collect();
+        assert_eq!(operations, vec!["create_relations", "create_entities"]);
+        assert_eq!(log[0].relations, vec![knows.clone()]);
+        assert_eq!(log[1].entities, vec!["Alice", "Bob"]);
+
+        manager.touch_entity("Alice".to_string()).await?;
+        manager
+            .delete_entities(vec!["Bob".to_string()], false, false)
+            .await?;
+        let log = manager.get_audit_log(None).await?;
+        // The oldest entry is dropped once the log is full
+        let operations: Vec<_> = log.iter().map(|e| e.operation).collect();
+        assert_eq!(
+            operations,
+            vec!["delete_entities", "touch_entity", "create_relations"]
+        );
+        assert_eq!(log[0].entities, vec!["Bob"]);
+        assert_eq!(log[0].relations, vec![knows]);
+
+        assert_eq!(manager.get_audit_log(Some(1)).await?.len(), 1);
+
+        let disabled = KnowledgeGraphManager::in_memory("")?.with_audit_log_size(0);
+        disabled
+            .create_entities(
+                vec![Entity {
+                    name: "Carol".to_string(),
+                    entity_type: "Person".to_string(),
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+        assert!(disabled.get_audit_log(None).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_touch_entity() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Stale".to_string(),
+                    entity_type: "Test".to_string(),
+                    observations: vec!["Kept as is".into()],
+                    created_at: Some(1),
+                    updated_at: Some(1),
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        let touched = manager.touch_entity("Stale".to_string()).await?;
+        assert!(touched.updated_at > Some(1));
+
+        let graph = manager.read_graph().await?;
+        let stored = &graph.entities["Stale"];
+        assert_eq!(stored.updated_at, touched.updated_at);
+        assert_eq!(stored.created_at, Some(1));
+        assert_eq!(stored.observations, vec!["Kept as is"]);
+
+        assert!(manager.touch_entity("Missing".to_string()).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upsert_entities() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "person".to_string(),
+                    observations: vec!["Likes coffee".into()],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        let results = manager
+            .upsert_entities(
+                vec![
+                    Entity {
+                        name: "Alice".to_string(),
+                        entity_type: "Person".to_string(),
+                        observations: vec!["Likes coffee".into(), "Lives in Paris".into()],
+                        ..Default::default()
+                    },
+                    Entity {
+                        name: "Bob".to_string(),
+                        entity_type: "Person".to_string(),
+                        observations: vec![],
+                        ..Default::default()
+                    },
+                ],
+                false,
+            )
+            .await?;
+        assert_eq!(
+            results,
+            vec![
+                ("Alice".to_string(), UpsertStatus::Updated),
+                ("Bob".to_string(), UpsertStatus::Created)
+            ]
+        );
+
+        let graph = manager.read_graph().await?;
+        let alice = &graph.entities["Alice"];
+        assert_eq!(alice.observations, vec!["Likes coffee", "Lives in Paris"]);
+        assert_eq!(alice.entity_type, "person");
+
+        let results = manager
+            .upsert_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec!["Likes coffee".into()],
+                    ..Default::default()
+                }],
+                true,
+            )
+            .await?;
+        assert_eq!(results[0].1, UpsertStatus::Updated);
+        assert_eq!(
+            manager.read_graph().await?.entities["Alice"].entity_type,
+            "Person"
+        );
+
+        let results = manager
+            .upsert_entities(
+                vec![Entity {
+                    name: "Bob".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec![],
+                    ..Default::default()
+                }],
+                true,
+            )
+            .await?;
+        assert_eq!(results[0].1, UpsertStatus::Unchanged);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_io_retry() -> Result<()> {
+        let retry = IoRetry {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+        };
+        let failing = |kind: std::io::ErrorKind, failures: u32| {
+            let attempts = std::sync::atomic::AtomicU32::new(0);
+            move || {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    if attempt <= failures {
+                        Err(std::io::Error::from(kind)).context("Failed to read graph")
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            }
+        };
+
+        // A transient failure is retried until it goes away
+        let attempts = retry
+            .run(failing(std::io::ErrorKind::Interrupted, 2))
+            .await?;
+        assert_eq!(attempts, 3);
+
+        // ...but only up to the attempt limit
+        let error = retry
+            .run(failing(std::io::ErrorKind::PermissionDenied, 3))
+            .await
+            .unwrap_err();
+        assert!(is_transient_io_error(&error));
+
+        // Other failures are reported on the first attempt
+        let mut calls = 0;
+        let error = retry
+            .run(|| {
+                calls += 1;
+                async { Err::<(), _>(std::io::Error::from(std::io::ErrorKind::NotFound).into()) }
+            })
+            .await
+            .unwrap_err();
+        assert!(!is_transient_io_error(&error));
+        assert_eq!(calls, 1);
+
+        // Saving into a missing directory is not retried
+        let temp_dir = tempdir()?;
+        let manager =
+            KnowledgeGraphManager::with_path(temp_dir.path().join("missing/memory.jsonl"));
+        let error = manager
+            .create_entities(vec![Entity::default()], None)
+            .await
+            .unwrap_err();
+        assert!(!is_transient_io_error(&error));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scoped_reads() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let snapshots = temp_dir.path().join("snapshots");
+        std::fs::create_dir(&snapshots)?;
+        let manager = KnowledgeGraphManager::with_path(temp_dir.path().join("memory.jsonl"))
+            .with_snapshot_dir(Some(snapshots.clone()));
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+        std::fs::write(
+            snapshots.join("old.jsonl"),
+            r#"{"type":"entity","name":"Bob","entityType":"Person","observations":["Lives in Berlin"]}"#,
+        )?;
+        std::fs::write(temp_dir.path().join("secret.jsonl"), "")?;
+
+        let scoped = manager.scoped("old.jsonl").await?;
+        let graph = scoped.read_graph().await?;
+        assert!(graph.entities.contains_key("Bob"));
+        assert!(!graph.entities.contains_key("Alice"));
+        let hits = scoped
+            .search_nodes("berlin", None, &SearchOptions::default())
+            .await?;
+        assert!(hits.entities.contains_key("Bob"));
+
+        // The primary graph is unaffected
+        assert_eq!(manager.get_stats().await?, (1, 0));
+
+        // Files outside the snapshot directory are rejected, however they are spelled
+        let outside = temp_dir.path().join("secret.jsonl");
+        for file in [
+            "../secret.jsonl",
+            outside.to_str().unwrap(),
+            "missing.jsonl",
+        ] {
+            assert!(manager.scoped(file).await.is_err(), "{file}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_relations_matching() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = [
+            ("Alice", "Works as an engineer"),
+            ("Bob", "Works as an engineer"),
+            ("Carol", "Leads the platform team"),
+            ("Dave", "Leads the data team"),
+        ]
+        .into_iter()
+        .map(|(name, observation)| Entity {
+            name: name.to_string(),
+            entity_type: "Person".to_string(),
+            observations: vec![observation.into()],
+            ..Default::default()
+        })
+        .collect();
+        manager.create_entities(entities, None).await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Carol", "reports_to"),
+                relation("Bob", "Dave", "reports_to"),
+                relation("Alice", "Bob", "knows"),
+                relation("Carol", "Dave", "reports_to"),
+            ])
+            .await?;
+
+        let graph = manager
+            .relations_matching("engineer".to_string(), Some("reports_to".to_string()))
+            .await?;
+        let mut relations = graph.relations.clone();
+        relations.sort_by(|a, b| a.from.cmp(&b.from));
+        assert_eq!(
+            relations,
+            vec![
+                relation("Alice", "Carol", "reports_to"),
+                relation("Bob", "Dave", "reports_to"),
+            ]
+        );
+        let mut names: Vec<_> = graph.entities.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["Alice", "Bob", "Carol", "Dave"]);
+        assert!(!graph.entities["Alice"].matched_fields.is_empty());
+        assert!(graph.entities["Carol"].matched_fields.is_empty());
+
+        // Without a type every relation touching a match is returned
+        let graph = manager
+            .relations_matching("engineer".to_string(), None)
+            .await?;
+        assert_eq!(graph.relations.len(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_subgraph() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = [
+            ("Alice", "Person", "Lives in Berlin"),
+            ("Bob", "Person", "Lives in Paris"),
+            ("Carol", "Person", "Lives in Rome"),
+            ("Apollo", "Project", "Launched in 2020"),
+        ]
+        .into_iter()
+        .map(|(name, entity_type, observation)| Entity {
+            name: name.to_string(),
+            entity_type: entity_type.to_string(),
+            observations: vec![observation.into()],
+            ..Default::default()
+        })
+        .collect();
+        manager.create_entities(entities, None).await?;
+        manager
+            .create_relations(vec![
+                relation("Alice", "Apollo", "works_on"),
+                relation("Bob", "Apollo", "works_on"),
+            ])
+            .await?;
+
+        let filter = ExportFilter {
+            types: vec!["Project".to_string()],
+            names: vec!["Carol".to_string()],
+            query: Some("berlin".to_string()),
+        };
+        let graph = manager.export_subgraph(&filter).await?;
+        let mut names: Vec<_> = graph.entities.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["Alice", "Apollo", "Carol"]);
+        assert_eq!(
+            graph.relations,
+            vec![relation("Alice", "Apollo", "works_on")]
+        );
+
+        let chunks = manager.export_jsonl_chunks(&filter, 100).await?;
+        assert_eq!(chunks.concat().lines().count(), 4);
+
+        let whole = manager.export_subgraph(&ExportFilter::default()).await?;
+        assert_eq!(whole.entities.len(), 4);
+        assert_eq!(whole.relations.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_jsonl_chunks() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = (0..5)
+            .map(|i| Entity {
+                name: format!("Entity_{i}"),
+                entity_type: "Test".to_string(),
+                observations: vec![],
+                ..Default::default()
+            })
+            .collect();
+        manager.create_entities(entities, None).await?;
+
+        let chunks = manager
+            .export_jsonl_chunks(&ExportFilter::default(), 2)
+            .await?;
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].lines().count(), 1);
+
+        // The chunks hold the same lines as the file on disk
+        let stored = std::fs::read_to_string(&temp_path)?;
+        let mut stored_lines: Vec<&str> = stored.lines().collect();
+        let exported = chunks.concat();
+        let mut exported_lines: Vec<&str> = exported.lines().collect();
+        stored_lines.sort();
+        exported_lines.sort();
+        assert_eq!(exported_lines, stored_lines);
+        let reloaded = KnowledgeGraphManager::in_memory(&chunks.concat())?;
+        assert_eq!(reloaded.get_stats().await?, (5, 0));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rename_entity_type() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let entities = [("Alice", "person"), ("Bob", "Person"), ("Acme", "Company")]
+            .into_iter()
+            .map(|(name, entity_type)| Entity {
+                name: name.to_string(),
+                entity_type: entity_type.to_string(),
+                observations: vec![],
+                ..Default::default()
+            })
+            .collect();
+        manager.create_entities(entities, None).await?;
+
+        let changed = manager
+            .rename_entity_type("person".to_string(), "Human".to_string(), false)
+            .await?;
+        assert_eq!(changed, 1);
+
+        let changed = manager
+            .rename_entity_type("PERSON".to_string(), "Human".to_string(), true)
+            .await?;
+        assert_eq!(changed, 1);
+
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.entities["Alice"].entity_type, "Human");
+        assert_eq!(graph.entities["Bob"].entity_type, "Human");
+        assert_eq!(graph.entities["Acme"].entity_type, "Company");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rename_relation_type_deduplicates() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let noted = |relation: Relation, observations: &[&str]| Relation {
+            observations: observations.iter().map(|o| o.to_string()).collect(),
+            ..relation
+        };
+        manager
+            .create_relations(vec![
+                relation("Alice", "Acme", "works_at"),
+                noted(relation("Alice", "Acme", "employed_by"), &["full time"]),
+                relation("Bob", "Acme", "employed_by"),
+            ])
+            .await?;
+
+        let changed = manager
+            .rename_relation_type("employed_by".to_string(), "works_at".to_string())
+            .await?;
+        assert_eq!(changed, 2);
+
+        // The dropped duplicate's observations move to the relation kept
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.relations.len(), 2);
+        assert!(graph
+            .relations
+            .iter()
+            .all(|r| r.relation_type == "works_at"));
+        assert_eq!(graph.relations[0], relation("Alice", "Acme", "works_at"));
+        assert_eq!(graph.relations[0].observations, ["full time"]);
+
+        // Renaming onto a symmetric type merges a relation into the reverse of an existing one
+        manager
+            .set_ontology(Ontology {
+                symmetric_relation_types: vec!["works_with".to_string()],
+                ..Default::default()
+            })
+            .await?;
+        manager
+            .create_relations(vec![
+                noted(relation("Carol", "Dave", "works_with"), &["since 2020"]),
+                noted(
+                    relation("Dave", "Carol", "collaborates_with"),
+                    &["on Rust", "since 2020"],
+                ),
+            ])
+            .await?;
+        let changed = manager
+            .rename_relation_type("collaborates_with".to_string(), "works_with".to_string())
+            .await?;
+        assert_eq!(changed, 1);
+        let graph = manager.read_graph().await?;
+        let carol: Vec<&Relation> = graph
+            .relations
+            .iter()
+            .filter(|r| r.from == "Carol" || r.to == "Carol")
+            .collect();
+        assert_eq!(carol, [&relation("Carol", "Dave", "works_with")]);
+        assert_eq!(carol[0].observations, ["since 2020", "on Rust"]);
+
+        let changed = manager
+            .rename_relation_type("missing".to_string(), "works_at".to_string())
+            .await?;
+        assert_eq!(changed, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_observation_sources() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let legacy = r#"{"type":"entity","name":"Alice","entityType":"Person","observations":["Likes coffee"]}"#;
+        std::fs::write(&temp_path, format!("{legacy}\n"))?;
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .add_observations(
+                vec![(
+                    "Alice".to_string(),
+                    vec![
+                        Observation::with_source("Lives in Paris", Some("chat-1".to_string())),
+                        Observation::with_source("Likes coffee", Some("chat-1".to_string())),
+                    ],
+                )],
+                ObservationOrder::Append,
+                false,
+            )
+            .await?;
+
+        let stored = std::fs::read_to_string(&temp_path)?;
+        assert!(stored.contains(r#""Likes coffee""#));
+        assert!(stored.contains(r#"{"content":"Lives in Paris","source":"chat-1"}"#));
+
+        let graph = manager.read_graph().await?;
+        let alice = &graph.entities["Alice"];
+        assert_eq!(alice.observations, vec!["Likes coffee", "Lives in Paris"]);
+        assert_eq!(alice.observations[0].source, None);
+        assert_eq!(alice.observations[1].source.as_deref(), Some("chat-1"));
+
+        let found = manager.find_by_source("chat-1".to_string()).await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].observations, vec!["Lives in Paris"]);
+        assert!(manager
+            .find_by_source("other".to_string())
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_observation_tags() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let legacy = r#"{"type":"entity","name":"Alice","entityType":"Person","observations":["Likes coffee"]}"#;
+        std::fs::write(&temp_path, format!("{legacy}\n"))?;
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        let tags = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        manager
+            .add_observations(
+                vec![(
+                    "Alice".to_string(),
+                    vec![
+                        Observation::from("Earns a lot")
+                            .with_tags(tags(&[("topic", "finance"), ("confidence", "low")])),
+                        Observation::from("Owns shares")
+                            .with_tags(tags(&[("topic", "finance"), ("confidence", "high")])),
+                    ],
+                )],
+                ObservationOrder::Append,
+                false,
+            )
+            .await?;
+
+        // Untagged observations stay plain strings on disk
+        let stored = std::fs::read_to_string(&temp_path)?;
+        assert!(stored.contains(r#""Likes coffee""#));
+        assert!(stored.contains(
+            r#"{"content":"Owns shares","tags":{"confidence":"high","topic":"finance"}}"#
+        ));
+
+        let reloaded = KnowledgeGraphManager::with_path(&temp_path);
+        let found = reloaded.search_by_tag("topic".to_string(), None).await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].observations, vec!["Earns a lot", "Owns shares"]);
+
+        let found = reloaded
+            .search_by_tag("confidence".to_string(), Some("high".to_string()))
+            .await?;
+        assert_eq!(found[0].observations, vec!["Owns shares"]);
+        assert_eq!(
+            found[0].observations[0].tags,
+            tags(&[("confidence", "high"), ("topic", "finance")])
+        );
+
+        assert!(reloaded
+            .search_by_tag("confidence".to_string(), Some("medium".to_string()))
+            .await?
+            .is_empty());
+        assert!(reloaded
+            .search_by_tag("mood".to_string(), None)
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_back_flush_persists_pending_changes() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path).with_write_back(true);
+
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec![],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        // Dirty state is visible in memory but not yet on disk
+        assert_eq!(manager.get_stats().await?, (1, 0));
+        assert!(!temp_path.exists());
+
+        // Simulated shutdown
+        manager.flush().await?;
+        drop(manager);
+
+        let reopened = KnowledgeGraphManager::with_path(&temp_path);
+        assert!(reopened.read_graph().await?.entities.contains_key("Alice"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_journal_replay_after_crash() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let journal_path = temp_dir.path().join("test_memory.journal.jsonl");
+        let open = || {
+            KnowledgeGraphManager::with_path(&temp_path)
+                .with_write_back(true)
+                .with_journal_path(Some(journal_path.clone()))
+        };
+
+        let manager = open();
+        let person = |name: &str| Entity {
+            name: name.to_string(),
+            entity_type: "Person".to_string(),
+            observations: vec![],
+            ..Default::default()
+        };
+        manager
+            .create_entities(vec![person("Alice"), person("Bob")], None)
+            .await?;
+        manager.flush().await?;
+        assert_eq!(fs::read_to_string(&journal_path).await?, "");
+
+        manager.create_entities(vec![person("Carol")], None).await?;
+        manager
+            .create_relations(vec![Relation {
+                from: "Alice".to_string(),
+                to: "Carol".to_string(),
+                relation_type: "knows".to_string(),
+                ..Default::default()
+            }])
+            .await?;
+        manager
+            .add_observations(
+                vec![("Alice".to_string(), vec!["Likes tea".into()])],
+                ObservationOrder::Append,
+                false,
+            )
+            .await?;
+        manager
+            .delete_entities(vec!["Bob".to_string()], false, false)
+            .await?;
+
+        // Simulated crash: the changes were acknowledged but never flushed
+        drop(manager);
+        let on_disk = KnowledgeGraphManager::with_path(&temp_path);
+        assert!(on_disk.read_graph().await?.entities.contains_key("Bob"));
+
+        let reopened = open();
+        assert_eq!(reopened.recover().await?, 4);
+        assert_eq!(fs::read_to_string(&journal_path).await?, "");
+
+        let graph = KnowledgeGraphManager::with_path(&temp_path)
+            .read_graph()
+            .await?;
+        let mut names: Vec<&str> = graph.entities.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["Alice", "Carol"]);
+        assert_eq!(
+            graph.entities["Alice"].observations,
+            vec![Observation::from("Likes tea")]
+        );
+        assert_eq!(graph.relations.len(), 1);
+
+        // Nothing left to replay
+        assert_eq!(reopened.recover().await?, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_journal_torn_tail() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let journal_path = temp_dir.path().join("test_memory.journal.jsonl");
+        let torn = r#"{"entities":[{"name":"Gho"#;
+        let open = || {
+            KnowledgeGraphManager::with_path(&temp_path)
+                .with_write_back(true)
+                .with_journal_path(Some(journal_path.clone()))
+        };
+        let person = |name: &str| Entity {
+            name: name.to_string(),
+            entity_type: "Person".to_string(),
+            ..Default::default()
+        };
+
+        // A crash during the first append after a checkpoint leaves only a torn line
+        fs::write(&journal_path, torn).await?;
+        let manager = open();
+        assert_eq!(manager.recover().await?, 0);
+        assert_eq!(fs::read_to_string(&journal_path).await?, "");
+
+        // Appending without recovering first cuts the torn line off
+        fs::write(&journal_path, torn).await?;
+        manager.create_entities(vec![person("Alice")], None).await?;
+        drop(manager);
+        assert!(!fs::read_to_string(&journal_path).await?.contains("Gho"));
+
+        let reopened = open();
+        assert_eq!(reopened.recover().await?, 1);
+        assert!(reopened.read_graph().await?.entities.contains_key("Alice"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_sees_external_edits() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec!["Drinks tea".into()],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+        let options = SearchOptions::default();
+        let search = || manager.search_nodes("tea", None, &options);
+        assert!(search().await?.entities.contains_key("Alice"));
+
+        // Another process rewrites the memory file
+        fs::write(
+            &temp_path,
+            r#"{"type":"entity","name":"Bob","entityType":"Person","observations":["Drinks green tea"]}"#,
+        )
+        .await?;
+        let found = search().await?;
+        assert!(found.entities.contains_key("Bob"));
+        assert!(!found.entities.contains_key("Alice"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_federated_read() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let primary_path = temp_dir.path().join("primary.jsonl");
+        let other_path = temp_dir.path().join("other.jsonl");
+
+        KnowledgeGraphManager::with_path(&other_path)
+            .create_entities(
+                vec![
+                    Entity {
+                        name: "Shared".to_string(),
+                        entity_type: "Other".to_string(),
+                        observations: vec![],
+                        ..Default::default()
+                    },
+                    Entity {
+                        name: "Remote".to_string(),
+                        entity_type: "Project".to_string(),
+                        observations: vec!["Lives in another project".into()],
+                        ..Default::default()
+                    },
+                ],
+                None,
+            )
+            .await?;
+        let other_before = fs::read_to_string(&other_path).await?;
+
+        let manager = KnowledgeGraphManager::with_path(&primary_path)
+            .with_federated_paths(vec![other_path.clone()]);
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Shared".to_string(),
+                    entity_type: "Primary".to_string(),
+                    observations: vec![],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        let graph = manager.read_graph().await?;
+        assert_eq!(graph.entities.len(), 2);
+        assert_eq!(graph.entities["Shared"].entity_type, "Primary");
+        assert_eq!(
+            graph.entities["Shared"].source_file.as_deref(),
+            Some(primary_path.display().to_string().as_str())
+        );
+        assert_eq!(
+            graph.entities["Remote"].source_file.as_deref(),
+            Some(other_path.display().to_string().as_str())
+        );
+
+        let results = manager
+            .search_nodes("another project", None, &SearchOptions::default())
+            .await?;
+        assert!(results.entities.contains_key("Remote"));
+
+        // Writes only touch the primary file
+        manager
+            .create_relations(vec![Relation {
+                from: "Shared".to_string(),
+                to: "Shared".to_string(),
+                relation_type: "self".to_string(),
+                ..Default::default()
+            }])
+            .await?;
+        assert_eq!(fs::read_to_string(&other_path).await?, other_before);
+        let primary = KnowledgeGraphManager::with_path(&primary_path)
+            .read_graph()
+            .await?;
+        assert_eq!(primary.entities.len(), 1);
+        assert!(primary.entities["Shared"].source_file.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_deletions_leave_file_unchanged() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
+
+        manager
+            .create_entities(
+                vec![
+                    Entity {
+                        name: "Alice".to_string(),
+                        entity_type: "Person".to_string(),
+                        observations: vec!["Likes tea".into()],
+                        ..Default::default()
+                    },
+                    Entity {
+                        name: "Bob".to_string(),
+                        entity_type: "Person".to_string(),
+                        observations: vec![],
+                        ..Default::default()
+                    },
+                ],
+                None,
+            )
+            .await?;
+        let knows = Relation {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            relation_type: "knows".to_string(),
+            ..Default::default()
+        };
+        manager.create_relations(vec![knows.clone()]).await?;
+        let before = fs::read_to_string(&temp_path).await?;
+
+        let report = manager
+            .delete_entities(vec!["Bob".to_string()], true, false)
+            .await?;
+        assert_eq!(report.entities, vec!["Bob"]);
+        assert_eq!(report.relations, vec![knows.clone()]);
+
+        let report = manager
+            .delete_observations(
+                vec![("Alice".to_string(), vec!["Likes tea".to_string()])],
+                true,
+            )
+            .await?;
+        assert_eq!(
+            report.observations,
+            vec![("Alice".to_string(), vec!["Likes tea".to_string()])]
+        );
+
+        let report = manager.delete_relations(vec![knows.clone()], true).await?;
+        assert_eq!(report.relations, vec![knows]);
+
+        let report = manager.clear_graph(true).await?;
+        assert_eq!(report.entities, vec!["Alice", "Bob"]);
+        assert_eq!(report.relations.len(), 1);
+
+        assert_eq!(fs::read_to_string(&temp_path).await?, before);
+
+        manager.clear_graph(false).await?;
+        assert_eq!(manager.get_stats().await?, (0, 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_name_policy_canonical_forms() {
+        let name = "  Alice   Smith ";
+        assert_eq!(NamePolicy::None.canonical(name), "  Alice   Smith ");
+        assert_eq!(NamePolicy::Trim.canonical(name), "Alice   Smith");
+        assert_eq!(
+            NamePolicy::TrimCollapseWhitespace.canonical(name),
+            "Alice Smith"
+        );
+        assert_eq!(NamePolicy::Lowercase.canonical(name), "alice smith");
+
+        assert_eq!(NamePolicy::from_env_value(None), NamePolicy::Trim);
+        assert_eq!(NamePolicy::from_env_value(Some("none")), NamePolicy::None);
+        assert_eq!(
+            NamePolicy::from_env_value(Some("trim_collapse_whitespace")),
+            NamePolicy::TrimCollapseWhitespace
+        );
+        assert_eq!(
+            NamePolicy::from_env_value(Some("Lowercase")),
+            NamePolicy::Lowercase
+        );
+    }
+
+    #[tokio::test]
+    async fn test_name_policies_deduplicate_entities() -> Result<()> {
+        let variants = ["Alice Smith", "Alice Smith ", "Alice  Smith", "alice smith"];
+        // Number of distinct entities each policy keeps from `variants`
+        let cases = [
+            (NamePolicy::None, 4),
+            (NamePolicy::Trim, 3),
+            (NamePolicy::TrimCollapseWhitespace, 2),
+            (NamePolicy::Lowercase, 1),
+        ];
+
+        for (policy, expected) in cases {
+            let temp_dir = tempdir()?;
+            let temp_path = temp_dir.path().join("test_memory.jsonl");
+            let manager = KnowledgeGraphManager::with_path(&temp_path)
+                .with_name_policy(policy)
+                .with_case_insensitive_duplicates(false);
+
+            let entities = variants
+                .iter()
+                .map(|name| Entity {
+                    name: name.to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec![],
+                    ..Default::default()
+                })
+                .collect();
+            let created = manager.create_entities(entities, None).await?.created;
+            assert_eq!(created.len(), expected, "{policy:?}");
+            // The first spelling is kept as the display name
+            assert_eq!(created[0].name, "Alice Smith", "{policy:?}");
+        }
+
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager =
+            KnowledgeGraphManager::with_path(&temp_path).with_name_policy(NamePolicy::Lowercase);
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Alice Smith".to_string(),
+                    entity_type: "Person".to_string(),
+                    observations: vec![],
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
+
+        manager
+            .add_observations(
+                vec![(" ALICE  smith".to_string(), vec!["Found".into()])],
+                ObservationOrder::Append,
+                false,
+            )
+            .await?;
+        let opened = manager
+            .open_nodes(vec!["alice smith".to_string()], None)
+            .await?;
+        assert_eq!(opened.entities[0].name, "Alice Smith");
+        assert_eq!(opened.entities[0].observations, vec!["Found"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connect_or_create() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().join("test_memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
 
-        let entities = self
-            .search_engine
-            .enhanced_text_search(&graph, query, limit)
+        manager
+            .create_entities(vec![entity("Alice", "Person", &[])], None)
             .await?;
 
-        let filtered_entity_names: HashSet<String> =
-            entities.iter().map(|e| e.name.clone()).collect();
+        let report = manager
+            .connect_or_create(
+                entity(" Alice ", "Person", &["New fact"]),
+                entity("Acme", "Company", &["New fact"]),
+                "works_at".to_string(),
+            )
+            .await?;
+        let created: Vec<&str> = report
+            .created_entities
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(created, ["Acme"]);
+        assert_eq!(report.existing_entities, ["Alice"]);
+        let works_at = Relation {
+            from: "Alice".to_string(),
+            to: "Acme".to_string(),
+            relation_type: "works_at".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(report.relation, works_at);
+        assert!(report.relation_created);
 
-        let filtered_relations = self.search_engine.filter_relations_smart(
-            &graph.relations,
-            &filtered_entity_names,
-            false,
-            false,
-        );
+        // The existing entity was not touched
+        let graph = manager.read_graph().await?;
+        assert!(graph.entities["Alice"].observations.is_empty());
 
-        Ok(Box::new(KnowledgeGraph {
-            entities: entities.into_iter().map(|e| (e.name.clone(), e)).collect(),
-            relations: filtered_relations,
-        }))
-    }
-}
+        // Running it again changes nothing
+        let version = manager.modification_info().await?.version;
+        let report = manager
+            .connect_or_create(
+                entity("Alice", "Person", &["New fact"]),
+                entity("Acme", "Company", &["New fact"]),
+                "works_at".to_string(),
+            )
+            .await?;
+        assert!(report.created_entities.is_empty());
+        assert_eq!(report.existing_entities, ["Alice", "Acme"]);
+        assert!(!report.relation_created);
+        assert_eq!(manager.modification_info().await?.version, version);
+        assert_eq!(manager.read_graph().await?.relations, vec![works_at]);
 
-impl Default for KnowledgeGraphManager {
-    fn default() -> Self {
-        Self::new()
+        Ok(())
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
 
     #[tokio::test]
-    async fn test_create_and_read_entities() -> Result<()> {
+    async fn test_replace_in_observations() -> Result<()> {
         let temp_dir = tempdir()?;
         let temp_path = temp_dir.path().join("test_memory.jsonl");
         let manager = KnowledgeGraphManager::with_path(&temp_path);
 
-        let entities = vec![Entity {
-            name: "Alice".to_string(),
-            entity_type: "Person".to_string(),
-            observations: vec!["Likes coffee".to_string()],
-        }];
+        manager
+            .create_entities(
+                vec![
+                    entity(
+                        "Alice",
+                        "Person",
+                        &["Uses Widgit Pro", "Uses Widget Pro", "Likes tea"],
+                    ),
+                    entity("Bob", "Person", &["Sells Widgit Pro"]),
+                ],
+                None,
+            )
+            .await?;
 
-        let created = manager.create_entities(entities.clone()).await?;
-        assert_eq!(created.len(), 1);
-        assert_eq!(created[0].name, "Alice");
+        let changed = manager
+            .replace_in_observations(
+                "Widgit".to_string(),
+                "Widget".to_string(),
+                Some(vec!["Alice".to_string()]),
+            )
+            .await?;
+        assert_eq!(changed, 1);
+        let graph = manager.read_graph().await?;
+        let contents = |name: &str| -> Vec<String> {
+            graph.entities[name]
+                .observations
+                .iter()
+                .map(|o| o.content.clone())
+                .collect()
+        };
+        // The corrected observation duplicated an existing one and was dropped
+        assert_eq!(contents("Alice"), ["Uses Widget Pro", "Likes tea"]);
+        assert_eq!(contents("Bob"), ["Sells Widgit Pro"]);
 
+        let changed = manager
+            .replace_in_observations("Widgit".to_string(), "Widget".to_string(), None)
+            .await?;
+        assert_eq!(changed, 1);
         let graph = manager.read_graph().await?;
-        assert_eq!(graph.entities.len(), 1);
-        let alice = graph.entities.get("Alice").unwrap();
-        assert_eq!(alice.name, "Alice");
+        assert_eq!(
+            graph.entities["Bob"].observations[0].content,
+            "Sells Widget Pro"
+        );
+
+        assert!(manager
+            .replace_in_observations(
+                "tea".to_string(),
+                "coffee".to_string(),
+                Some(vec!["Nobody".to_string()]),
+            )
+            .await
+            .is_err());
+        assert!(manager
+            .replace_in_observations(String::new(), "x".to_string(), None)
+            .await
+            .is_err());
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_boxed_manager() -> Result<()> {
+    async fn test_get_config() -> Result<()> {
         let temp_dir = tempdir()?;
-        let temp_path = temp_dir.path().join("test_memory.jsonl");
-        let manager = Box::new(KnowledgeGraphManager::with_path(&temp_path));
+        let temp_path = temp_dir.path().join("memory.jsonl");
+        let manager = KnowledgeGraphManager::with_path(&temp_path)
+            .with_name_policy(NamePolicy::Lowercase)
+            .with_audit_log_size(5)
+            .with_search_config_path(None);
+        manager
+            .set_search_config(SearchConfig {
+                name_weight: Some(5.0),
+                ..Default::default()
+            })
+            .await?;
 
-        let entities = vec![Entity {
-            name: "BoxedEntity".to_string(),
-            entity_type: "Test".to_string(),
-            observations: vec!["Created with Box".to_string()],
-        }];
+        let config = manager.get_config().await?;
+        assert_eq!(config.memory_path, Some(temp_path));
+        assert_eq!(config.name_policy, NamePolicy::Lowercase);
+        assert_eq!(config.audit_log_size, 5);
+        assert!(!config.strict_schema);
+        // Settings from the config file override the defaults, which fill in the rest
+        assert_eq!(config.search.name_weight, Some(5.0));
+        assert_eq!(config.search.type_weight, Some(1.5));
 
-        let created = manager.create_entities(entities).await?;
-        assert_eq!(created.len(), 1);
-        assert_eq!(created[0].name, "BoxedEntity");
+        let json = serde_json::to_value(&config)?;
+        assert_eq!(json["name_policy"], "lowercase");
+        assert_eq!(json["duplicate_policy"], "skip");
+
+        let in_memory = KnowledgeGraphManager::in_memory("")?.get_config().await?;
+        assert_eq!(in_memory.memory_path, None);
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_large_graph_in_heap() -> Result<()> {
+    async fn test_search_config_persists() -> Result<()> {
         let temp_dir = tempdir()?;
-        let temp_path = temp_dir.path().join("test_large_memory.jsonl");
+        let temp_path = temp_dir.path().join("memory.jsonl");
         let manager = KnowledgeGraphManager::with_path(&temp_path);
 
-        let mut large_entities = Vec::new();
-        for i in 0..10000 {
-            large_entities.push(Entity {
-                name: format!("LargeEntity_{i}"),
-                entity_type: "TestLarge".to_string(),
-                observations: vec![
-                    format!("Observation 1 for {}", i),
-                    format!("Observation 2 for {}", i),
-                    format!("Large data set with ID {}", i),
+        manager
+            .create_entities(
+                vec![
+                    Entity {
+                        name: "Rust Book".to_string(),
+                        entity_type: "Book".to_string(),
+                        observations: vec![],
+                        ..Default::default()
+                    },
+                    Entity {
+                        name: "Alice".to_string(),
+                        entity_type: "Person".to_string(),
+                        observations: vec!["Loves rust".into(), "Writes rust daily".into()],
+                        ..Default::default()
+                    },
                 ],
-            });
+                None,
+            )
+            .await?;
+        async fn top_hit(manager: &KnowledgeGraphManager) -> Result<String> {
+            let graph = manager
+                .search_nodes("rust", Some(1), &SearchOptions::default())
+                .await?;
+            Ok(graph.entities.into_keys().next().unwrap())
         }
+        assert_eq!(top_hit(&manager).await?, "Alice");
 
-        let created = manager.create_entities(large_entities).await?;
-        assert_eq!(created.len(), 10000);
-
-        let (entity_count, relation_count) = manager.get_stats().await?;
-        assert_eq!(entity_count, 10000);
-        assert_eq!(relation_count, 0);
+        let config = manager
+            .set_search_config(SearchConfig {
+                name_weight: Some(5.0),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(config.name_weight, Some(5.0));
+        assert!(temp_dir.path().join("memory.config.json").exists());
+        assert_eq!(top_hit(&manager).await?, "Rust Book");
 
-        let search_result = manager.search_nodes("LargeEntity_9999", None).await?;
-        // Enhanced search may return multiple similar entities
-        assert!(
-            !search_result.entities.is_empty(),
-            "Should find at least LargeEntity_9999"
-        );
-        assert!(
-            search_result.entities.contains_key("LargeEntity_9999"),
-            "Should find LargeEntity_9999"
-        );
-        let large_entity = search_result.entities.get("LargeEntity_9999").unwrap();
-        assert_eq!(large_entity.name, "LargeEntity_9999");
+        // A new manager picks the settings up from the sidecar file
+        let reopened = KnowledgeGraphManager::with_path(&temp_path);
+        assert_eq!(reopened.get_search_config().await?, config);
+        assert_eq!(top_hit(&reopened).await?, "Rust Book");
 
-        let full_graph = manager.read_graph().await?;
-        assert_eq!(full_graph.entities.len(), 10000);
+        // Out-of-range values are rejected and leave the settings as they were
+        for update in [
+            SearchConfig {
+                observation_weight: Some(-1.0),
+                ..Default::default()
+            },
+            SearchConfig {
+                type_weight: Some(f32::NAN),
+                ..Default::default()
+            },
+            SearchConfig {
+                connectivity_cap: Some(1.5),
+                ..Default::default()
+            },
+        ] {
+            assert!(reopened.set_search_config(update).await.is_err());
+        }
+        assert_eq!(reopened.get_search_config().await?, config);
 
-        println!(
-            "Successfully handled {} entities in heap",
-            full_graph.entities.len()
+        // Concurrent updates of different fields all take effect
+        let (type_weight, observation_weight) = tokio::join!(
+            reopened.set_search_config(SearchConfig {
+                type_weight: Some(3.0),
+                ..Default::default()
+            }),
+            reopened.set_search_config(SearchConfig {
+                observation_weight: Some(4.0),
+                ..Default::default()
+            }),
+        );
+        type_weight?;
+        observation_weight?;
+        let merged = reopened.get_search_config().await?;
+        assert_eq!(
+            (
+                merged.name_weight,
+                merged.type_weight,
+                merged.observation_weight
+            ),
+            (Some(5.0), Some(3.0), Some(4.0))
         );
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_arc_manager() -> Result<()> {
+    async fn test_symmetric_relation_types() -> Result<()> {
         let temp_dir = tempdir()?;
         let temp_path = temp_dir.path().join("test_memory.jsonl");
-        let manager = std::sync::Arc::new(KnowledgeGraphManager::with_path(&temp_path));
-        let manager_clone = manager.clone();
-
-        assert_eq!(std::sync::Arc::strong_count(&manager), 2);
+        let manager = KnowledgeGraphManager::with_path(&temp_path);
 
-        let entities = vec![Entity {
-            name: "ArcEntity".to_string(),
-            entity_type: "Test".to_string(),
-            observations: vec!["Created with Arc".to_string()],
-        }];
+        manager
+            .create_entities(
+                vec![entity("Alice", "Person", &[]), entity("Bob", "Person", &[])],
+                None,
+            )
+            .await?;
+        manager
+            .set_ontology(Ontology {
+                symmetric_relation_types: vec!["sibling_of".to_string()],
+                ..Default::default()
+            })
+            .await?;
 
-        let created = manager_clone.create_entities(entities).await?;
+        let created = manager
+            .create_relations(vec![relation("Bob", "Alice", "sibling_of")])
+            .await?
+            .created;
         assert_eq!(created.len(), 1);
 
-        let graph = manager.read_graph().await?; // Box<KnowledgeGraph>
-        assert_eq!(graph.entities.len(), 1);
-        let arc_entity = graph.entities.get("ArcEntity").unwrap();
-        assert_eq!(arc_entity.name, "ArcEntity");
+        // The reverse of a symmetric relation is a duplicate, within a batch too
+        let created = manager
+            .create_relations(vec![
+                relation("Alice", "Bob", "sibling_of"),
+                relation("Alice", "Bob", "knows"),
+                relation("Bob", "Alice", "knows"),
+            ])
+            .await?
+            .created;
+        assert_eq!(
+            created,
+            vec![
+                relation("Alice", "Bob", "knows"),
+                relation("Bob", "Alice", "knows"),
+            ]
+        );
+        let report = manager
+            .connect_or_create(
+                entity("Alice", "Person", &[]),
+                entity("Bob", "Person", &[]),
+                "sibling_of".to_string(),
+            )
+            .await?;
+        assert!(!report.relation_created);
+        assert_eq!(manager.read_graph().await?.relations.len(), 3);
+
+        // Lookups and deletes match the reverse of a symmetric relation, but not of others
+        assert!(
+            manager
+                .relation_exists("Alice".into(), "Bob".into(), "sibling_of".into())
+                .await?
+        );
+        manager
+            .delete_relations(vec![relation("Bob", "Alice", "knows")], false)
+            .await?;
+        assert!(
+            manager
+                .relation_exists("Alice".into(), "Bob".into(), "knows".into())
+                .await?
+        );
+        let report = manager
+            .delete_relations(vec![relation("Alice", "Bob", "sibling_of")], false)
+            .await?;
+        assert_eq!(
+            report.relations,
+            vec![relation("Bob", "Alice", "sibling_of")]
+        );
+        assert!(
+            !manager
+                .relation_exists("Bob".into(), "Alice".into(), "sibling_of".into())
+                .await?
+        );
+        assert_eq!(manager.read_graph().await?.relations.len(), 1);
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_create_relations() -> Result<()> {
+    async fn test_strict_ontology_rejects_unknown_types() -> Result<()> {
         let temp_dir = tempdir()?;
         let temp_path = temp_dir.path().join("test_memory.jsonl");
-        let manager = KnowledgeGraphManager::with_path(&temp_path);
-
-        // Ensure we start with a clean graph
-        let initial_graph = manager.read_graph().await?;
-        assert_eq!(initial_graph.relations.len(), 0);
-
-        let entities = vec![
-            Entity {
-                name: "Alice".to_string(),
-                entity_type: "Person".to_string(),
-                observations: vec![],
-            },
-            Entity {
-                name: "Bob".to_string(),
-                entity_type: "Person".to_string(),
-                observations: vec![],
-            },
-        ];
-
-        manager.create_entities(entities).await?;
-
-        // Verify entities were created
-        let graph_after_entities = manager.read_graph().await?;
-        assert_eq!(graph_after_entities.entities.len(), 2);
-        assert_eq!(graph_after_entities.relations.len(), 0);
+        let schema_path = temp_dir.path().join("schema.json");
+        let manager = KnowledgeGraphManager::with_path(&temp_path)
+            .with_schema_path(Some(schema_path.clone()));
 
-        let relations = vec![Relation {
-            from: "Alice".to_string(),
-            to: "Bob".to_string(),
-            relation_type: "knows".to_string(),
-        }];
+        assert_eq!(manager.get_ontology().await?, Ontology::default());
+        let person = |entity_type: &str| Entity {
+            name: format!("A {entity_type}"),
+            entity_type: entity_type.to_string(),
+            observations: vec![],
+            ..Default::default()
+        };
 
-        let created_relations = manager.create_relations(relations.clone()).await?;
+        let ontology = Ontology {
+            entity_types: vec!["person".to_string()],
+            relation_types: vec!["knows".to_string()],
+            strict: false,
+            ..Default::default()
+        };
+        manager.set_ontology(ontology.clone()).await?;
+        // Not enforced until strict
+        manager.create_entities(vec![person("robot")], None).await?;
 
-        // Debug information for CI failures
-        if created_relations.len() != 1 {
-            let current_graph = manager.read_graph().await?;
-            panic!(
-                "Expected 1 created relation, got {}. Current graph has {} relations: {:?}. Trying to create: {:?}",
-                created_relations.len(),
-                current_graph.relations.len(),
-                current_graph.relations,
-                relations
-            );
-        }
+        manager
+            .set_ontology(Ontology {
+                strict: true,
+                ..ontology
+            })
+            .await?;
+        let err = manager
+            .create_entities(
+                vec![person("person"), person("alien"), person("animal")],
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "entity types not in schema: alien, animal");
+        assert_eq!(manager.get_stats().await?, (1, 0));
 
-        assert_eq!(created_relations.len(), 1);
+        manager
+            .create_entities(vec![person("person")], None)
+            .await?;
+        let err = manager
+            .create_relations(vec![Relation {
+                from: "A person".to_string(),
+                to: "A robot".to_string(),
+                relation_type: "owns".to_string(),
+                ..Default::default()
+            }])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("owns"));
 
-        let graph = manager.read_graph().await?;
-        assert_eq!(graph.relations.len(), 1);
-        assert_eq!(graph.relations[0].from, "Alice");
-        assert_eq!(graph.relations[0].to, "Bob");
+        // The ontology survives a restart through the schema file
+        let reopened =
+            KnowledgeGraphManager::with_path(&temp_path).with_schema_path(Some(schema_path));
+        assert!(reopened.get_ontology().await?.strict);
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_search_nodes() -> Result<()> {
+    async fn test_modification_info_and_changed_since() -> Result<()> {
         let temp_dir = tempdir()?;
         let temp_path = temp_dir.path().join("test_memory.jsonl");
         let manager = KnowledgeGraphManager::with_path(&temp_path);
 
-        let entities = vec![
-            Entity {
-                name: "Alice".to_string(),
-                entity_type: "Person".to_string(),
-                observations: vec!["Likes coffee".to_string()],
-            },
-            Entity {
-                name: "Bob".to_string(),
-                entity_type: "Person".to_string(),
-                observations: vec!["Likes tea".to_string()],
-            },
-        ];
+        assert_eq!(
+            manager.modification_info().await?,
+            ModificationInfo::default()
+        );
 
-        manager.create_entities(entities).await?;
+        let dated = |name: &str, updated_at: i64| Entity {
+            created_at: Some(1),
+            updated_at: Some(updated_at),
+            ..entity(name, "Test", &[])
+        };
+        manager
+            .create_entities(
+                vec![dated("Old", 100), dated("Newer", 300), dated("New", 200)],
+                None,
+            )
+            .await?;
+        manager
+            .create_entities(
+                vec![Entity {
+                    name: "Untimed".to_string(),
+                    ..Default::default()
+                }],
+                None,
+            )
+            .await?;
 
-        let search_result = manager.search_nodes("coffee", None).await?;
-        // The enhanced search may return more entities based on relevance scores
-        assert!(
-            !search_result.entities.is_empty(),
-            "Should find at least Alice"
-        );
-        assert!(
-            search_result.entities.contains_key("Alice"),
-            "Should find Alice who likes coffee"
-        );
-        let alice = search_result.entities.get("Alice").unwrap();
-        assert_eq!(alice.name, "Alice");
+        let info = manager.modification_info().await?;
+        assert_eq!(info.version, 2);
+        assert!(info.last_modified.is_some());
 
-        Ok(())
-    }
+        // Dry runs don't count as changes
+        manager.clear_graph(true).await?;
+        assert_eq!(manager.modification_info().await?.version, 2);
 
-    #[tokio::test]
-    async fn test_entity_not_found_error() {
-        let temp_dir = tempdir().unwrap();
-        let temp_path = temp_dir.path().join("test_memory.jsonl");
-        let manager = KnowledgeGraphManager::with_path(&temp_path);
+        let names: Vec<_> = manager
+            .entities_changed_since(100)
+            .await?
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        // The bound is inclusive, so a change in the same second isn't missed
+        assert_eq!(names[..3], ["Old", "New", "Newer"]);
+        // "Untimed" was stamped with the current time on creation
+        assert_eq!(names[3], "Untimed");
 
-        let result = manager
-            .add_observations(vec![(
-                "NonExistent".to_string(),
-                vec!["Some observation".to_string()],
-            )])
-            .await;
+        // A fresh manager falls back to the file's modification time
+        let reopened = KnowledgeGraphManager::with_path(&temp_path);
+        let info = reopened.modification_info().await?;
+        assert_eq!(info.version, 0);
+        assert!(info.last_modified.is_some());
 
-        assert!(result.is_err());
-        let error_message = result.unwrap_err().to_string();
-        assert!(error_message.contains("NonExistent"));
-        assert!(error_message.contains("not found"));
+        Ok(())
     }
 
     #[tokio::test]
-    async fn test_stats_and_entity_exists() -> Result<()> {
+    async fn test_write_debounce_coalesces_writes() -> Result<()> {
         let temp_dir = tempdir()?;
         let temp_path = temp_dir.path().join("test_memory.jsonl");
-        let manager = KnowledgeGraphManager::with_path(&temp_path);
+        let manager = KnowledgeGraphManager::with_path(&temp_path)
+            .with_write_debounce(Some(Duration::from_millis(100)));
 
-        let (initial_entities, initial_relations) = manager.get_stats().await?;
-        assert_eq!(initial_entities, 0);
-        assert_eq!(initial_relations, 0);
+        for i in 0..20 {
+            manager
+                .create_entities(
+                    vec![Entity {
+                        name: format!("Entity {i}"),
+                        entity_type: "Test".to_string(),
+                        observations: vec![],
+                        ..Default::default()
+                    }],
+                    None,
+                )
+                .await?;
+        }
+        let last_change = std::time::Instant::now();
+        assert_eq!(manager.get_stats().await?, (20, 0));
+        assert_eq!(manager.pending.disk_writes.load(Ordering::SeqCst), 0);
 
-        let entities = vec![
-            Entity {
-                name: "TestEntity1".to_string(),
-                entity_type: "Test".to_string(),
-                observations: vec![],
-            },
-            Entity {
-                name: "TestEntity2".to_string(),
-                entity_type: "Test".to_string(),
-                observations: vec![],
-            },
-        ];
+        for _ in 0..25 {
+            if manager.pending.disk_writes.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(manager.pending.disk_writes.load(Ordering::SeqCst), 1);
+        assert!(last_change.elapsed() >= Duration::from_millis(100));
 
-        manager.create_entities(entities).await?;
+        let reopened = KnowledgeGraphManager::with_path(&temp_path);
+        assert_eq!(reopened.get_stats().await?, (20, 0));
 
-        let (updated_entities, updated_relations) = manager.get_stats().await?;
-        assert_eq!(updated_entities, 2);
-        assert_eq!(updated_relations, 0);
+        // An explicit flush writes immediately
+        manager.touch_entity("Entity 0".to_string()).await?;
+        manager.flush().await?;
+        let writes = manager.pending.disk_writes.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(manager.pending.disk_writes.load(Ordering::SeqCst), writes);
+
+        // With write-back set as well, the debounce still writes once mutations pause
+        let manager = KnowledgeGraphManager::with_path(&temp_path)
+            .with_write_back(true)
+            .with_write_debounce(Some(Duration::from_millis(50)));
+        manager
+            .create_entities(vec![entity("Late", "Test", &[])], None)
+            .await?;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(manager.pending.disk_writes.load(Ordering::SeqCst), 1);
+        assert_eq!(reopened.get_stats().await?, (21, 0));
 
         Ok(())
     }
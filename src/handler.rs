@@ -1,39 +1,254 @@
+use std::collections::{BTreeMap, HashMap};
 use std::future::Future;
 use std::sync::Arc;
 
 use rmcp::{
-    handler::server::{router::tool::ToolRouter, tool::Parameters},
-    model::{CallToolResult, Content, ErrorData as McpError, ServerCapabilities, ServerInfo},
-    schemars, tool, tool_handler, tool_router, ServerHandler,
+    handler::server::{
+        router::tool::ToolRouter,
+        tool::{Parameters, ToolCallContext},
+    },
+    model::{
+        CallToolRequestParam, CallToolResult, Content, ErrorData as McpError, ListToolsResult,
+        PaginatedRequestParam, ServerCapabilities, ServerInfo,
+    },
+    schemars,
+    service::RequestContext,
+    tool, tool_router, RoleServer, ServerHandler,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::graph::{Entity, KnowledgeGraph, KnowledgeGraphManager, Relation};
+use crate::export::{ExportFilter, ExportFormat, DEFAULT_OBSERVATION_DELIMITER};
+use crate::graph::{
+    AddedObservations, AddedRelationObservations, AuditEntry, Centrality, ConnectReport,
+    ConsolidationPreview, CoreConcepts, CreateReport, CreatedRelations, Degree, DeletionReport,
+    DetailedStats, DuplicatePolicy, EffectiveConfig, Entity, EntityDescription, EntityValidation,
+    FuzzyMatch, GraphMetrics, GraphOverview, GraphPath, KnowledgeGraph, KnowledgeGraphManager,
+    ModificationInfo, Observation, ObservationOrder, ObservationSort, Ontology, OpenedNodes,
+    RelatedEntity, Relation, SearchResults, SimilarEntity, SplitEntity, TimeField, UpsertStatus,
+    ValidationReport, DEFAULT_CORE_CONCEPTS, DEFAULT_OVERVIEW_LIMIT,
+};
+use crate::search::{
+    match_snippet, EntityCoverage, FieldBoosts, ObservationMatch, RankerKind, SearchConfig,
+    SearchOptions,
+};
 
 #[async_trait::async_trait]
 pub trait GraphService: Send + Sync + 'static {
-    async fn create_entities(&self, entities: Vec<Entity>) -> anyhow::Result<Vec<Entity>>;
-    async fn create_relations(&self, relations: Vec<Relation>) -> anyhow::Result<Vec<Relation>>;
+    /// A read-only service over another memory file, such as an archived snapshot
+    async fn scoped(&self, file: &str) -> anyhow::Result<Self>
+    where
+        Self: Sized;
+    async fn create_entities(
+        &self,
+        entities: Vec<Entity>,
+        on_duplicate: Option<DuplicatePolicy>,
+    ) -> anyhow::Result<CreateReport>;
+    async fn create_relations(&self, relations: Vec<Relation>) -> anyhow::Result<CreatedRelations>;
+    async fn connect_or_create(
+        &self,
+        from: Entity,
+        to: Entity,
+        relation_type: String,
+    ) -> anyhow::Result<ConnectReport>;
+    async fn upsert_entities(
+        &self,
+        entities: Vec<Entity>,
+        update_type: bool,
+    ) -> anyhow::Result<Vec<(String, UpsertStatus)>>;
     async fn search_nodes(
         &self,
         query: &str,
         limit: Option<usize>,
-    ) -> anyhow::Result<Box<KnowledgeGraph>>;
+        options: SearchOptions,
+    ) -> anyhow::Result<SearchResults>;
     async fn get_stats(&self) -> anyhow::Result<(usize, usize)>;
+    async fn get_detailed_stats(&self) -> anyhow::Result<DetailedStats>;
+    async fn graph_metrics(&self, exact_diameter: bool) -> anyhow::Result<GraphMetrics>;
+    async fn core_concepts(&self, k: usize, centrality: Centrality)
+        -> anyhow::Result<CoreConcepts>;
+    async fn overview(&self, limit: usize) -> anyhow::Result<GraphOverview>;
+    async fn modification_info(&self) -> anyhow::Result<ModificationInfo>;
+    async fn entities_changed_since(&self, since: i64) -> anyhow::Result<Vec<Entity>>;
+    async fn entities_in_range(
+        &self,
+        start: Option<i64>,
+        end: Option<i64>,
+        field: TimeField,
+    ) -> anyhow::Result<Vec<Entity>>;
+    async fn entities_by_observation_count(
+        &self,
+        limit: Option<usize>,
+        ascending: bool,
+    ) -> anyhow::Result<Vec<Entity>>;
+    async fn relation_exists(
+        &self,
+        from: String,
+        to: String,
+        relation_type: String,
+    ) -> anyhow::Result<bool>;
+    async fn related_entities(
+        &self,
+        name: String,
+        depth: usize,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<RelatedEntity>>;
+    async fn similar_entities(
+        &self,
+        name: String,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<SimilarEntity>>;
+    async fn export_ego_dot(&self, name: String, depth: usize) -> anyhow::Result<String>;
+    async fn relation_count_between(
+        &self,
+        from: String,
+        to: String,
+        directed: bool,
+    ) -> anyhow::Result<usize>;
+    async fn find_paths(
+        &self,
+        from: String,
+        to: String,
+        max_paths: usize,
+        max_length: usize,
+    ) -> anyhow::Result<Vec<GraphPath>>;
+    async fn adjacency(
+        &self,
+        relation_type: Option<String>,
+    ) -> anyhow::Result<HashMap<String, Vec<String>>>;
+    async fn term_frequency(&self, term: String) -> anyhow::Result<(usize, usize)>;
     async fn read_graph(&self) -> anyhow::Result<Box<KnowledgeGraph>>;
+    async fn fetch(
+        &self,
+        names: Vec<String>,
+        types: Vec<String>,
+    ) -> anyhow::Result<Box<KnowledgeGraph>>;
+    async fn raw_entity_line(&self, name: String) -> anyhow::Result<Option<String>>;
+    async fn add_relation_observations(
+        &self,
+        relation: Relation,
+    ) -> anyhow::Result<AddedRelationObservations>;
 
     async fn add_observations(
         &self,
-        observations: Vec<(String, Vec<String>)>,
-    ) -> anyhow::Result<Vec<(String, Vec<String>)>>;
-    async fn delete_entities(&self, entity_names: Vec<String>) -> anyhow::Result<()>;
+        observations: Vec<(String, Vec<Observation>)>,
+        order: ObservationOrder,
+        dedup_substrings: bool,
+    ) -> anyhow::Result<Vec<AddedObservations>>;
+    async fn sort_observations(
+        &self,
+        name: String,
+        sort: ObservationSort,
+    ) -> anyhow::Result<Entity>;
+    async fn delete_entities(
+        &self,
+        entity_names: Vec<String>,
+        dry_run: bool,
+        keep_relations: bool,
+    ) -> anyhow::Result<DeletionReport>;
     async fn delete_observations(
         &self,
         deletions: Vec<(String, Vec<String>)>,
-    ) -> anyhow::Result<()>;
-    async fn delete_relations(&self, relations: Vec<Relation>) -> anyhow::Result<()>;
-    async fn open_nodes(&self, names: Vec<String>) -> anyhow::Result<Box<KnowledgeGraph>>;
+        dry_run: bool,
+    ) -> anyhow::Result<DeletionReport>;
+    async fn delete_relations(
+        &self,
+        relations: Vec<Relation>,
+        dry_run: bool,
+    ) -> anyhow::Result<DeletionReport>;
+    async fn clear_graph(&self, dry_run: bool) -> anyhow::Result<DeletionReport>;
+    async fn clear_observations(&self, entity_name: String) -> anyhow::Result<usize>;
+    async fn prune_dangling_relations(&self) -> anyhow::Result<usize>;
+    async fn open_nodes(
+        &self,
+        names: Vec<String>,
+        fuzzy: Option<f32>,
+    ) -> anyhow::Result<OpenedNodes>;
+    async fn degrees(&self, names: &[String]) -> anyhow::Result<HashMap<String, Degree>>;
+    async fn describe_entity(&self, name: String) -> anyhow::Result<EntityDescription>;
+    async fn relations_subgraph(
+        &self,
+        relations: &[Relation],
+    ) -> anyhow::Result<Box<KnowledgeGraph>>;
+    async fn get_observations(
+        &self,
+        name: String,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> anyhow::Result<(Vec<Observation>, usize)>;
+    async fn search_observations(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<ObservationMatch>>;
+    async fn consolidation_preview(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+    ) -> anyhow::Result<ConsolidationPreview>;
+    async fn relations_matching(
+        &self,
+        entity_query: String,
+        relation_type: Option<String>,
+    ) -> anyhow::Result<Box<KnowledgeGraph>>;
+    async fn validate(&self) -> anyhow::Result<ValidationReport>;
+    async fn validate_entities(
+        &self,
+        entities: &[Entity],
+        policy: Option<DuplicatePolicy>,
+    ) -> anyhow::Result<Vec<EntityValidation>>;
+    async fn get_audit_log(&self, limit: Option<usize>) -> anyhow::Result<Vec<AuditEntry>>;
+    async fn export_jsonl(
+        &self,
+        filter: &ExportFilter,
+        chunk_size: usize,
+    ) -> anyhow::Result<Vec<String>>;
+    async fn export(
+        &self,
+        filter: &ExportFilter,
+        format: ExportFormat,
+        delimiter: &str,
+    ) -> anyhow::Result<String>;
+    async fn rename_entity_type(
+        &self,
+        from: String,
+        to: String,
+        case_insensitive: bool,
+    ) -> anyhow::Result<usize>;
+    async fn rename_relation_type(&self, from: String, to: String) -> anyhow::Result<usize>;
+    async fn replace_in_observations(
+        &self,
+        find: String,
+        replace: String,
+        entity_names: Option<Vec<String>>,
+    ) -> anyhow::Result<usize>;
+    async fn find_by_source(&self, source: String) -> anyhow::Result<Vec<Entity>>;
+    async fn search_by_tag(
+        &self,
+        key: String,
+        value: Option<String>,
+    ) -> anyhow::Result<Vec<Entity>>;
+    async fn entities_with_observation(
+        &self,
+        substring: String,
+        case_sensitive: bool,
+    ) -> anyhow::Result<Vec<Entity>>;
+    async fn entities_covering(&self, terms: Vec<String>) -> anyhow::Result<Vec<EntityCoverage>>;
+    async fn touch_entity(&self, name: String) -> anyhow::Result<Entity>;
+    async fn split_entity(
+        &self,
+        name: String,
+        new_name: String,
+        observations_to_move: Vec<String>,
+        entity_type: Option<String>,
+        relink: Vec<Relation>,
+    ) -> anyhow::Result<SplitEntity>;
+    async fn get_ontology(&self) -> anyhow::Result<Ontology>;
+    async fn set_ontology(&self, ontology: Ontology) -> anyhow::Result<()>;
+    async fn set_search_config(&self, update: SearchConfig) -> anyhow::Result<SearchConfig>;
+    async fn get_config(&self) -> anyhow::Result<EffectiveConfig>;
+    async fn flush(&self) -> anyhow::Result<()>;
+    async fn recover(&self) -> anyhow::Result<usize>;
 }
 
 #[derive(Clone)]
@@ -67,340 +282,2988 @@ impl KnowledgeGraphService {
             manager: Arc::new(KnowledgeGraphManager::with_path(path)),
         }
     }
+
+    /// Serve a graph seeded from a JSON/JSONL string that is never written to disk
+    pub fn in_memory(seed: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            manager: Arc::new(KnowledgeGraphManager::in_memory(seed)?),
+        })
+    }
+
+    /// Serve an in-memory copy of the configured memory file without ever writing back to it
+    pub async fn in_memory_snapshot() -> anyhow::Result<Self> {
+        Ok(Self {
+            manager: Arc::new(KnowledgeGraphManager::new().into_in_memory().await?),
+        })
+    }
 }
 
 #[async_trait::async_trait]
 impl GraphService for KnowledgeGraphService {
-    async fn create_entities(&self, entities: Vec<Entity>) -> anyhow::Result<Vec<Entity>> {
-        self.manager.create_entities(entities).await
+    async fn scoped(&self, file: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            manager: Arc::new(self.manager.scoped(file).await?),
+        })
+    }
+
+    async fn create_entities(
+        &self,
+        entities: Vec<Entity>,
+        on_duplicate: Option<DuplicatePolicy>,
+    ) -> anyhow::Result<CreateReport> {
+        self.manager.create_entities(entities, on_duplicate).await
     }
 
-    async fn create_relations(&self, relations: Vec<Relation>) -> anyhow::Result<Vec<Relation>> {
+    async fn create_relations(&self, relations: Vec<Relation>) -> anyhow::Result<CreatedRelations> {
         self.manager.create_relations(relations).await
     }
 
+    async fn connect_or_create(
+        &self,
+        from: Entity,
+        to: Entity,
+        relation_type: String,
+    ) -> anyhow::Result<ConnectReport> {
+        self.manager
+            .connect_or_create(from, to, relation_type)
+            .await
+    }
+
+    async fn upsert_entities(
+        &self,
+        entities: Vec<Entity>,
+        update_type: bool,
+    ) -> anyhow::Result<Vec<(String, UpsertStatus)>> {
+        self.manager.upsert_entities(entities, update_type).await
+    }
+
     async fn search_nodes(
         &self,
         query: &str,
         limit: Option<usize>,
-    ) -> anyhow::Result<Box<KnowledgeGraph>> {
-        self.manager.search_nodes(query, limit).await
+        options: SearchOptions,
+    ) -> anyhow::Result<SearchResults> {
+        self.manager.search_nodes(query, limit, &options).await
     }
 
     async fn get_stats(&self) -> anyhow::Result<(usize, usize)> {
         self.manager.get_stats().await
     }
 
-    async fn read_graph(&self) -> anyhow::Result<Box<KnowledgeGraph>> {
-        self.manager.read_graph().await
+    async fn get_detailed_stats(&self) -> anyhow::Result<DetailedStats> {
+        self.manager.get_detailed_stats().await
     }
 
-    async fn add_observations(
+    async fn graph_metrics(&self, exact_diameter: bool) -> anyhow::Result<GraphMetrics> {
+        self.manager.graph_metrics(exact_diameter).await
+    }
+
+    async fn core_concepts(
         &self,
-        observations: Vec<(String, Vec<String>)>,
-    ) -> anyhow::Result<Vec<(String, Vec<String>)>> {
-        self.manager.add_observations(observations).await
+        k: usize,
+        centrality: Centrality,
+    ) -> anyhow::Result<CoreConcepts> {
+        self.manager.core_concepts(k, centrality).await
     }
 
-    async fn delete_entities(&self, entity_names: Vec<String>) -> anyhow::Result<()> {
-        self.manager.delete_entities(entity_names).await
+    async fn overview(&self, limit: usize) -> anyhow::Result<GraphOverview> {
+        self.manager.overview(limit).await
     }
 
-    async fn delete_observations(
+    async fn modification_info(&self) -> anyhow::Result<ModificationInfo> {
+        self.manager.modification_info().await
+    }
+
+    async fn entities_changed_since(&self, since: i64) -> anyhow::Result<Vec<Entity>> {
+        self.manager.entities_changed_since(since).await
+    }
+
+    async fn entities_in_range(
         &self,
-        deletions: Vec<(String, Vec<String>)>,
-    ) -> anyhow::Result<()> {
-        self.manager.delete_observations(deletions).await
+        start: Option<i64>,
+        end: Option<i64>,
+        field: TimeField,
+    ) -> anyhow::Result<Vec<Entity>> {
+        self.manager.entities_in_range(start, end, field).await
     }
 
-    async fn delete_relations(&self, relations: Vec<Relation>) -> anyhow::Result<()> {
-        self.manager.delete_relations(relations).await
+    async fn entities_by_observation_count(
+        &self,
+        limit: Option<usize>,
+        ascending: bool,
+    ) -> anyhow::Result<Vec<Entity>> {
+        self.manager
+            .entities_by_observation_count(limit, ascending)
+            .await
     }
 
-    async fn open_nodes(&self, names: Vec<String>) -> anyhow::Result<Box<KnowledgeGraph>> {
-        self.manager.open_nodes(names).await
+    async fn relation_exists(
+        &self,
+        from: String,
+        to: String,
+        relation_type: String,
+    ) -> anyhow::Result<bool> {
+        self.manager.relation_exists(from, to, relation_type).await
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GraphStats {
-    pub entities: usize,
-    pub relations: usize,
-}
+    async fn related_entities(
+        &self,
+        name: String,
+        depth: usize,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<RelatedEntity>> {
+        self.manager.related_entities(name, depth, limit).await
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct CreateEntitiesRequest {
-    pub entities: Vec<Entity>,
-}
+    async fn export_ego_dot(&self, name: String, depth: usize) -> anyhow::Result<String> {
+        self.manager.export_ego_dot(name, depth).await
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct CreateRelationsRequest {
-    pub relations: Vec<Relation>,
-}
+    async fn similar_entities(
+        &self,
+        name: String,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<SimilarEntity>> {
+        self.manager.similar_entities(name, limit).await
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct SearchNodesRequest {
-    #[schemars(
-        description = "The search query to match against entity names, types, and observation content"
-    )]
-    pub query: String,
-    #[schemars(description = "Maximum number of results to return")]
-    pub limit: Option<usize>,
-}
+    async fn relation_count_between(
+        &self,
+        from: String,
+        to: String,
+        directed: bool,
+    ) -> anyhow::Result<usize> {
+        self.manager
+            .relation_count_between(from, to, directed)
+            .await
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct AddObservationsRequest {
-    pub observations: Vec<ObservationRequest>,
-}
+    async fn find_paths(
+        &self,
+        from: String,
+        to: String,
+        max_paths: usize,
+        max_length: usize,
+    ) -> anyhow::Result<Vec<GraphPath>> {
+        self.manager
+            .find_paths(from, to, max_paths, max_length)
+            .await
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct DeleteEntitiesRequest {
-    #[schemars(description = "An array of entity names to delete")]
-    pub entity_names: Vec<String>,
-}
+    async fn adjacency(
+        &self,
+        relation_type: Option<String>,
+    ) -> anyhow::Result<HashMap<String, Vec<String>>> {
+        self.manager.adjacency(relation_type).await
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct DeleteObservationsRequest {
-    pub deletions: Vec<ObservationDeletion>,
-}
+    async fn term_frequency(&self, term: String) -> anyhow::Result<(usize, usize)> {
+        self.manager.term_frequency(term).await
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct DeleteRelationsRequest {
-    #[schemars(description = "An array of relations to delete")]
-    pub relations: Vec<Relation>,
-}
+    async fn read_graph(&self) -> anyhow::Result<Box<KnowledgeGraph>> {
+        self.manager.read_graph().await
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct OpenNodesRequest {
-    #[schemars(description = "An array of entity names to retrieve")]
-    pub names: Vec<String>,
-}
+    async fn fetch(
+        &self,
+        names: Vec<String>,
+        types: Vec<String>,
+    ) -> anyhow::Result<Box<KnowledgeGraph>> {
+        self.manager.fetch(names, types).await
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct ObservationRequest {
-    #[serde(rename = "entityName")]
-    pub entity_name: String,
-    #[schemars(description = "An array of observation contents to add")]
-    pub contents: Vec<String>,
-}
+    async fn raw_entity_line(&self, name: String) -> anyhow::Result<Option<String>> {
+        self.manager.raw_entity_line(name).await
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct ObservationDeletion {
-    #[serde(rename = "entityName")]
-    pub entity_name: String,
-    #[schemars(description = "An array of observations to delete")]
-    pub observations: Vec<String>,
-}
+    async fn add_relation_observations(
+        &self,
+        relation: Relation,
+    ) -> anyhow::Result<AddedRelationObservations> {
+        self.manager.add_relation_observations(relation).await
+    }
 
-#[derive(Debug, Clone)]
-pub struct GraphServiceHandler<GS: GraphService> {
-    graph_service: Arc<GS>,
-    tool_router: ToolRouter<Self>,
-}
+    async fn add_observations(
+        &self,
+        observations: Vec<(String, Vec<Observation>)>,
+        order: ObservationOrder,
+        dedup_substrings: bool,
+    ) -> anyhow::Result<Vec<AddedObservations>> {
+        self.manager
+            .add_observations(observations, order, dedup_substrings)
+            .await
+    }
 
-#[tool_router]
-impl<GS: GraphService> GraphServiceHandler<GS> {
-    pub fn new(graph_service: GS) -> Self {
-        Self {
-            graph_service: Arc::new(graph_service),
-            tool_router: Self::tool_router(),
-        }
+    async fn sort_observations(
+        &self,
+        name: String,
+        sort: ObservationSort,
+    ) -> anyhow::Result<Entity> {
+        self.manager.sort_observations(name, sort).await
     }
 
-    #[tool(description = "Create new entities in the knowledge graph")]
-    pub async fn create_entities(
+    async fn delete_entities(
         &self,
-        Parameters(request): Parameters<CreateEntitiesRequest>,
-    ) -> Result<String, String> {
-        match self.graph_service.create_entities(request.entities).await {
-            Ok(created) => Ok(serde_json::to_string(&created).unwrap_or_else(|e| {
-                format!("Created entities but failed to serialize response: {e}")
-            })),
-            Err(e) => Err(e.to_string()),
-        }
+        entity_names: Vec<String>,
+        dry_run: bool,
+        keep_relations: bool,
+    ) -> anyhow::Result<DeletionReport> {
+        self.manager
+            .delete_entities(entity_names, dry_run, keep_relations)
+            .await
     }
 
-    #[tool(description = "Create new relations in the knowledge graph")]
-    pub async fn create_relations(
+    async fn delete_observations(
         &self,
-        Parameters(request): Parameters<CreateRelationsRequest>,
-    ) -> Result<String, String> {
-        match self.graph_service.create_relations(request.relations).await {
-            Ok(created) => Ok(serde_json::to_string(&created).unwrap_or_else(|e| {
-                format!("Created relations but failed to serialize response: {e}")
-            })),
-            Err(e) => Err(e.to_string()),
-        }
+        deletions: Vec<(String, Vec<String>)>,
+        dry_run: bool,
+    ) -> anyhow::Result<DeletionReport> {
+        self.manager.delete_observations(deletions, dry_run).await
     }
 
-    #[tool(description = "Search for nodes in the knowledge graph by text query")]
-    async fn search_nodes(
+    async fn delete_relations(
         &self,
-        Parameters(request): Parameters<SearchNodesRequest>,
-    ) -> Result<CallToolResult, McpError> {
-        let result = self
-            .graph_service
-            .search_nodes(&request.query, request.limit)
-            .await;
+        relations: Vec<Relation>,
+        dry_run: bool,
+    ) -> anyhow::Result<DeletionReport> {
+        self.manager.delete_relations(relations, dry_run).await
+    }
 
-        match result {
-            Ok(graph) => match serde_json::to_string(&*graph) {
-                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
-                Err(e) => Err(McpError::internal_error(
-                    format!("Failed to serialize graph: {e}"),
-                    None,
-                )),
-            },
-            Err(e) => Err(McpError::internal_error(
-                format!("Search failed: {e}"),
-                None,
-            )),
-        }
+    async fn clear_graph(&self, dry_run: bool) -> anyhow::Result<DeletionReport> {
+        self.manager.clear_graph(dry_run).await
     }
 
-    #[tool(description = "Get statistics about the knowledge graph")]
-    async fn get_stats(&self) -> Result<CallToolResult, McpError> {
-        match self.graph_service.get_stats().await {
-            Ok((entities, relations)) => {
-                let stats = GraphStats {
-                    entities,
-                    relations,
-                };
-                match serde_json::to_string(&stats) {
-                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
-                    Err(e) => Err(McpError::internal_error(
-                        format!("Failed to serialize stats: {e}"),
-                        None,
-                    )),
-                }
-            }
-            Err(e) => Err(McpError::internal_error(
-                format!("Failed to get stats: {e}"),
-                None,
-            )),
-        }
+    async fn clear_observations(&self, entity_name: String) -> anyhow::Result<usize> {
+        self.manager.clear_observations(entity_name).await
     }
 
-    #[tool(description = "Read the entire knowledge graph")]
-    async fn read_graph(&self) -> Result<CallToolResult, McpError> {
-        match self.graph_service.read_graph().await {
-            Ok(graph) => match serde_json::to_string(&*graph) {
-                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
-                Err(e) => Err(McpError::internal_error(
-                    format!("Failed to serialize graph: {e}"),
-                    None,
-                )),
-            },
-            Err(e) => Err(McpError::internal_error(
-                format!("Failed to read graph: {e}"),
-                None,
-            )),
-        }
+    async fn prune_dangling_relations(&self) -> anyhow::Result<usize> {
+        self.manager.prune_dangling_relations().await
     }
 
-    #[tool(description = "Add new observations to existing entities in the knowledge graph")]
-    async fn add_observations(
+    async fn open_nodes(
         &self,
-        Parameters(request): Parameters<AddObservationsRequest>,
-    ) -> Result<CallToolResult, McpError> {
-        let observations: Vec<(String, Vec<String>)> = request
-            .observations
-            .into_iter()
-            .map(|o| (o.entity_name, o.contents))
-            .collect();
+        names: Vec<String>,
+        fuzzy: Option<f32>,
+    ) -> anyhow::Result<OpenedNodes> {
+        self.manager.open_nodes(names, fuzzy).await
+    }
 
-        match self.graph_service.add_observations(observations).await {
-            Ok(results) => {
-                let formatted_results: Vec<ObservationRequest> = results
-                    .into_iter()
-                    .map(|(entity_name, contents)| ObservationRequest {
-                        entity_name,
-                        contents,
-                    })
-                    .collect();
+    async fn degrees(&self, names: &[String]) -> anyhow::Result<HashMap<String, Degree>> {
+        self.manager.degrees(names).await
+    }
 
-                match serde_json::to_string(&formatted_results) {
-                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
-                    Err(e) => Err(McpError::internal_error(
-                        format!("Failed to serialize observations: {e}"),
+    async fn describe_entity(&self, name: String) -> anyhow::Result<EntityDescription> {
+        self.manager.describe_entity(name).await
+    }
+
+    async fn relations_subgraph(
+        &self,
+        relations: &[Relation],
+    ) -> anyhow::Result<Box<KnowledgeGraph>> {
+        self.manager.relations_subgraph(relations).await
+    }
+
+    async fn get_observations(
+        &self,
+        name: String,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> anyhow::Result<(Vec<Observation>, usize)> {
+        self.manager.get_observations(name, offset, limit).await
+    }
+
+    async fn consolidation_preview(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+    ) -> anyhow::Result<ConsolidationPreview> {
+        self.manager.consolidation_preview(query, limit).await
+    }
+
+    async fn search_observations(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<ObservationMatch>> {
+        self.manager.search_observations(query, limit).await
+    }
+
+    async fn relations_matching(
+        &self,
+        entity_query: String,
+        relation_type: Option<String>,
+    ) -> anyhow::Result<Box<KnowledgeGraph>> {
+        self.manager
+            .relations_matching(entity_query, relation_type)
+            .await
+    }
+
+    async fn validate(&self) -> anyhow::Result<ValidationReport> {
+        self.manager.validate().await
+    }
+
+    async fn validate_entities(
+        &self,
+        entities: &[Entity],
+        policy: Option<DuplicatePolicy>,
+    ) -> anyhow::Result<Vec<EntityValidation>> {
+        self.manager.validate_entities(entities, policy).await
+    }
+
+    async fn get_audit_log(&self, limit: Option<usize>) -> anyhow::Result<Vec<AuditEntry>> {
+        self.manager.get_audit_log(limit).await
+    }
+
+    async fn export_jsonl(
+        &self,
+        filter: &ExportFilter,
+        chunk_size: usize,
+    ) -> anyhow::Result<Vec<String>> {
+        self.manager.export_jsonl_chunks(filter, chunk_size).await
+    }
+
+    async fn export(
+        &self,
+        filter: &ExportFilter,
+        format: ExportFormat,
+        delimiter: &str,
+    ) -> anyhow::Result<String> {
+        self.manager.export(filter, format, delimiter).await
+    }
+
+    async fn rename_entity_type(
+        &self,
+        from: String,
+        to: String,
+        case_insensitive: bool,
+    ) -> anyhow::Result<usize> {
+        self.manager
+            .rename_entity_type(from, to, case_insensitive)
+            .await
+    }
+
+    async fn rename_relation_type(&self, from: String, to: String) -> anyhow::Result<usize> {
+        self.manager.rename_relation_type(from, to).await
+    }
+
+    async fn replace_in_observations(
+        &self,
+        find: String,
+        replace: String,
+        entity_names: Option<Vec<String>>,
+    ) -> anyhow::Result<usize> {
+        self.manager
+            .replace_in_observations(find, replace, entity_names)
+            .await
+    }
+
+    async fn find_by_source(&self, source: String) -> anyhow::Result<Vec<Entity>> {
+        self.manager.find_by_source(source).await
+    }
+
+    async fn search_by_tag(
+        &self,
+        key: String,
+        value: Option<String>,
+    ) -> anyhow::Result<Vec<Entity>> {
+        self.manager.search_by_tag(key, value).await
+    }
+
+    async fn entities_with_observation(
+        &self,
+        substring: String,
+        case_sensitive: bool,
+    ) -> anyhow::Result<Vec<Entity>> {
+        self.manager
+            .entities_with_observation(substring, case_sensitive)
+            .await
+    }
+
+    async fn entities_covering(&self, terms: Vec<String>) -> anyhow::Result<Vec<EntityCoverage>> {
+        self.manager.entities_covering(terms).await
+    }
+
+    async fn touch_entity(&self, name: String) -> anyhow::Result<Entity> {
+        self.manager.touch_entity(name).await
+    }
+
+    async fn split_entity(
+        &self,
+        name: String,
+        new_name: String,
+        observations_to_move: Vec<String>,
+        entity_type: Option<String>,
+        relink: Vec<Relation>,
+    ) -> anyhow::Result<SplitEntity> {
+        self.manager
+            .split_entity(name, new_name, observations_to_move, entity_type, relink)
+            .await
+    }
+
+    async fn get_ontology(&self) -> anyhow::Result<Ontology> {
+        self.manager.get_ontology().await
+    }
+
+    async fn set_ontology(&self, ontology: Ontology) -> anyhow::Result<()> {
+        self.manager.set_ontology(ontology).await
+    }
+
+    async fn set_search_config(&self, update: SearchConfig) -> anyhow::Result<SearchConfig> {
+        self.manager.set_search_config(update).await
+    }
+
+    async fn get_config(&self) -> anyhow::Result<EffectiveConfig> {
+        self.manager.get_config().await
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.manager.flush().await
+    }
+
+    async fn recover(&self) -> anyhow::Result<usize> {
+        self.manager.recover().await
+    }
+}
+
+/// Server instructions advertised through `get_info`, summarising each tool with an example call
+const SERVER_INSTRUCTIONS: &str = r#"Knowledge graph memory service. Entities have a unique name, an entityType and a list of observations (facts); relations connect two entity names with a relationType in active voice.
+
+Writing:
+- create_entities: add new entities; names matching existing entities are reported as collisions and skipped, or merged with "on_duplicate": "merge". {"entities": [{"name": "Alice", "entityType": "person", "observations": ["Works at Acme"]}]}
+- validate_entities: dry run of create_entities with the same input, listing per entity the errors to fix and likely duplicates before writing.
+- upsert_entities: create or merge entities. {"entities": [{"name": "Alice", "entityType": "person", "observations": ["Likes tea"]}], "update_type": false}
+- create_relations: link entities, optionally with notes on the link and returning the resulting neighborhood. {"relations": [{"from": "Alice", "to": "Acme", "relationType": "works_at", "observations": ["Since 2019"]}], "return_subgraph": true}
+- connect_or_create: create whichever of two entities is missing and link them, in one call. {"from": {"name": "Alice", "entityType": "person", "observations": []}, "to": {"name": "Acme", "entityType": "company", "observations": []}, "relation_type": "works_at"}
+- add_observations: append facts to existing entities, or put them first with "order": "prepend". "dedup_substrings": true also drops facts already contained in existing ones and replaces shorter ones the new fact contains. {"observations": [{"entityName": "Alice", "contents": ["Moved to Berlin"], "source": "chat", "tags": {"confidence": "high"}}], "order": "append"}
+- add_relation_observations: add notes to an existing relation; search_nodes matches them with "search_relation_observations": true. {"from": "Alice", "to": "Bob", "relationType": "worked_with", "observations": ["2019-2021"]}
+- sort_observations: reorder an entity's facts. {"name": "Alice", "by": "alphabetical"} or "by": "reverse"
+- delete_entities: remove entities and their relations, or keep the relations dangling to repoint them later. {"entity_names": ["Alice"], "keep_relations": false}
+- delete_observations: remove facts by content. {"deletions": [{"entityName": "Alice", "observations": ["Likes tea"]}]}
+- clear_observations: remove all of an entity's facts before re-describing it. {"entityName": "Alice"}
+- prune_dangling_relations: clean up relations pointing at entities that no longer exist, e.g. after a bad rename or a hand edit. {}
+- delete_relations: remove exact relations. {"relations": [{"from": "Alice", "to": "Acme", "relationType": "works_at"}]}
+- clear_graph: remove everything. {"dry_run": true}
+  All delete tools and clear_graph accept "dry_run": true to preview what would be removed without changing anything.
+  Batches over the server's limit (1000 items by default) are rejected with the limit in the error; split them into smaller calls.
+- touch_entity: mark an entity as still relevant without changing it. {"name": "Alice"}
+- split_entity: move some facts of an overloaded entity into a new one, optionally moving relations along. {"name": "Alice", "new_name": "Alice (work)", "observations": ["Works at Acme"], "relink": [{"from": "Alice", "to": "Acme", "relationType": "works_at"}]}
+- rename_entity_type: {"from": "person", "to": "Person", "case_insensitive": true}
+- rename_relation_type: {"from": "works_at", "to": "employed_by"}
+- replace_in_observations: correct a misspelling across facts; duplicates it creates are merged. {"find": "Widgit", "replace": "Widget", "entity_names": ["Acme"]}
+- set_search_config: change and persist search ranking settings; omitted fields are kept, and {} returns the current settings. {"name_weight": 3.0, "search_types": false}
+- set_schema: define the allowed types; with strict, other types are rejected. Symmetric relation types are stored one way only; single-valued ones allow one relation per source. {"entity_types": ["person", "organization"], "relation_types": ["works_at", "sibling_of"], "strict": true, "symmetric_relation_types": ["sibling_of"], "single_valued_relation_types": ["reports_to"]}
+
+Reading:
+- search_nodes: ranked entity search; name_boost, type_boost and observation_boost reweight fields for one query. {"query": "berlin", "limit": 5, "ranker": "bm25", "name_boost": 4.0}; add "format": "names_only" or "summary" for compact results, and "search_types": false to ignore entity types. Quote the query for an exact phrase: {"query": "\"project alpha\""}
+- relations_matching: relations of one type touching every entity matching a query, with the entities at both ends, e.g. who the engineers report to. {"entity_query": "engineer", "relation_type": "reports_to"}
+- search_observations: ranked individual facts. {"query": "berlin", "limit": 10}
+- consolidation_preview: propose merging near-duplicate entities matching a query (canonical name, combined facts and relations) without applying it; review, then apply with create/delete tools. {"query": "acme", "limit": 5}
+- open_nodes: fetch entities by name, optionally with each one's relation counts; "fuzzy" tolerates typos and reports what each name resolved to. {"names": ["Alice"], "include_degree": true, "fuzzy": 0.8}
+- describe_entity: everything about one entity in one call, its facts plus relations grouped by type and its neighbors. {"name": "Alice"}
+- fetch: a slice of the graph in one call, the named entities plus every entity of the given types, with the relations among them. {"names": ["Alice", "Bob"], "types": ["Project"]}
+- get_observations: page through one entity's facts. {"name": "Alice", "offset": 0, "limit": 20}
+- find_by_source: facts recorded from a source. {"source": "chat"}
+- search_by_tag: facts tagged with a key, optionally with a given value. {"key": "topic", "value": "finance"}
+- entities_with_observation: every entity with a fact containing some text, unranked and unlimited. {"substring": "Berlin", "case_sensitive": false}
+- entities_covering: find the entity that knows about several things at once; entities whose facts mention the most terms come first, each with the terms it covers. {"terms": ["rust", "python", "sql"]}
+- entities_changed_since: entities changed at or after a Unix timestamp, e.g. the last_modified from a previous get_stats. {"since": 1700000000}
+- entities_in_range: entities created (or updated, with field "updated_at") within an inclusive time window, e.g. yesterday's. {"start": 1700000000, "end": 1700086399, "field": "created_at"}
+- entities_by_observation_count: richest entities first, or empty stubs first with ascending. {"limit": 10, "ascending": true}
+- term_frequency: how prominent a concept is, without fetching entities. {"term": "berlin", "include_occurrences": true}
+- adjacency: entity -> outgoing neighbor names, cheaper than the full relation list. {"relation_type": "knows"}
+- related_entities: entities near another one as a flat list, closest and best connected first, each with its distance and degree. {"name": "Alice", "depth": 2, "limit": 10}
+- similar_entities: entities whose facts use the same words as another one's, to find links nobody recorded. {"name": "Alice", "limit": 5}
+- relation_exists: whether an exact relation is already stored, since create_relations silently skips duplicates. {"from": "Alice", "to": "Acme", "relationType": "works_at"}
+- relation_count_between: how many relations connect two entities. {"from": "Alice", "to": "Acme", "directed": false}
+- find_paths: several ways two entities are connected, shortest first, each as entities and the relations between them; [] when unconnected. {"from": "Alice", "to": "Acme", "max_paths": 5, "max_length": 4}
+- get_audit_log: recent changes, newest first, to see what changed memory and when. {"limit": 20}
+- overview: start here in an existing memory; counts, most connected and most recently updated entities, entities per type and orphans. {"limit": 5}
+- get_config: the settings in effect, to check that an environment variable was applied; takes no arguments.
+- core_concepts: a compact map of the memory, its most central entities and how they interconnect, instead of the full graph. {"k": 10, "centrality": "degree"}
+- graph_metrics: density, clustering, connected components and diameter, to tell whether memory is fragmenting. {"exact_diameter": false}
+- get_stats, get_detailed_stats, validate, schema, get_schema: take no arguments.
+- read_graph: the whole graph. {}
+  read_graph, search_nodes and open_nodes accept "file" to read an archived memory file in the snapshot directory instead, for that call only. {"file": "memory-2024-01-01.jsonl"}
+- export_jsonl: the graph as JSONL chunks; types, names and query export just that slice. {"chunk_size": 500, "types": ["Project"]}
+- export: the graph or a slice of it as json, jsonl, dot or csv. {"format": "dot", "names": ["Alice"], "query": "berlin"}
+- export_ego_dot: one entity's neighborhood as Graphviz DOT, with that entity highlighted. {"name": "Alice", "depth": 2}
+- raw_entity_line: the exact memory file line of one entity, for debugging. {"name": "Alice"}"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphStats {
+    pub entities: usize,
+    pub relations: usize,
+    pub version: u64,
+    pub last_modified: Option<i64>,
+}
+
+/// Result of `get_config`: the graph settings and the server's own
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigResult {
+    #[serde(flatten)]
+    pub graph: EffectiveConfig,
+    pub max_batch_size: usize,
+    pub pretty_json: bool,
+    /// Tools left out by `MEMORY_ENABLED_TOOLS`
+    pub disabled_tools: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateRelationsResult {
+    pub created: Vec<Relation>,
+    /// Relations of single-valued types removed to make room for the created ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replaced: Vec<Relation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subgraph: Option<KnowledgeGraph>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationExistsResult {
+    pub exists: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationCountResult {
+    pub from: String,
+    pub to: String,
+    pub directed: bool,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TermFrequencyResult {
+    pub term: String,
+    pub entities: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occurrences: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpsertResult {
+    pub name: String,
+    pub status: UpsertStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteEntitiesResult {
+    pub entities_deleted: usize,
+    pub relations_deleted: usize,
+    pub dry_run: bool,
+    pub entities: Vec<String>,
+    pub relations: Vec<Relation>,
+    /// Relations kept with `keep_relations` that now point at a deleted entity
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dangling_relations: Vec<Relation>,
+}
+
+impl DeleteEntitiesResult {
+    fn new(report: DeletionReport, dry_run: bool) -> Self {
+        Self {
+            entities_deleted: report.entities.len(),
+            relations_deleted: report.relations.len(),
+            dry_run,
+            entities: report.entities,
+            relations: report.relations,
+            dangling_relations: report.dangling_relations,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteObservationsResult {
+    pub observations_deleted: usize,
+    pub dry_run: bool,
+    pub observations: Vec<ObservationDeletion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClearObservationsResult {
+    pub observations_deleted: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PruneDanglingRelationsResult {
+    pub relations_deleted: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteRelationsResult {
+    pub relations_deleted: usize,
+    pub dry_run: bool,
+    pub relations: Vec<Relation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EntityWithDegree {
+    #[serde(flatten)]
+    pub entity: Entity,
+    pub degree: Degree,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenedNodesWithDegree {
+    pub entities: Vec<EntityWithDegree>,
+    pub relations: Vec<Relation>,
+    pub not_found: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fuzzy_matches: Vec<FuzzyMatch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawEntityLineResult {
+    pub name: String,
+    /// The stored JSONL line, or null when no such entity exists
+    pub line: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenameResult {
+    pub changed: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObservationPage {
+    pub name: String,
+    pub observations: Vec<Observation>,
+    pub offset: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CreateEntitiesRequest {
+    pub entities: Vec<Entity>,
+    #[schemars(
+        description = "For entities whose name matches an existing one under the server's name policy: 'skip' them or 'merge' their observations into the existing entity (defaults to the server setting)"
+    )]
+    pub on_duplicate: Option<DuplicatePolicy>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConnectOrCreateRequest {
+    #[schemars(description = "The source entity, created as given if it doesn't exist")]
+    pub from: Entity,
+    #[schemars(description = "The target entity, created as given if it doesn't exist")]
+    pub to: Entity,
+    #[schemars(description = "The type of the relation from 'from' to 'to'")]
+    pub relation_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UpsertEntitiesRequest {
+    pub entities: Vec<Entity>,
+    #[schemars(
+        description = "Replace the type of existing entities with the given type (default false)"
+    )]
+    pub update_type: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CreateRelationsRequest {
+    pub relations: Vec<Relation>,
+    #[schemars(
+        description = "Also return the endpoints of the created relations and every edge among them (default false)"
+    )]
+    pub return_subgraph: Option<bool>,
+}
+
+/// How much of each entity `search_nodes` returns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultFormat {
+    /// Entities with all their observations
+    #[default]
+    Full,
+    /// Only entity names and types
+    NamesOnly,
+    /// Each entity's first matching observation, cut to a short snippet
+    Summary,
+}
+
+/// Maximum characters of an observation kept in `ResultFormat::Summary`
+const SUMMARY_SNIPPET_CHARS: usize = 160;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityName {
+    pub name: String,
+    #[serde(rename = "entityType")]
+    pub entity_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamesOnlyResult {
+    pub entities: Vec<EntityName>,
+    pub relations: Vec<Relation>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub near_duplicates_suppressed: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SearchNodesRequest {
+    #[schemars(
+        description = "The search query to match against entity names, types, and observation content"
+    )]
+    pub query: String,
+    #[schemars(description = "Maximum number of results to return")]
+    pub limit: Option<usize>,
+    #[schemars(
+        description = "Ranking algorithm: 'weighted' (default) or 'bm25', which favours rare query terms"
+    )]
+    pub ranker: Option<RankerKind>,
+    #[schemars(
+        description = "Also return neighbors linked to several matching entities, with the relations among them"
+    )]
+    pub include_related_entities: Option<bool>,
+    #[schemars(
+        description = "Number of links to matching entities a neighbor needs to be included (default 2)"
+    )]
+    pub related_entity_threshold: Option<usize>,
+    #[schemars(
+        description = "Favour recently updated entities: scores halve for every this many days since the last update"
+    )]
+    pub decay_half_life_days: Option<f64>,
+    #[schemars(
+        description = "Only return entities with at least one observation from this source"
+    )]
+    pub source: Option<String>,
+    #[schemars(description = "Weight for name matches in this query (default 2.0)")]
+    pub name_boost: Option<f32>,
+    #[schemars(description = "Weight for entity type matches in this query (default 1.5)")]
+    pub type_boost: Option<f32>,
+    #[schemars(description = "Weight for observation matches in this query (default 1.0)")]
+    pub observation_boost: Option<f32>,
+    #[schemars(
+        description = "Whether entity types can match the query; false stops a query like 'note' from matching every entity typed 'note' (defaults to the server setting)"
+    )]
+    pub search_types: Option<bool>,
+    #[schemars(
+        description = "Also match the observations recorded on an entity's relations, reported as 'relation' in matchedFields (default false)"
+    )]
+    pub search_relation_observations: Option<bool>,
+    #[schemars(
+        description = "Result detail: 'full' (default), 'names_only' for names and types, or 'summary' for one matching snippet per entity"
+    )]
+    pub format: Option<ResultFormat>,
+    #[schemars(
+        description = "Read this memory file instead of the primary one for this call only, e.g. an archived snapshot; relative to, and restricted to, the snapshot directory"
+    )]
+    pub file: Option<String>,
+    #[schemars(
+        description = "Indent the JSON output (default compact, or the server's MEMORY_PRETTY_JSON setting)"
+    )]
+    pub pretty: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RelationsMatchingRequest {
+    #[schemars(description = "Search query selecting the entities whose relations to return")]
+    pub entity_query: String,
+    #[schemars(description = "Only return relations of this type (default all types)")]
+    pub relation_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConsolidationPreviewRequest {
+    #[schemars(description = "The search query selecting entities to consolidate")]
+    pub query: String,
+    #[schemars(description = "Maximum number of entities to consider (default 10)")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SearchObservationsRequest {
+    #[schemars(description = "The search query to match against individual observations")]
+    pub query: String,
+    #[schemars(description = "Maximum number of observations to return (default 10)")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AddObservationsRequest {
+    pub observations: Vec<ObservationRequest>,
+    #[schemars(
+        description = "Where new observations go: 'append' (default) after existing ones, or 'prepend' to keep the most recent first"
+    )]
+    pub order: Option<ObservationOrder>,
+    #[schemars(
+        description = "Also skip observations already contained in an existing one, and replace existing ones a new observation contains, ignoring case (default false)"
+    )]
+    pub dedup_substrings: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SortObservationsRequest {
+    #[schemars(description = "The entity whose observations to reorder")]
+    pub name: String,
+    #[schemars(
+        description = "'alphabetical' (default) sorts by content; 'reverse' flips the stored order"
+    )]
+    pub by: Option<ObservationSort>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DeleteEntitiesRequest {
+    #[schemars(description = "An array of entity names to delete")]
+    pub entity_names: Vec<String>,
+    #[schemars(description = "Report what would be removed without changing the graph")]
+    pub dry_run: Option<bool>,
+    #[schemars(
+        description = "Keep the relations of the deleted entities, left dangling to be repointed later, and list them in dangling_relations (default false deletes them)"
+    )]
+    pub keep_relations: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DeleteObservationsRequest {
+    pub deletions: Vec<ObservationDeletion>,
+    #[schemars(description = "Report what would be removed without changing the graph")]
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DeleteRelationsRequest {
+    #[schemars(description = "An array of relations to delete")]
+    pub relations: Vec<Relation>,
+    #[schemars(description = "Report what would be removed without changing the graph")]
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ClearObservationsRequest {
+    #[serde(rename = "entityName")]
+    #[schemars(description = "The name of the entity whose observations should all be removed")]
+    pub entity_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ClearGraphRequest {
+    #[schemars(description = "Report what would be removed without changing the graph")]
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OpenNodesRequest {
+    #[schemars(description = "An array of entity names to retrieve")]
+    pub names: Vec<String>,
+    #[schemars(
+        description = "Add each entity's incoming and outgoing relation counts as 'degree' (default false)"
+    )]
+    pub include_degree: Option<bool>,
+    #[schemars(
+        description = "Similarity threshold between 0 and 1; names matching no entity resolve to the closest entity name at least this similar, reported in fuzzy_matches (default exact matching only)"
+    )]
+    pub fuzzy: Option<f32>,
+    #[schemars(
+        description = "Read this memory file instead of the primary one for this call only, e.g. an archived snapshot; relative to, and restricted to, the snapshot directory"
+    )]
+    pub file: Option<String>,
+    #[schemars(
+        description = "Indent the JSON output (default compact, or the server's MEMORY_PRETTY_JSON setting)"
+    )]
+    pub pretty: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ReadGraphRequest {
+    #[schemars(
+        description = "Read this memory file instead of the primary one for this call only, e.g. an archived snapshot; relative to, and restricted to, the snapshot directory"
+    )]
+    pub file: Option<String>,
+    #[schemars(
+        description = "Indent the JSON output (default compact, or the server's MEMORY_PRETTY_JSON setting)"
+    )]
+    pub pretty: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DescribeEntityRequest {
+    #[schemars(description = "The name of the entity to describe")]
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FetchRequest {
+    #[schemars(description = "Entity names to include")]
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[schemars(description = "Entity types whose entities are all included")]
+    #[serde(default)]
+    pub types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GetObservationsRequest {
+    #[schemars(description = "The name of the entity whose observations to retrieve")]
+    pub name: String,
+    #[schemars(description = "Number of observations to skip (defaults to 0)")]
+    pub offset: Option<usize>,
+    #[schemars(description = "Maximum number of observations to return (defaults to all)")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RawEntityLineRequest {
+    #[schemars(description = "The name of the entity")]
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExportJsonlRequest {
+    #[schemars(description = "Maximum number of JSONL lines per content item (default 500)")]
+    pub chunk_size: Option<usize>,
+    #[serde(flatten)]
+    pub filter: ExportFilter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExportRequest {
+    #[schemars(
+        description = "Output format: 'json' (read_graph shape, default), 'jsonl' (memory file), 'dot' (Graphviz) or 'csv'"
+    )]
+    pub format: Option<ExportFormat>,
+    #[schemars(
+        description = "Separator between an entity's observations in CSV (default ' | '); occurrences inside an observation are escaped with a backslash"
+    )]
+    pub observation_delimiter: Option<String>,
+    #[serde(flatten)]
+    pub filter: ExportFilter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SplitEntityRequest {
+    #[schemars(description = "The entity to split")]
+    pub name: String,
+    #[schemars(description = "Name of the new entity; must not exist yet")]
+    pub new_name: String,
+    #[schemars(description = "Observation contents to move to the new entity")]
+    pub observations: Vec<String>,
+    #[schemars(description = "Type of the new entity (defaults to the original's type)")]
+    pub entity_type: Option<String>,
+    #[schemars(
+        description = "Existing relations of the original entity to attach to the new entity instead"
+    )]
+    pub relink: Option<Vec<Relation>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RenameEntityTypeRequest {
+    #[schemars(description = "The entity type to replace")]
+    pub from: String,
+    #[schemars(description = "The new entity type")]
+    pub to: String,
+    #[schemars(description = "Match the 'from' type ignoring case (default false)")]
+    pub case_insensitive: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ReplaceInObservationsRequest {
+    #[schemars(description = "The text to find, matched literally and case-sensitively")]
+    pub find: String,
+    #[schemars(description = "The text to put in its place")]
+    pub replace: String,
+    #[schemars(description = "Only change the observations of these entities (default all)")]
+    pub entity_names: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RenameRelationTypeRequest {
+    #[schemars(description = "The relation type to replace")]
+    pub from: String,
+    #[schemars(description = "The new relation type")]
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ObservationRequest {
+    #[serde(rename = "entityName")]
+    pub entity_name: String,
+    #[schemars(description = "An array of observation contents to add")]
+    pub contents: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(
+        description = "Where these observations came from, e.g. a document or conversation id"
+    )]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(
+        description = "Key-value metadata attached to each of these observations, e.g. {\"confidence\": \"high\", \"topic\": \"finance\"}"
+    )]
+    pub tags: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddObservationsResult {
+    #[serde(rename = "entityName")]
+    pub entity_name: String,
+    pub contents: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    pub already_present: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dropped: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub replaced: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FindBySourceRequest {
+    #[schemars(description = "The observation source to look up")]
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SearchByTagRequest {
+    #[schemars(description = "The tag key to look up, e.g. 'topic'")]
+    pub key: String,
+    #[schemars(
+        description = "Only match observations with this value for the tag (default any value)"
+    )]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EntitiesWithObservationRequest {
+    #[schemars(description = "Text an observation must contain")]
+    pub substring: String,
+    #[schemars(description = "Match case exactly (default false)")]
+    pub case_sensitive: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EntitiesCoveringRequest {
+    #[schemars(
+        description = "Words or phrases to look for in observations, ignoring case; entities mentioning all of them rank first"
+    )]
+    pub terms: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TermFrequencyRequest {
+    #[schemars(description = "The text to look for in observations, ignoring case")]
+    pub term: String,
+    #[schemars(
+        description = "Also report the total number of occurrences across all observations (default false)"
+    )]
+    pub include_occurrences: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AdjacencyRequest {
+    #[schemars(description = "Only follow relations of this type (default: all types)")]
+    pub relation_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RelatedEntitiesRequest {
+    #[schemars(description = "The entity to start from")]
+    pub name: String,
+    #[schemars(description = "Maximum number of relation hops to follow (default 2)")]
+    pub depth: Option<usize>,
+    #[schemars(description = "Maximum number of entities to return (default 10)")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExportEgoDotRequest {
+    #[schemars(description = "The entity at the center of the drawing")]
+    pub name: String,
+    #[schemars(description = "Maximum number of relation hops to follow (default 1)")]
+    pub depth: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SimilarEntitiesRequest {
+    #[schemars(description = "The entity to compare the others with")]
+    pub name: String,
+    #[schemars(description = "Maximum number of entities to return (default 10)")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RelationCountRequest {
+    #[schemars(description = "The name of the first entity")]
+    pub from: String,
+    #[schemars(description = "The name of the second entity")]
+    pub to: String,
+    #[schemars(
+        description = "Only count relations pointing from `from` to `to` (default false counts both directions)"
+    )]
+    pub directed: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FindPathsRequest {
+    #[schemars(description = "The entity the paths start from")]
+    pub from: String,
+    #[schemars(description = "The entity the paths lead to")]
+    pub to: String,
+    #[schemars(description = "Maximum number of paths to return (default 5, at most 100)")]
+    pub max_paths: Option<usize>,
+    #[schemars(description = "Maximum number of relations in a path (default 4, at most 8)")]
+    pub max_length: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AuditLogRequest {
+    #[schemars(description = "Maximum number of entries to return, newest first (default 50)")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OverviewRequest {
+    #[schemars(description = "Maximum number of entities in each ranking (default 5)")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ObservationCountRankingRequest {
+    #[schemars(description = "Maximum number of entities to return (default all)")]
+    pub limit: Option<usize>,
+    #[schemars(
+        description = "List the entities with the fewest observations first, surfacing empty stubs (default false)"
+    )]
+    pub ascending: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChangedSinceRequest {
+    #[schemars(
+        description = "Unix timestamp (seconds); entities created or updated at or after it are returned"
+    )]
+    pub since: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GraphMetricsRequest {
+    #[schemars(
+        description = "Compute the diameter exactly even on large graphs, which can be slow (default false estimates it above 1000 entities)"
+    )]
+    pub exact_diameter: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CoreConceptsRequest {
+    #[schemars(description = "Number of entities to return (default 10)")]
+    pub k: Option<usize>,
+    #[schemars(
+        description = "How to rank entities: 'degree' (default, number of relations) or 'pagerank' (pointed at by other important entities)"
+    )]
+    pub centrality: Option<Centrality>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EntitiesInRangeRequest {
+    #[schemars(
+        description = "Earliest Unix timestamp (seconds) included (default: no lower bound)"
+    )]
+    pub start: Option<i64>,
+    #[schemars(description = "Latest Unix timestamp (seconds) included (default: no upper bound)")]
+    pub end: Option<i64>,
+    #[schemars(
+        description = "Timestamp to compare: 'created_at' (default) or 'updated_at'. Entities without it are left out"
+    )]
+    pub field: Option<TimeField>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TouchEntityRequest {
+    #[schemars(description = "The name of the entity to mark as recently updated")]
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ObservationDeletion {
+    #[serde(rename = "entityName")]
+    pub entity_name: String,
+    #[schemars(description = "An array of observations to delete")]
+    pub observations: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphServiceHandler<GS: GraphService> {
+    graph_service: Arc<GS>,
+    tool_router: ToolRouter<Self>,
+    /// Tools removed from `tool_router`, sorted by name
+    disabled_tools: Vec<String>,
+    /// Most entities, relations, observations or names accepted in one call
+    max_batch_size: usize,
+    /// Indent tool output unless a request asks otherwise
+    pretty_json: bool,
+}
+
+/// Batch limit used when `MEMORY_MAX_BATCH_SIZE` is not set
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 1000;
+
+/// Tools that add to or change the graph or its settings (the `write` group)
+const WRITE_TOOLS: &[&str] = &[
+    "create_entities",
+    "create_relations",
+    "connect_or_create",
+    "upsert_entities",
+    "add_observations",
+    "add_relation_observations",
+    "sort_observations",
+    "touch_entity",
+    "split_entity",
+    "rename_entity_type",
+    "rename_relation_type",
+    "replace_in_observations",
+    "set_schema",
+    "set_search_config",
+];
+
+/// Tools that remove data (the `delete` group)
+const DELETE_TOOLS: &[&str] = &[
+    "delete_entities",
+    "delete_relations",
+    "delete_observations",
+    "clear_observations",
+    "prune_dangling_relations",
+    "clear_graph",
+];
+
+/// Tools that look things up by text (the `search` group)
+const SEARCH_TOOLS: &[&str] = &[
+    "search_nodes",
+    "search_observations",
+    "relations_matching",
+    "consolidation_preview",
+    "entities_with_observation",
+    "entities_covering",
+    "find_by_source",
+    "search_by_tag",
+    "term_frequency",
+];
+
+/// Whether `tool` is selected by any of `entries`: a tool name, or one of the groups
+/// `all`, `read` (every tool that doesn't change the graph), `search`, `write` or `delete`
+fn tool_enabled(tool: &str, entries: &[String]) -> bool {
+    entries.iter().any(|entry| match entry.as_str() {
+        "all" => true,
+        "read" => !WRITE_TOOLS.contains(&tool) && !DELETE_TOOLS.contains(&tool),
+        "search" => SEARCH_TOOLS.contains(&tool),
+        "write" => WRITE_TOOLS.contains(&tool),
+        "delete" => DELETE_TOOLS.contains(&tool),
+        name => name == tool,
+    })
+}
+
+#[tool_router]
+impl<GS: GraphService> GraphServiceHandler<GS> {
+    pub fn new(graph_service: GS) -> Self {
+        let max_batch_size = std::env::var("MEMORY_MAX_BATCH_SIZE")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|size| *size > 0)
+            .unwrap_or(DEFAULT_MAX_BATCH_SIZE);
+        let pretty_json = std::env::var("MEMORY_PRETTY_JSON")
+            .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+
+        let handler = Self {
+            graph_service: Arc::new(graph_service),
+            tool_router: Self::tool_router(),
+            disabled_tools: Vec::new(),
+            max_batch_size,
+            pretty_json,
+        };
+        match std::env::var("MEMORY_ENABLED_TOOLS") {
+            Ok(tools) if !tools.trim().is_empty() => {
+                let entries: Vec<String> = tools.split(',').map(|t| t.trim().to_string()).collect();
+                handler.with_enabled_tools(&entries)
+            }
+            _ => handler,
+        }
+    }
+
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size;
+        self
+    }
+
+    pub fn with_pretty_json(mut self, pretty_json: bool) -> Self {
+        self.pretty_json = pretty_json;
+        self
+    }
+
+    /// Serialize a tool result, indented when the request's `pretty` flag
+    /// (or, without one, the server-wide setting) asks for it
+    fn to_json<T: Serialize + ?Sized>(
+        &self,
+        value: &T,
+        pretty: Option<bool>,
+    ) -> serde_json::Result<String> {
+        if pretty.unwrap_or(self.pretty_json) {
+            serde_json::to_string_pretty(value)
+        } else {
+            serde_json::to_string(value)
+        }
+    }
+
+    /// Register only the tools selected by `entries` (tool names or groups, see `tool_enabled`);
+    /// the others are neither listed nor callable
+    pub fn with_enabled_tools(mut self, entries: &[String]) -> Self {
+        self.tool_router = Self::tool_router();
+        self.disabled_tools = self
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(|tool| tool.name.to_string())
+            .filter(|name| !tool_enabled(name, entries))
+            .collect();
+        self.disabled_tools.sort();
+        for name in &self.disabled_tools {
+            self.tool_router.remove_route::<(), ()>(name);
+        }
+        self
+    }
+
+    /// Reject calls to tools this server doesn't offer, naming the tool
+    fn check_tool_available(&self, name: &str) -> Result<(), McpError> {
+        if self.tool_router.has_route(name) {
+            return Ok(());
+        }
+        let message = if self.disabled_tools.iter().any(|t| t == name) {
+            format!("Tool '{name}' is not available: it is disabled on this server")
+        } else {
+            format!("Tool '{name}' is not available: no such tool")
+        };
+        Err(McpError::invalid_params(
+            message,
+            Some(serde_json::json!({ "tool": name })),
+        ))
+    }
+
+    /// Reject a batch over the size limit before touching the graph,
+    /// telling the client how far to chunk it
+    fn check_batch_size(&self, items: &str, received: usize) -> Result<(), McpError> {
+        if received <= self.max_batch_size {
+            return Ok(());
+        }
+        Err(McpError::invalid_params(
+            format!(
+                "Too many {items} in one call: received {received}, the limit is {}. Split the request into smaller batches",
+                self.max_batch_size
+            ),
+            Some(serde_json::json!({
+                "items": items,
+                "limit": self.max_batch_size,
+                "received": received,
+            })),
+        ))
+    }
+
+    /// The service reads go to: the primary graph, or the alternate memory file `file`
+    async fn reader(&self, file: Option<String>) -> Result<Arc<GS>, McpError> {
+        let Some(file) = file else {
+            return Ok(Arc::clone(&self.graph_service));
+        };
+        match self.graph_service.scoped(&file).await {
+            Ok(service) => Ok(Arc::new(service)),
+            Err(e) => Err(McpError::invalid_params(
+                format!("Cannot read memory file: {e}"),
+                Some(serde_json::json!({ "file": file })),
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Create new entities in the knowledge graph. Entities whose name matches an existing one, ignoring case and the server's name policy, are skipped or, with on_duplicate 'merge', merged. Returns {\"created\": [...], \"collisions\": [...]}. Input: {\"entities\": [{\"name\": string, \"entityType\": string, \"observations\": [string]}], \"on_duplicate\": \"skip\" | \"merge\"?}"
+    )]
+    pub async fn create_entities(
+        &self,
+        Parameters(request): Parameters<CreateEntitiesRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size("entities", request.entities.len())?;
+        self.check_batch_size(
+            "observations",
+            request.entities.iter().map(|e| e.observations.len()).sum(),
+        )?;
+
+        let result = self
+            .graph_service
+            .create_entities(request.entities, request.on_duplicate)
+            .await;
+        let text = match result {
+            Ok(report) => self.to_json(&report, None),
+            Err(e) => return Ok(CallToolResult::error(vec![Content::text(e.to_string())])),
+        }
+        .unwrap_or_else(|e| format!("Created entities but failed to serialize response: {e}"));
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        description = "Create new relations between existing entities. With return_subgraph, also return the endpoints of the created relations and the edges among them. Relations can carry observations, notes about the relation itself. Input: {\"relations\": [{\"from\": string, \"to\": string, \"relationType\": string, \"observations\": [string]?}], \"return_subgraph\": bool?}"
+    )]
+    pub async fn create_relations(
+        &self,
+        Parameters(request): Parameters<CreateRelationsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size("relations", request.relations.len())?;
+
+        let tool_error =
+            |e: anyhow::Error| CallToolResult::error(vec![Content::text(e.to_string())]);
+        let CreatedRelations { created, replaced } =
+            match self.graph_service.create_relations(request.relations).await {
+                Ok(created) => created,
+                Err(e) => return Ok(tool_error(e)),
+            };
+
+        let subgraph = if request.return_subgraph.unwrap_or(false) {
+            match self.graph_service.relations_subgraph(&created).await {
+                Ok(subgraph) => Some(*subgraph),
+                Err(e) => return Ok(tool_error(e)),
+            }
+        } else {
+            None
+        };
+        // A plain list unless there is more to report
+        let serialized = if subgraph.is_none() && replaced.is_empty() {
+            self.to_json(&created, None)
+        } else {
+            self.to_json(
+                &CreateRelationsResult {
+                    created,
+                    replaced,
+                    subgraph,
+                },
+                None,
+            )
+        };
+
+        let text = serialized
+            .unwrap_or_else(|e| format!("Created relations but failed to serialize response: {e}"));
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        description = "Ensure two entities exist and are related, in one step: creates whichever entity is missing and the relation if absent, and reports what was created and what already existed. Existing entities are not modified. Input: {\"from\": {\"name\": string, \"entityType\": string, \"observations\": [string]}, \"to\": {...}, \"relation_type\": string}"
+    )]
+    async fn connect_or_create(
+        &self,
+        Parameters(request): Parameters<ConnectOrCreateRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .connect_or_create(request.from, request.to, request.relation_type)
+            .await
+        {
+            Ok(report) => match self.to_json(&report, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize connect result: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to connect entities: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Create entities that don't exist yet and merge new observations into ones that do, reporting whether each was created, updated, or unchanged. Input: {\"entities\": [{\"name\": string, \"entityType\": string, \"observations\": [string]}], \"update_type\": bool?}"
+    )]
+    async fn upsert_entities(
+        &self,
+        Parameters(request): Parameters<UpsertEntitiesRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size("entities", request.entities.len())?;
+        self.check_batch_size(
+            "observations",
+            request.entities.iter().map(|e| e.observations.len()).sum(),
+        )?;
+        match self
+            .graph_service
+            .upsert_entities(request.entities, request.update_type.unwrap_or(false))
+            .await
+        {
+            Ok(results) => {
+                let formatted_results: Vec<UpsertResult> = results
+                    .into_iter()
+                    .map(|(name, status)| UpsertResult { name, status })
+                    .collect();
+
+                match self.to_json(&formatted_results, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize upsert results: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to upsert entities: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Search for nodes in the knowledge graph by text query. Each matching entity lists the fields that matched (name, type, observation) in matchedFields. Use format 'names_only' or 'summary' to return less text"
+    )]
+    async fn search_nodes(
+        &self,
+        Parameters(request): Parameters<SearchNodesRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        let options = SearchOptions {
+            ranker: request.ranker.unwrap_or_default(),
+            include_related_entities: request.include_related_entities.unwrap_or(false),
+            related_entity_threshold: request.related_entity_threshold,
+            decay_half_life_days: request.decay_half_life_days,
+            source: request.source,
+            boosts: (request.name_boost.is_some()
+                || request.type_boost.is_some()
+                || request.observation_boost.is_some())
+            .then_some(FieldBoosts {
+                name: request.name_boost,
+                entity_type: request.type_boost,
+                observation: request.observation_boost,
+            }),
+            search_types: request.search_types,
+            ranker_config: None,
+            graph_version: None,
+            search_relation_observations: request.search_relation_observations.unwrap_or(false),
+        };
+        let result = self
+            .reader(request.file)
+            .await?
+            .search_nodes(&request.query, request.limit, options)
+            .await;
+
+        let mut graph = match result {
+            Ok(graph) => graph,
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("Search failed: {e}"),
+                    None,
+                ))
+            }
+        };
+
+        let serialized = match request.format.unwrap_or_default() {
+            ResultFormat::Full => self.to_json(&graph, request.pretty),
+            ResultFormat::NamesOnly => {
+                let mut entities: Vec<EntityName> = graph
+                    .entities
+                    .into_values()
+                    .map(|e| EntityName {
+                        name: e.name,
+                        entity_type: e.entity_type,
+                    })
+                    .collect();
+                entities.sort_by(|a, b| a.name.cmp(&b.name));
+                self.to_json(
+                    &NamesOnlyResult {
+                        entities,
+                        relations: graph.relations,
+                        near_duplicates_suppressed: graph.near_duplicates_suppressed,
+                    },
+                    request.pretty,
+                )
+            }
+            ResultFormat::Summary => {
+                for entity in graph.entities.values_mut() {
+                    entity.observations = entity
+                        .observations
+                        .iter()
+                        .find_map(|o| {
+                            match_snippet(&o.content, &request.query, SUMMARY_SNIPPET_CHARS).map(
+                                |snippet| {
+                                    Observation::with_source(snippet, o.source.clone())
+                                        .with_tags(o.tags.clone())
+                                },
+                            )
+                        })
+                        .into_iter()
+                        .collect();
+                }
+                self.to_json(&graph, request.pretty)
+            }
+        };
+
+        match serialized {
+            Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to serialize graph: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Find the entities matching a text query and return the relations touching them, optionally only of one type, with the entities at both ends. Matching entities carry matchedFields; the others were reached through a relation. Input: {\"entity_query\": string, \"relation_type\": string?}"
+    )]
+    async fn relations_matching(
+        &self,
+        Parameters(request): Parameters<RelationsMatchingRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .relations_matching(request.entity_query, request.relation_type)
+            .await
+        {
+            Ok(graph) => match self.to_json(&*graph, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize graph: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to find matching relations: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Preview consolidating the entities matching a search query into one: a suggested canonical name and type, the union of their observations, and their relations redirected to the canonical name. Nothing is changed. Input: {\"query\": string, \"limit\": integer?}"
+    )]
+    async fn consolidation_preview(
+        &self,
+        Parameters(request): Parameters<ConsolidationPreviewRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .consolidation_preview(&request.query, request.limit)
+            .await
+        {
+            Ok(preview) => match self.to_json(&preview, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize consolidation preview: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to preview consolidation: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Search individual observations across all entities, returning the best matching facts with their entity and index"
+    )]
+    async fn search_observations(
+        &self,
+        Parameters(request): Parameters<SearchObservationsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .search_observations(&request.query, request.limit)
+            .await
+        {
+            Ok(matches) => match self.to_json(&matches, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize observations: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Search failed: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Get the configuration the server is running with, after environment variables, config files and defaults are applied: memory file and related paths, write mode, name and duplicate policies, strict schema, effective search weights and limits, batch limit, and disabled tools"
+    )]
+    async fn get_config(&self) -> Result<CallToolResult, McpError> {
+        match self.graph_service.get_config().await {
+            Ok(graph) => {
+                let config = ConfigResult {
+                    graph,
+                    max_batch_size: self.max_batch_size,
+                    pretty_json: self.pretty_json,
+                    disabled_tools: self.disabled_tools.clone(),
+                };
+                match self.to_json(&config, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize config: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to get config: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Get statistics about the knowledge graph: entity and relation counts, a version counter bumped on every save, and the last modification time"
+    )]
+    async fn get_stats(&self) -> Result<CallToolResult, McpError> {
+        let result = match self.graph_service.get_stats().await {
+            Ok(counts) => self
+                .graph_service
+                .modification_info()
+                .await
+                .map(|info| (counts, info)),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(((entities, relations), info)) => {
+                let stats = GraphStats {
+                    entities,
+                    relations,
+                    version: info.version,
+                    last_modified: info.last_modified,
+                };
+                match self.to_json(&stats, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize stats: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to get stats: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Get a health picture of the knowledge graph: counts, observation volume and the entity with the most observations, and degree metrics (average and maximum relations per entity, isolated entities)"
+    )]
+    async fn get_detailed_stats(&self) -> Result<CallToolResult, McpError> {
+        match self.graph_service.get_detailed_stats().await {
+            Ok(stats) => match self.to_json(&stats, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize stats: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to get stats: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Measure how tangled or fragmented the knowledge graph is, treating relations as undirected links: density (links over possible links), average clustering coefficient, number of connected components and size of the largest, and diameter. Above 1000 entities the diameter is a sampled lower bound (diameter_exact false) unless exact_diameter is set. Input: {\"exact_diameter\": bool?}"
+    )]
+    async fn graph_metrics(
+        &self,
+        Parameters(request): Parameters<GraphMetricsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .graph_metrics(request.exact_diameter.unwrap_or(false))
+            .await
+        {
+            Ok(metrics) => match self.to_json(&metrics, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize graph metrics: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to compute graph metrics: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Get the skeleton of the knowledge graph: the k most central entities (by degree, or PageRank over relation direction) with their scores, and the relations among just those entities. Entities without relations are never included. Input: {\"k\": integer?, \"centrality\": \"degree\" | \"pagerank\"?}"
+    )]
+    async fn core_concepts(
+        &self,
+        Parameters(request): Parameters<CoreConceptsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .core_concepts(
+                request.k.unwrap_or(DEFAULT_CORE_CONCEPTS),
+                request.centrality.unwrap_or_default(),
+            )
+            .await
+        {
+            Ok(core) => match self.to_json(&core, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize core concepts: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to find core concepts: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Get a snapshot of the knowledge graph to orient in it at the start of a session: entity, relation and observation counts, the most connected entities, the number of entities per type, the most recently updated entities, and the number of entities without relations. Input: {\"limit\": integer?}"
+    )]
+    async fn overview(
+        &self,
+        Parameters(request): Parameters<OverviewRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        let limit = request.limit.unwrap_or(DEFAULT_OVERVIEW_LIMIT);
+        match self.graph_service.overview(limit).await {
+            Ok(overview) => match self.to_json(&overview, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize overview: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to build overview: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "List entities ranked by how many observations they hold, most first, or fewest first with ascending to find empty stubs. Input: {\"limit\": integer?, \"ascending\": bool?}"
+    )]
+    async fn entities_by_observation_count(
+        &self,
+        Parameters(request): Parameters<ObservationCountRankingRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .entities_by_observation_count(request.limit, request.ascending.unwrap_or(false))
+            .await
+        {
+            Ok(entities) => match self.to_json(&entities, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize entities: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to rank entities: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "List entities created or updated at or after a Unix timestamp, oldest change first, for incremental sync. Input: {\"since\": integer}"
+    )]
+    async fn entities_changed_since(
+        &self,
+        Parameters(request): Parameters<ChangedSinceRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .entities_changed_since(request.since)
+            .await
+        {
+            Ok(entities) => match self.to_json(&entities, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize entities: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to list changed entities: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "List entities whose created_at (or, with field 'updated_at', updated_at) timestamp lies between start and end, both inclusive Unix timestamps and each optional, oldest first. Entities without that timestamp are never returned. Input: {\"start\": integer?, \"end\": integer?, \"field\": \"created_at\" | \"updated_at\"?}"
+    )]
+    async fn entities_in_range(
+        &self,
+        Parameters(request): Parameters<EntitiesInRangeRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .entities_in_range(
+                request.start,
+                request.end,
+                request.field.unwrap_or_default(),
+            )
+            .await
+        {
+            Ok(entities) => match self.to_json(&entities, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize entities: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to list entities in range: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Count how many entities have an observation containing a term, and optionally how often it occurs in total, without returning any entities. Input: {\"term\": string, \"include_occurrences\": bool?}"
+    )]
+    async fn term_frequency(
+        &self,
+        Parameters(request): Parameters<TermFrequencyRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .term_frequency(request.term.clone())
+            .await
+        {
+            Ok((entities, occurrences)) => {
+                let result = TermFrequencyResult {
+                    term: request.term,
+                    entities,
+                    occurrences: request
+                        .include_occurrences
+                        .unwrap_or(false)
+                        .then_some(occurrences),
+                };
+                match self.to_json(&result, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize term frequency: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to count term: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Get the graph structure as an adjacency list mapping each entity to the entities its relations point to, optionally for one relation type. Input: {\"relation_type\": string?}"
+    )]
+    async fn adjacency(
+        &self,
+        Parameters(request): Parameters<AdjacencyRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self.graph_service.adjacency(request.relation_type).await {
+            Ok(adjacency) => match self.to_json(&adjacency, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize adjacency list: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to build adjacency list: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "List the entities reachable from an entity within depth relation hops (either direction), ranked by distance and then by number of relations, each with its distance and degree. Input: {\"name\": string, \"depth\": integer?, \"limit\": integer?}"
+    )]
+    async fn related_entities(
+        &self,
+        Parameters(request): Parameters<RelatedEntitiesRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .related_entities(
+                request.name,
+                request.depth.unwrap_or(2),
+                Some(request.limit.unwrap_or(10)),
+            )
+            .await
+        {
+            Ok(related) => match self.to_json(&related, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize related entities: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to find related entities: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "List the entities whose observations share the most terms with those of an entity (Jaccard similarity of their term sets), whether or not they are related, each with its similarity and the shared terms. Input: {\"name\": string, \"limit\": integer?}"
+    )]
+    async fn similar_entities(
+        &self,
+        Parameters(request): Parameters<SimilarEntitiesRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .similar_entities(request.name, Some(request.limit.unwrap_or(10)))
+            .await
+        {
+            Ok(similar) => match self.to_json(&similar, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize similar entities: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to find similar entities: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Check whether an exact relation already exists. Input: {\"from\": string, \"to\": string, \"relationType\": string}"
+    )]
+    async fn relation_exists(
+        &self,
+        Parameters(relation): Parameters<Relation>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .relation_exists(relation.from, relation.to, relation.relation_type)
+            .await
+        {
+            Ok(exists) => match self.to_json(&RelationExistsResult { exists }, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize relation check: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to check relation: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Count the relations connecting two entities, optionally only in one direction. Input: {\"from\": string, \"to\": string, \"directed\": bool?}"
+    )]
+    async fn relation_count_between(
+        &self,
+        Parameters(request): Parameters<RelationCountRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        let directed = request.directed.unwrap_or(false);
+        match self
+            .graph_service
+            .relation_count_between(request.from.clone(), request.to.clone(), directed)
+            .await
+        {
+            Ok(count) => {
+                let result = RelationCountResult {
+                    from: request.from,
+                    to: request.to,
+                    directed,
+                    count,
+                };
+                match self.to_json(&result, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize relation count: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to count relations: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Find up to max_paths distinct paths between two entities that visit no entity twice, following relations in either direction, shortest first and at most max_length relations long. Each path lists its entities and the relations joining them as stored. Returns an empty list when the entities aren't connected within max_length. At most 100 paths of up to 8 relations are returned whatever is asked. Input: {\"from\": string, \"to\": string, \"max_paths\": integer?, \"max_length\": integer?}"
+    )]
+    async fn find_paths(
+        &self,
+        Parameters(request): Parameters<FindPathsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .find_paths(
+                request.from,
+                request.to,
+                request.max_paths.unwrap_or(5),
+                request.max_length.unwrap_or(4),
+            )
+            .await
+        {
+            Ok(paths) => match self.to_json(&paths, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize paths: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to find paths: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Check the stored graph for structural problems: dangling or duplicate relations, empty or duplicate entity names, and empty observations"
+    )]
+    async fn validate(&self) -> Result<CallToolResult, McpError> {
+        match self.graph_service.validate().await {
+            Ok(report) => match self.to_json(&report, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize validation report: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to validate graph: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Check entities before creating them, without writing anything. Takes the same input as create_entities and returns, per entity, whether it is valid, errors to fix (empty name, empty observations, type outside a strict schema), warnings (type outside a non-strict schema, empty type, duplicate name) and the existing or earlier entity a duplicate matches. Input: {\"entities\": [{\"name\": string, \"entityType\": string, \"observations\": [string]}], \"on_duplicate\": \"skip\" | \"merge\"?}"
+    )]
+    async fn validate_entities(
+        &self,
+        Parameters(request): Parameters<CreateEntitiesRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size("entities", request.entities.len())?;
+        match self
+            .graph_service
+            .validate_entities(&request.entities, request.on_duplicate)
+            .await
+        {
+            Ok(results) => match self.to_json(&results, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize validation results: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to validate entities: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "List the most recent changes to the graph, newest first, each with its Unix timestamp, operation, and the entities and relations it affected. Only changes made since the server started are kept. Input: {\"limit\": integer?}"
+    )]
+    async fn get_audit_log(
+        &self,
+        Parameters(request): Parameters<AuditLogRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .get_audit_log(Some(request.limit.unwrap_or(50)))
+            .await
+        {
+            Ok(entries) => match self.to_json(&entries, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize audit log: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to read audit log: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Export the knowledge graph in the JSONL memory file format, split across multiple content items. Optionally export only the entities of some types, with some names, or matching a search query, with the relations among them. Input: {\"chunk_size\": integer?, \"types\": [string]?, \"names\": [string]?, \"query\": string?}"
+    )]
+    async fn export_jsonl(
+        &self,
+        Parameters(request): Parameters<ExportJsonlRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size("names", request.filter.names.len())?;
+        match self
+            .graph_service
+            .export_jsonl(&request.filter, request.chunk_size.unwrap_or(500))
+            .await
+        {
+            Ok(chunks) => Ok(CallToolResult::success(
+                chunks.into_iter().map(Content::text).collect(),
+            )),
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to export graph: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Export the knowledge graph, or only the entities of some types, with some names, or matching a search query (with the relations among them), as JSON, JSONL, Graphviz DOT or CSV. In CSV an entity's observations share one field, separated by observation_delimiter (default ' | ') with a backslash before any delimiter or backslash inside an observation. Input: {\"format\": \"json\" | \"jsonl\" | \"dot\" | \"csv\"?, \"observation_delimiter\": string?, \"types\": [string]?, \"names\": [string]?, \"query\": string?}"
+    )]
+    async fn export(
+        &self,
+        Parameters(request): Parameters<ExportRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size("names", request.filter.names.len())?;
+        match self
+            .graph_service
+            .export(
+                &request.filter,
+                request.format.unwrap_or_default(),
+                request
+                    .observation_delimiter
+                    .as_deref()
+                    .unwrap_or(DEFAULT_OBSERVATION_DELIMITER),
+            )
+            .await
+        {
+            Ok(exported) => Ok(CallToolResult::success(vec![Content::text(exported)])),
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to export graph: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Export an entity and the entities within depth relation hops of it (either direction), with the relations among them, as Graphviz DOT. The entity itself is drawn filled and double-circled. Input: {\"name\": string, \"depth\": integer?}"
+    )]
+    async fn export_ego_dot(
+        &self,
+        Parameters(request): Parameters<ExportEgoDotRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .export_ego_dot(request.name, request.depth.unwrap_or(1))
+            .await
+        {
+            Ok(dot) => Ok(CallToolResult::success(vec![Content::text(dot)])),
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to export neighborhood: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Get the exact JSONL line stored in the memory file for an entity, for debugging serialization. Input: {\"name\": string}"
+    )]
+    async fn raw_entity_line(
+        &self,
+        Parameters(request): Parameters<RawEntityLineRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .raw_entity_line(request.name.clone())
+            .await
+        {
+            Ok(line) => {
+                let result = RawEntityLineResult {
+                    name: request.name,
+                    line,
+                };
+                match self.to_json(&result, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize entity line: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to get entity line: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Read the entire knowledge graph, or with file an archived memory file. Input: {\"file\": string?}"
+    )]
+    async fn read_graph(
+        &self,
+        Parameters(request): Parameters<ReadGraphRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self.reader(request.file).await?.read_graph().await {
+            Ok(graph) => match self.to_json(&*graph, request.pretty) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize graph: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to read graph: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Add notes to an existing relation, identified by from, to and relationType, such as when it held. Observations the relation already has are skipped. Returns the relation with all its observations, the ones added and the ones already present. Input: {\"from\": string, \"to\": string, \"relationType\": string, \"observations\": [string]}"
+    )]
+    async fn add_relation_observations(
+        &self,
+        Parameters(relation): Parameters<Relation>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size("observations", relation.observations.len())?;
+        match self.graph_service.add_relation_observations(relation).await {
+            Ok(result) => match self.to_json(&result, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize relation observations: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to add relation observations: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Add new observations to existing entities. For each entity the response lists the added observations in contents and the ones skipped because the entity already has them in already_present. They are appended unless order is 'prepend'. With dedup_substrings, observations contained in existing ones are dropped and existing ones a new observation contains are replaced; both are reported. Input: {\"observations\": [{\"entityName\": string, \"contents\": [string], \"source\": string?, \"tags\": {string: string}?}], \"order\": \"append\" | \"prepend\"?, \"dedup_substrings\": bool?}"
+    )]
+    async fn add_observations(
+        &self,
+        Parameters(request): Parameters<AddObservationsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size(
+            "observations",
+            request.observations.iter().map(|o| o.contents.len()).sum(),
+        )?;
+        let metadata: Vec<(Option<String>, BTreeMap<String, String>)> = request
+            .observations
+            .iter()
+            .map(|o| (o.source.clone(), o.tags.clone().unwrap_or_default()))
+            .collect();
+        let observations: Vec<(String, Vec<Observation>)> = request
+            .observations
+            .into_iter()
+            .map(|o| {
+                let source = o.source;
+                let tags = o.tags.unwrap_or_default();
+                let contents = o
+                    .contents
+                    .into_iter()
+                    .map(|content| {
+                        Observation::with_source(content, source.clone()).with_tags(tags.clone())
+                    })
+                    .collect();
+                (o.entity_name, contents)
+            })
+            .collect();
+
+        match self
+            .graph_service
+            .add_observations(
+                observations,
+                request.order.unwrap_or_default(),
+                request.dedup_substrings.unwrap_or(false),
+            )
+            .await
+        {
+            Ok(results) => {
+                let formatted_results: Vec<AddObservationsResult> = results
+                    .into_iter()
+                    .zip(metadata)
+                    .map(|(result, (source, tags))| AddObservationsResult {
+                        entity_name: result.entity_name,
+                        contents: result.added.into_iter().map(|o| o.content).collect(),
+                        source,
+                        tags,
+                        already_present: result.already_present,
+                        dropped: result.dropped,
+                        replaced: result.replaced,
+                    })
+                    .collect();
+
+                match self.to_json(&formatted_results, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize observations: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to add observations: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Delete multiple entities and their associated relations from the knowledge graph, returning what was removed. With keep_relations the relations stay, dangling, and are listed in dangling_relations. Input: {\"entity_names\": [string], \"dry_run\": bool?, \"keep_relations\": bool?}"
+    )]
+    async fn delete_entities(
+        &self,
+        Parameters(request): Parameters<DeleteEntitiesRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size("entities", request.entity_names.len())?;
+        let dry_run = request.dry_run.unwrap_or(false);
+        match self
+            .graph_service
+            .delete_entities(
+                request.entity_names,
+                dry_run,
+                request.keep_relations.unwrap_or(false),
+            )
+            .await
+        {
+            Ok(report) => {
+                let result = DeleteEntitiesResult::new(report, dry_run);
+                match self.to_json(&result, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize deletion result: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to delete entities: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Delete specific observations from entities by content, returning what was removed. Input: {\"deletions\": [{\"entityName\": string, \"observations\": [string]}], \"dry_run\": bool?}"
+    )]
+    async fn delete_observations(
+        &self,
+        Parameters(request): Parameters<DeleteObservationsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size(
+            "observations",
+            request.deletions.iter().map(|d| d.observations.len()).sum(),
+        )?;
+        let dry_run = request.dry_run.unwrap_or(false);
+        let deletions: Vec<(String, Vec<String>)> = request
+            .deletions
+            .into_iter()
+            .map(|d| (d.entity_name, d.observations))
+            .collect();
+
+        match self
+            .graph_service
+            .delete_observations(deletions, dry_run)
+            .await
+        {
+            Ok(report) => {
+                let observations: Vec<ObservationDeletion> = report
+                    .observations
+                    .into_iter()
+                    .map(|(entity_name, observations)| ObservationDeletion {
+                        entity_name,
+                        observations,
+                    })
+                    .collect();
+                let result = DeleteObservationsResult {
+                    observations_deleted: observations.iter().map(|d| d.observations.len()).sum(),
+                    dry_run,
+                    observations,
+                };
+                match self.to_json(&result, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize deletion result: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to delete observations: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Remove every observation of an entity, keeping the entity and its relations. Input: {\"entityName\": string}"
+    )]
+    async fn clear_observations(
+        &self,
+        Parameters(request): Parameters<ClearObservationsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .clear_observations(request.entity_name)
+            .await
+        {
+            Ok(observations_deleted) => {
+                let result = ClearObservationsResult {
+                    observations_deleted,
+                };
+                match self.to_json(&result, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize deletion result: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to clear observations: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Remove relations whose source or target entity no longer exists, e.g. after a bad rename or a hand edit of the memory file, returning how many were removed"
+    )]
+    async fn prune_dangling_relations(&self) -> Result<CallToolResult, McpError> {
+        match self.graph_service.prune_dangling_relations().await {
+            Ok(relations_deleted) => {
+                let result = PruneDanglingRelationsResult { relations_deleted };
+                match self.to_json(&result, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize deletion result: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to prune dangling relations: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Delete multiple relations from the knowledge graph, returning what was removed. Input: {\"relations\": [{\"from\": string, \"to\": string, \"relationType\": string}], \"dry_run\": bool?}"
+    )]
+    async fn delete_relations(
+        &self,
+        Parameters(request): Parameters<DeleteRelationsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size("relations", request.relations.len())?;
+        let dry_run = request.dry_run.unwrap_or(false);
+        match self
+            .graph_service
+            .delete_relations(request.relations, dry_run)
+            .await
+        {
+            Ok(report) => {
+                let result = DeleteRelationsResult {
+                    relations_deleted: report.relations.len(),
+                    dry_run,
+                    relations: report.relations,
+                };
+                match self.to_json(&result, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize deletion result: {e}"),
                         None,
                     )),
                 }
             }
             Err(e) => Err(McpError::internal_error(
-                format!("Failed to add observations: {e}"),
+                format!("Failed to delete relations: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Remove every entity and relation from the knowledge graph, returning what was removed. Input: {\"dry_run\": bool?}"
+    )]
+    async fn clear_graph(
+        &self,
+        Parameters(request): Parameters<ClearGraphRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        let dry_run = request.dry_run.unwrap_or(false);
+        match self.graph_service.clear_graph(dry_run).await {
+            Ok(report) => {
+                let result = DeleteEntitiesResult::new(report, dry_run);
+                match self.to_json(&result, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize deletion result: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to clear graph: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Bump an entity's updatedAt timestamp without changing its data, keeping it fresh for recency-weighted search. Input: {\"name\": string}"
+    )]
+    async fn touch_entity(
+        &self,
+        Parameters(request): Parameters<TouchEntityRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self.graph_service.touch_entity(request.name).await {
+            Ok(entity) => match self.to_json(&entity, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize entity: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to touch entity: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Reorder an entity's observations alphabetically or reverse their stored order, returning the entity. Input: {\"name\": string, \"by\": \"alphabetical\" | \"reverse\"?}"
+    )]
+    async fn sort_observations(
+        &self,
+        Parameters(request): Parameters<SortObservationsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .sort_observations(request.name, request.by.unwrap_or_default())
+            .await
+        {
+            Ok(entity) => match self.to_json(&entity, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize entity: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to sort observations: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Split an entity by moving some of its observations into a new entity of the same type (or entity_type), optionally moving chosen relations to it. Input: {\"name\": string, \"new_name\": string, \"observations\": [string], \"entity_type\": string?, \"relink\": [{\"from\": string, \"to\": string, \"relationType\": string}]?}"
+    )]
+    async fn split_entity(
+        &self,
+        Parameters(request): Parameters<SplitEntityRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .split_entity(
+                request.name,
+                request.new_name,
+                request.observations,
+                request.entity_type,
+                request.relink.unwrap_or_default(),
+            )
+            .await
+        {
+            Ok(split) => match self.to_json(&split, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize split result: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to split entity: {e}"),
                 None,
             )),
         }
     }
 
     #[tool(
-        description = "Delete multiple entities and their associated relations from the knowledge graph"
+        description = "Rename an entity type on every entity that has it, returning the number of entities changed. Input: {\"from\": string, \"to\": string, \"case_insensitive\": bool?}"
     )]
-    async fn delete_entities(
+    async fn rename_entity_type(
         &self,
-        Parameters(request): Parameters<DeleteEntitiesRequest>,
+        Parameters(request): Parameters<RenameEntityTypeRequest>,
     ) -> Result<CallToolResult, McpError> {
         match self
             .graph_service
-            .delete_entities(request.entity_names)
+            .rename_entity_type(
+                request.from,
+                request.to,
+                request.case_insensitive.unwrap_or(false),
+            )
             .await
         {
-            Ok(_) => Ok(CallToolResult::success(vec![Content::text(
-                "Entities deleted successfully".to_string(),
-            )])),
+            Ok(changed) => match self.to_json(&RenameResult { changed }, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize rename result: {e}"),
+                    None,
+                )),
+            },
             Err(e) => Err(McpError::internal_error(
-                format!("Failed to delete entities: {e}"),
+                format!("Failed to rename entity type: {e}"),
                 None,
             )),
         }
     }
 
-    #[tool(description = "Delete specific observations from entities in the knowledge graph")]
-    async fn delete_observations(
+    #[tool(
+        description = "Replace literal text in observations across the given entities, or all entities, e.g. to fix a misspelled name everywhere. Observations that become identical to another of the same entity are merged. Returns the number of observations changed. Input: {\"find\": string, \"replace\": string, \"entity_names\": [string]?}"
+    )]
+    async fn replace_in_observations(
         &self,
-        Parameters(request): Parameters<DeleteObservationsRequest>,
+        Parameters(request): Parameters<ReplaceInObservationsRequest>,
     ) -> Result<CallToolResult, McpError> {
-        let deletions: Vec<(String, Vec<String>)> = request
-            .deletions
-            .into_iter()
-            .map(|d| (d.entity_name, d.observations))
-            .collect();
+        if let Some(names) = &request.entity_names {
+            self.check_batch_size("names", names.len())?;
+        }
+        match self
+            .graph_service
+            .replace_in_observations(request.find, request.replace, request.entity_names)
+            .await
+        {
+            Ok(changed) => match self.to_json(&RenameResult { changed }, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize replace result: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to replace in observations: {e}"),
+                None,
+            )),
+        }
+    }
 
-        match self.graph_service.delete_observations(deletions).await {
-            Ok(_) => Ok(CallToolResult::success(vec![Content::text(
-                "Observations deleted successfully".to_string(),
-            )])),
+    #[tool(
+        description = "Rename a relation type on every relation that has it, removing relations that become duplicates, and return the number changed. Input: {\"from\": string, \"to\": string}"
+    )]
+    async fn rename_relation_type(
+        &self,
+        Parameters(request): Parameters<RenameRelationTypeRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .rename_relation_type(request.from, request.to)
+            .await
+        {
+            Ok(changed) => match self.to_json(&RenameResult { changed }, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize rename result: {e}"),
+                    None,
+                )),
+            },
             Err(e) => Err(McpError::internal_error(
-                format!("Failed to delete observations: {e}"),
+                format!("Failed to rename relation type: {e}"),
                 None,
             )),
         }
     }
 
-    #[tool(description = "Delete multiple relations from the knowledge graph")]
-    async fn delete_relations(
+    #[tool(
+        description = "Find entities with observations from a given source, returning only those observations"
+    )]
+    async fn find_by_source(
         &self,
-        Parameters(request): Parameters<DeleteRelationsRequest>,
+        Parameters(request): Parameters<FindBySourceRequest>,
     ) -> Result<CallToolResult, McpError> {
-        match self.graph_service.delete_relations(request.relations).await {
-            Ok(_) => Ok(CallToolResult::success(vec![Content::text(
-                "Relations deleted successfully".to_string(),
-            )])),
+        match self.graph_service.find_by_source(request.source).await {
+            Ok(entities) => match self.to_json(&entities, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize entities: {e}"),
+                    None,
+                )),
+            },
             Err(e) => Err(McpError::internal_error(
-                format!("Failed to delete relations: {e}"),
+                format!("Failed to find observations by source: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Find entities with observations tagged with a key, and optionally a specific value, returning only those observations. Input: {\"key\": string, \"value\": string?}"
+    )]
+    async fn search_by_tag(
+        &self,
+        Parameters(request): Parameters<SearchByTagRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .search_by_tag(request.key, request.value)
+            .await
+        {
+            Ok(entities) => match self.to_json(&entities, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize entities: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to find observations by tag: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "List every entity with an observation containing a substring, sorted by name. An exact filter without ranking or limit. Input: {\"substring\": string, \"case_sensitive\": bool?}"
+    )]
+    async fn entities_with_observation(
+        &self,
+        Parameters(request): Parameters<EntitiesWithObservationRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .graph_service
+            .entities_with_observation(request.substring, request.case_sensitive.unwrap_or(false))
+            .await
+        {
+            Ok(entities) => match self.to_json(&entities, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize entities: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to filter entities by observation: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "List entities whose observations mention the given terms (ignoring case), ranked by how many terms each covers so those covering all come first. Each result reports its covered_terms and coverage (0-1); entities covering none are left out. Input: {\"terms\": [string]}"
+    )]
+    async fn entities_covering(
+        &self,
+        Parameters(request): Parameters<EntitiesCoveringRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self.graph_service.entities_covering(request.terms).await {
+            Ok(found) => match self.to_json(&found, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize entities: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to find entities covering terms: {e}"),
                 None,
             )),
         }
     }
 
-    #[tool(description = "Open specific nodes in the knowledge graph by their names")]
+    #[tool(
+        description = "Open specific nodes in the knowledge graph by their names. Entities are returned in the requested order, with the relations among them and a not_found list of names that don't exist. With include_degree, each entity also carries its incoming and outgoing relation counts. With fuzzy, misspelled names resolve to the closest entity at least that similar (0-1), listed in fuzzy_matches. Input: {\"names\": [string], \"include_degree\": bool?, \"fuzzy\": number?}"
+    )]
     async fn open_nodes(
         &self,
         Parameters(request): Parameters<OpenNodesRequest>,
     ) -> Result<CallToolResult, McpError> {
-        match self.graph_service.open_nodes(request.names).await {
-            Ok(graph) => match serde_json::to_string(&*graph) {
+        self.check_batch_size("names", request.names.len())?;
+        let reader = self.reader(request.file).await?;
+        let opened = match reader.open_nodes(request.names, request.fuzzy).await {
+            Ok(opened) => opened,
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("Failed to open nodes: {e}"),
+                    None,
+                ))
+            }
+        };
+
+        let serialized = if request.include_degree.unwrap_or(false) {
+            let names: Vec<String> = opened.entities.iter().map(|e| e.name.clone()).collect();
+            let degrees = reader.degrees(&names).await.map_err(|e| {
+                McpError::internal_error(format!("Failed to count relations: {e}"), None)
+            })?;
+            self.to_json(
+                &OpenedNodesWithDegree {
+                    entities: opened
+                        .entities
+                        .into_iter()
+                        .map(|entity| EntityWithDegree {
+                            degree: degrees.get(&entity.name).copied().unwrap_or_default(),
+                            entity,
+                        })
+                        .collect(),
+                    relations: opened.relations,
+                    not_found: opened.not_found,
+                    fuzzy_matches: opened.fuzzy_matches,
+                },
+                request.pretty,
+            )
+        } else {
+            self.to_json(&opened, request.pretty)
+        };
+
+        match serialized {
+            Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to serialize graph: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Describe one entity: its type and observations, observation_count, outgoing and incoming relations grouped by relation type, and the names of all neighbors. Input: {\"name\": string}"
+    )]
+    async fn describe_entity(
+        &self,
+        Parameters(request): Parameters<DescribeEntityRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        match self.graph_service.describe_entity(request.name).await {
+            Ok(description) => match self.to_json(&description, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize entity description: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to describe entity: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Fetch the entities matching any of the given names or types, with the relations among them, as one graph. Input: {\"names\": [string]?, \"types\": [string]?}"
+    )]
+    async fn fetch(
+        &self,
+        Parameters(request): Parameters<FetchRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_batch_size("names", request.names.len())?;
+        match self.graph_service.fetch(request.names, request.types).await {
+            Ok(graph) => match self.to_json(&*graph, None) {
                 Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
                 Err(e) => Err(McpError::internal_error(
                     format!("Failed to serialize graph: {e}"),
@@ -408,20 +3271,297 @@ impl<GS: GraphService> GraphServiceHandler<GS> {
                 )),
             },
             Err(e) => Err(McpError::internal_error(
-                format!("Failed to open nodes: {e}"),
+                format!("Failed to fetch entities: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Get the JSON Schema of the core types (Entity, Relation, ObservationRequest, ObservationDeletion) accepted by the other tools"
+    )]
+    async fn schema(&self) -> Result<CallToolResult, McpError> {
+        let schemas = serde_json::json!({
+            "Entity": schemars::schema_for!(Entity),
+            "Relation": schemars::schema_for!(Relation),
+            "ObservationRequest": schemars::schema_for!(ObservationRequest),
+            "ObservationDeletion": schemars::schema_for!(ObservationDeletion),
+        });
+
+        match self.to_json(&schemas, None) {
+            Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to serialize schema: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Get the ontology of allowed entity and relation types, and whether it is enforced"
+    )]
+    async fn get_schema(&self) -> Result<CallToolResult, McpError> {
+        match self.graph_service.get_ontology().await {
+            Ok(ontology) => match self.to_json(&ontology, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize ontology: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to load ontology: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Replace the ontology of allowed entity and relation types. When strict, creating entities or relations with other types fails and lists the offending types. Relations of a symmetric type are the same as their reverse, so creating the reverse is skipped. An entity can be the source of at most one relation of a single-valued type; a second one is rejected, or replaces the first with on_cardinality_conflict 'replace'. Input: {\"entity_types\": [string], \"relation_types\": [string], \"strict\": bool, \"symmetric_relation_types\": [string]?, \"single_valued_relation_types\": [string]?, \"on_cardinality_conflict\": \"reject\" | \"replace\"?}"
+    )]
+    async fn set_schema(
+        &self,
+        Parameters(ontology): Parameters<Ontology>,
+    ) -> Result<CallToolResult, McpError> {
+        match self.graph_service.set_ontology(ontology).await {
+            Ok(()) => Ok(CallToolResult::success(vec![Content::text(
+                "Schema updated successfully",
+            )])),
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to update schema: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Change the search ranking settings and persist them next to the memory file so they survive restarts. Omitted fields keep their current value; an empty object returns the current settings. Weights must be at least 0, and connectivity_cap and near_duplicate_similarity between 0 and 1. Input: {\"name_weight\": number?, \"type_weight\": number?, \"observation_weight\": number?, \"observation_count_weight\": number?, \"connectivity_weight\": number?, \"connectivity_cap\": number?, \"search_types\": bool?, \"count_occurrences\": bool?, \"near_duplicate_similarity\": number?}"
+    )]
+    async fn set_search_config(
+        &self,
+        Parameters(update): Parameters<SearchConfig>,
+    ) -> Result<CallToolResult, McpError> {
+        if let Err(e) = update.validate() {
+            return Err(McpError::invalid_params(
+                format!("Invalid search config: {e}"),
+                None,
+            ));
+        }
+        match self.graph_service.set_search_config(update).await {
+            Ok(config) => match self.to_json(&config, None) {
+                Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                Err(e) => Err(McpError::internal_error(
+                    format!("Failed to serialize search config: {e}"),
+                    None,
+                )),
+            },
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to update search config: {e}"),
+                None,
+            )),
+        }
+    }
+
+    #[tool(
+        description = "Get a page of observations for a single entity, along with the total observation count"
+    )]
+    async fn get_observations(
+        &self,
+        Parameters(request): Parameters<GetObservationsRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        let offset = request.offset.unwrap_or(0);
+        match self
+            .graph_service
+            .get_observations(request.name.clone(), request.offset, request.limit)
+            .await
+        {
+            Ok((observations, total)) => {
+                let page = ObservationPage {
+                    name: request.name,
+                    observations,
+                    offset,
+                    total,
+                };
+                match self.to_json(&page, None) {
+                    Ok(serialized) => Ok(CallToolResult::success(vec![Content::text(serialized)])),
+                    Err(e) => Err(McpError::internal_error(
+                        format!("Failed to serialize observations: {e}"),
+                        None,
+                    )),
+                }
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Failed to get observations: {e}"),
                 None,
             )),
         }
     }
 }
 
-#[tool_handler]
 impl<GS: GraphService> ServerHandler for GraphServiceHandler<GS> {
     fn get_info(&self) -> ServerInfo {
+        let instructions = if self.disabled_tools.is_empty() {
+            SERVER_INSTRUCTIONS.to_string()
+        } else {
+            format!(
+                "{SERVER_INSTRUCTIONS}\n\nDisabled on this server, ignore them above: {}",
+                self.disabled_tools.join(", ")
+            )
+        };
         ServerInfo {
-            instructions: Some("knowledge graph service".into()),
+            instructions: Some(instructions),
             capabilities: ServerCapabilities::builder().enable_tools().build(),
             ..Default::default()
         }
     }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_tool_available(&request.name)?;
+        self.tool_router
+            .call(ToolCallContext::new(self, request, context))
+            .await
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult::with_all_items(self.tool_router.list_all()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::ErrorCode;
+
+    fn handler(entries: &[&str]) -> anyhow::Result<GraphServiceHandler<KnowledgeGraphService>> {
+        let entries: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        Ok(
+            GraphServiceHandler::new(KnowledgeGraphService::in_memory("")?)
+                .with_enabled_tools(&entries),
+        )
+    }
+
+    #[test]
+    fn test_pretty_json() -> anyhow::Result<()> {
+        let value = serde_json::json!({ "name": "Alice" });
+        let compact = handler(&["all"])?;
+        assert_eq!(compact.to_json(&value, None)?, r#"{"name":"Alice"}"#);
+        assert_eq!(
+            compact.to_json(&value, Some(true))?,
+            "{\n  \"name\": \"Alice\"\n}"
+        );
+
+        let pretty = handler(&["all"])?.with_pretty_json(true);
+        assert!(pretty.to_json(&value, None)?.contains('\n'));
+        assert_eq!(pretty.to_json(&value, Some(false))?, r#"{"name":"Alice"}"#);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_only_tool_subset() -> anyhow::Result<()> {
+        // The groups only name tools that exist
+        let everything = handler(&["all"])?;
+        for tool in DELETE_TOOLS.iter().chain(WRITE_TOOLS).chain(SEARCH_TOOLS) {
+            assert!(everything.tool_router.has_route(tool), "{tool}");
+        }
+
+        let read_only = handler(&["read"])?;
+        for tool in DELETE_TOOLS.iter().chain(WRITE_TOOLS) {
+            assert!(!read_only.tool_router.has_route(tool), "{tool}");
+            assert!(read_only.check_tool_available(tool).is_err(), "{tool}");
+        }
+        for tool in ["read_graph", "search_nodes", "open_nodes", "get_stats"] {
+            assert!(read_only.tool_router.has_route(tool), "{tool}");
+        }
+        let instructions = read_only.get_info().instructions.unwrap_or_default();
+        assert!(instructions.contains("Disabled on this server"));
+        assert!(instructions.contains("clear_graph"));
+
+        // Groups and individual tool names combine
+        let mixed = handler(&["search", "create_entities"])?;
+        let mut tools: Vec<String> = mixed
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(|t| t.name.to_string())
+            .collect();
+        tools.sort();
+        let mut expected: Vec<String> = SEARCH_TOOLS
+            .iter()
+            .chain(&["create_entities"])
+            .map(|t| t.to_string())
+            .collect();
+        expected.sort();
+        assert_eq!(tools, expected);
+
+        // Widening again restores every tool
+        let all = mixed.with_enabled_tools(&["all".to_string()]);
+        assert!(all.disabled_tools.is_empty());
+        assert!(all.check_tool_available("clear_graph").is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_size_limit() -> anyhow::Result<()> {
+        let handler = handler(&["all"])?.with_max_batch_size(2);
+        let entities: Vec<Entity> = ["Alice", "Bob", "Carol"]
+            .map(|name| Entity {
+                name: name.to_string(),
+                entity_type: "Person".to_string(),
+                ..Default::default()
+            })
+            .to_vec();
+        let relations: Vec<Relation> = [("Alice", "Bob"), ("Bob", "Carol"), ("Carol", "Alice")]
+            .map(|(from, to)| Relation {
+                from: from.to_string(),
+                to: to.to_string(),
+                relation_type: "knows".to_string(),
+                ..Default::default()
+            })
+            .to_vec();
+        let assert_rejected = |error: McpError, items: &str| {
+            assert_eq!(error.code, ErrorCode::INVALID_PARAMS);
+            assert_eq!(
+                error.data,
+                Some(serde_json::json!({ "items": items, "limit": 2, "received": 3 }))
+            );
+        };
+
+        let error = handler
+            .create_entities(Parameters(CreateEntitiesRequest {
+                entities: entities.clone(),
+                on_duplicate: None,
+            }))
+            .await
+            .unwrap_err();
+        assert_rejected(error, "entities");
+        let error = handler
+            .create_relations(Parameters(CreateRelationsRequest {
+                relations,
+                return_subgraph: None,
+            }))
+            .await
+            .unwrap_err();
+        assert_rejected(error, "relations");
+        assert_eq!(handler.graph_service.get_stats().await?, (0, 0));
+
+        // A batch at the limit goes through
+        handler
+            .create_entities(Parameters(CreateEntitiesRequest {
+                entities: entities[..2].to_vec(),
+                on_duplicate: None,
+            }))
+            .await?;
+        assert_eq!(handler.graph_service.get_stats().await?, (2, 0));
+
+        Ok(())
+    }
 }
@@ -1,3 +1,4 @@
+mod export;
 mod graph;
 pub mod handler;
 mod search;
@@ -1,16 +1,93 @@
-use anyhow::Result;
-use mcp_memory::handler::{GraphServiceHandler, KnowledgeGraphService};
+use anyhow::{bail, Context, Result};
+use mcp_memory::handler::{GraphService, GraphServiceHandler, KnowledgeGraphService};
 use rmcp::ServiceExt;
 use tokio::io::{stdin, stdout};
 
+/// Command line options
+#[derive(Debug, Default)]
+struct Options {
+    /// Keep the graph in memory and never write to disk
+    read_only: bool,
+    /// Initial graph contents (JSON or JSONL) for read-only mode
+    seed: Option<String>,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut options = Options::default();
+        let mut args = args.skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--read-only" | "--in-memory" => options.read_only = true,
+                "--seed" => {
+                    options.seed = Some(args.next().context("--seed requires a value")?);
+                }
+                "--seed-file" => {
+                    let path = args.next().context("--seed-file requires a path")?;
+                    let data = std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read seed file {path}"))?;
+                    options.seed = Some(data);
+                }
+                _ => bail!("Unknown argument: {arg}"),
+            }
+        }
+
+        if options.seed.is_some() && !options.read_only {
+            bail!("--seed and --seed-file require --read-only");
+        }
+
+        Ok(options)
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let options = Options::parse(std::env::args())?;
     let transport = (stdin(), stdout());
 
-    let graph_service = KnowledgeGraphService::new();
-    let graph_server = GraphServiceHandler::new(graph_service);
+    let graph_service = match (options.read_only, options.seed) {
+        (true, Some(seed)) => KnowledgeGraphService::in_memory(&seed)?,
+        (true, None) => KnowledgeGraphService::in_memory_snapshot().await?,
+        (false, _) => KnowledgeGraphService::new(),
+    };
+    // Restore changes acknowledged but not yet written when the last run died
+    graph_service.recover().await?;
+    let graph_server = GraphServiceHandler::new(graph_service.clone());
 
     let server = graph_server.serve(transport).await?;
-    server.waiting().await?;
+    let result = tokio::select! {
+        result = server.waiting() => result.map(|_| ()),
+        _ = shutdown_signal() => Ok(()),
+    };
+
+    // Persist anything still held in memory before exiting, even if the transport failed
+    graph_service.flush().await?;
+    result?;
     Ok(())
 }
+
+/// Resolve when the process is asked to stop (Ctrl-C, or SIGTERM on Unix)
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
@@ -1,7 +1,353 @@
-use crate::graph::{Entity, KnowledgeGraph, Relation};
+use crate::graph::{now_timestamp, Entity, KnowledgeGraph, Observation, Relation};
 use anyhow::Result;
 use rayon::prelude::*;
-use std::collections::{HashMap, HashSet};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use rmcp::schemars;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::sync::{Arc, Mutex};
+
+/// BM25 term frequency saturation
+const BM25_K1: f32 = 1.2;
+/// BM25 document length normalization
+const BM25_B: f32 = 0.75;
+
+/// Scoring strategy used to rank search results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RankerKind {
+    /// Additive field weights, counting matching observations
+    #[default]
+    Weighted,
+    /// Okapi BM25 over observation terms, weighting rare terms higher
+    Bm25,
+}
+
+/// Default number of links to found entities required to pull in a neighbor
+pub const DEFAULT_RELATED_ENTITY_THRESHOLD: usize = 2;
+
+/// Per-query search options
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// Scoring strategy
+    pub ranker: RankerKind,
+    /// Also return neighbors linked to several found entities
+    pub include_related_entities: bool,
+    /// Links required to include a neighbor (defaults to the engine setting)
+    pub related_entity_threshold: Option<usize>,
+    /// Halve an entity's score for every this many days since it was last updated
+    pub decay_half_life_days: Option<f64>,
+    /// Only consider entities with at least one observation from this source
+    pub source: Option<String>,
+    /// Field weights overriding the ranker defaults for this query
+    pub boosts: Option<FieldBoosts>,
+    /// Whether entity types can match, overriding the engine setting
+    pub search_types: Option<bool>,
+    /// Persisted ranker settings, applied beneath the per-query overrides above
+    pub ranker_config: Option<SearchConfig>,
+    /// Version of the graph searched; when set, results are cached until it changes
+    pub graph_version: Option<u64>,
+    /// Also match the observations of an entity's relations
+    pub search_relation_observations: bool,
+}
+
+/// Entity field that matched a search query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+    Name,
+    Type,
+    Observation,
+    /// An observation of one of the entity's relations
+    Relation,
+}
+
+/// Per-query overrides of the ranker's field weights; unset fields keep the defaults
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FieldBoosts {
+    /// Replaces `SearchRanker::name_weight`
+    pub name: Option<f32>,
+    /// Replaces `SearchRanker::type_weight`
+    pub entity_type: Option<f32>,
+    /// Replaces `SearchRanker::observation_weight`
+    pub observation: Option<f32>,
+}
+
+/// Ranker settings persisted next to the memory file; unset fields keep the engine's values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SearchConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Weight for entity name matches (default 2.0)")]
+    pub name_weight: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Weight for entity type matches (default 1.5)")]
+    pub type_weight: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Weight for observation matches (default 1.0)")]
+    pub observation_weight: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Weight for the number of observations (default 0.5)")]
+    pub observation_count_weight: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Weight for the number of relations (default 0.3)")]
+    pub connectivity_weight: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(
+        description = "Largest share of a score connectivity may make up, between 0 and 1 (default 0.25)"
+    )]
+    pub connectivity_cap: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Whether entity types can match queries (default true)")]
+    pub search_types: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(
+        description = "Score observations by how often they contain the query, relative to their length, instead of counting the observations that contain it (default false; weighted ranker only)"
+    )]
+    pub count_occurrences: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(
+        description = "Drop results ranked below an entity named exactly like the query whose names are at least this similar to it, between 0 and 1, e.g. 0.8 (default off); the number dropped is reported"
+    )]
+    pub near_duplicate_similarity: Option<f32>,
+}
+
+impl SearchConfig {
+    /// This config with the fields set in `update` replacing its own
+    pub fn merged(self, update: SearchConfig) -> Self {
+        Self {
+            name_weight: update.name_weight.or(self.name_weight),
+            type_weight: update.type_weight.or(self.type_weight),
+            observation_weight: update.observation_weight.or(self.observation_weight),
+            observation_count_weight: update
+                .observation_count_weight
+                .or(self.observation_count_weight),
+            connectivity_weight: update.connectivity_weight.or(self.connectivity_weight),
+            connectivity_cap: update.connectivity_cap.or(self.connectivity_cap),
+            search_types: update.search_types.or(self.search_types),
+            count_occurrences: update.count_occurrences.or(self.count_occurrences),
+            near_duplicate_similarity: update
+                .near_duplicate_similarity
+                .or(self.near_duplicate_similarity),
+        }
+    }
+
+    /// Reject weights that are negative or not finite, and a cap or similarity outside 0 to 1
+    pub fn validate(&self) -> Result<()> {
+        let weights = [
+            ("name_weight", self.name_weight),
+            ("type_weight", self.type_weight),
+            ("observation_weight", self.observation_weight),
+            ("observation_count_weight", self.observation_count_weight),
+            ("connectivity_weight", self.connectivity_weight),
+        ];
+        for (field, value) in weights {
+            if let Some(value) = value.filter(|v| !v.is_finite() || *v < 0.0) {
+                anyhow::bail!("{field} must be a finite number of at least 0, got {value}");
+            }
+        }
+        let fractions = [
+            ("connectivity_cap", self.connectivity_cap),
+            ("near_duplicate_similarity", self.near_duplicate_similarity),
+        ];
+        for (field, value) in fractions {
+            if let Some(value) = value.filter(|v| !(0.0..=1.0).contains(v)) {
+                anyhow::bail!("{field} must be between 0 and 1, got {value}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Occurrences of `query_lower` in `text` divided by the square root of its word count,
+/// so repeating a term counts but a long observation doesn't outscore a focused one
+fn occurrence_density(text: &str, query_lower: &str) -> f32 {
+    if query_lower.is_empty() {
+        return 0.0;
+    }
+    let occurrences = text.to_lowercase().matches(query_lower).count();
+    if occurrences == 0 {
+        return 0.0;
+    }
+    let words = text.split_whitespace().count().max(1);
+    occurrences as f32 / (words as f32).sqrt()
+}
+
+/// Seconds in a day, for timestamp arithmetic
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Exponential recency factor in (0, 1]; entities without timestamps are not decayed
+fn recency_decay(entity: &Entity, now: i64, half_life_days: Option<f64>) -> f32 {
+    let (Some(half_life), Some(timestamp)) = (
+        half_life_days.filter(|h| *h > 0.0),
+        entity.updated_at.or(entity.created_at),
+    ) else {
+        return 1.0;
+    };
+
+    let age_days = (now - timestamp).max(0) as f64 / SECONDS_PER_DAY;
+    0.5_f64.powf(age_days / half_life) as f32
+}
+
+/// A single observation matching a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObservationMatch {
+    pub entity_name: String,
+    pub observation_index: usize,
+    pub observation: Observation,
+    pub score: f32,
+}
+
+/// An entity whose observations mention some of a set of terms
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityCoverage {
+    pub entity: Entity,
+    /// The terms found in the entity's observations, in the order they were given
+    pub covered_terms: Vec<String>,
+    /// Share of the terms covered, from 0 to 1
+    pub coverage: f32,
+}
+
+/// Split text into lowercase alphanumeric terms
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Case-insensitive similarity of two names between 0 and 1:
+/// one minus their edit distance relative to the longer name
+pub fn name_similarity(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    // Levenshtein distance, one row at a time
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    1.0 - previous[b.len()] as f32 / longest as f32
+}
+
+/// The phrase of a query wrapped in double quotes, e.g. `"project alpha"`
+fn quoted_phrase(query: &str) -> Option<&str> {
+    let phrase = query.trim().strip_prefix('"')?.strip_suffix('"')?.trim();
+    (!phrase.is_empty()).then_some(phrase)
+}
+
+/// Excerpt of at most `max_chars` characters around the first case-insensitive match of
+/// `query` in `text`, falling back to the first matching query term.
+/// Cut ends are marked with an ellipsis; `None` when nothing matches.
+pub fn match_snippet(text: &str, query: &str, max_chars: usize) -> Option<String> {
+    fn lower(text: &str) -> Vec<char> {
+        text.chars()
+            .map(|c| c.to_lowercase().next().unwrap_or(c))
+            .collect()
+    }
+    fn find(haystack: &[char], needle: &[char]) -> Option<usize> {
+        if needle.is_empty() || needle.len() > haystack.len() {
+            return None;
+        }
+        haystack.windows(needle.len()).position(|w| w == needle)
+    }
+
+    let query = quoted_phrase(query).unwrap_or(query);
+    let chars: Vec<char> = text.chars().collect();
+    let haystack = lower(text);
+    let (position, length) = std::iter::once(query.to_string())
+        .chain(tokenize(query))
+        .find_map(|needle| {
+            let needle = lower(needle.trim());
+            find(&haystack, &needle).map(|p| (p, needle.len()))
+        })?;
+
+    if chars.len() <= max_chars {
+        return Some(text.to_string());
+    }
+
+    let context = max_chars.saturating_sub(length) / 2;
+    let end = (position.saturating_sub(context) + max_chars).min(chars.len());
+    let start = end.saturating_sub(max_chars);
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    snippet.extend(&chars[start..end]);
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+/// Observation term statistics used by the BM25 ranker
+#[derive(Debug, Clone, Default)]
+pub struct TermStats {
+    /// Number of entities whose observations contain each term
+    document_frequency: HashMap<String, usize>,
+    /// Number of entities in the graph
+    document_count: usize,
+    /// Average number of observation terms per entity
+    average_length: f32,
+}
+
+impl TermStats {
+    /// Compute term statistics over all entity observations
+    pub fn build(graph: &KnowledgeGraph) -> Self {
+        let mut document_frequency: HashMap<String, usize> = HashMap::new();
+        let mut total_length = 0;
+
+        for entity in graph.entities.values() {
+            let terms: Vec<String> = entity
+                .observations
+                .iter()
+                .flat_map(|o| tokenize(&o.content))
+                .collect();
+            total_length += terms.len();
+
+            let unique: HashSet<String> = terms.into_iter().collect();
+            for term in unique {
+                *document_frequency.entry(term).or_insert(0) += 1;
+            }
+        }
+
+        let document_count = graph.entities.len();
+        let average_length = if document_count > 0 {
+            total_length as f32 / document_count as f32
+        } else {
+            0.0
+        };
+
+        Self {
+            document_frequency,
+            document_count,
+            average_length,
+        }
+    }
+
+    /// Inverse document frequency, always positive
+    fn idf(&self, term: &str) -> f32 {
+        let n = self.document_count as f32;
+        let df = self.document_frequency.get(term).copied().unwrap_or(0) as f32;
+        ((n - df + 0.5) / (df + 0.5)).ln_1p()
+    }
+}
+
+/// Default share of the score connectivity may make up
+pub const DEFAULT_CONNECTIVITY_CAP: f32 = 0.25;
 
 /// Ranking algorithm used by the search engine
 #[derive(Debug, Clone)]
@@ -16,6 +362,20 @@ pub struct SearchRanker {
     pub observation_count_weight: f32,
     /// Weight for connectivity (number of relations)
     pub connectivity_weight: f32,
+    /// Largest share of an entity's score that connectivity may make up, between 0 and 1.
+    /// Keeps heavily linked hubs from outranking better text matches; `None` leaves it uncapped.
+    pub connectivity_cap: Option<f32>,
+    /// Whether the entity type is matched against the query at all
+    pub search_types: bool,
+    /// Score observation matches by query occurrences per observation, normalized by its
+    /// length, rather than by the number of observations containing the query
+    pub count_occurrences: bool,
+    /// Results ranked below an exact name match are dropped when their names are at least
+    /// this similar to it (see `name_similarity`), so "Alice" doesn't bring "Alices" along.
+    /// Off when `None`, since distinct entities can have similar names.
+    pub near_duplicate_similarity: Option<f32>,
+    /// Short terms and stop words dropped from queries before scoring
+    term_filter: TermFilter,
 }
 
 impl Default for SearchRanker {
@@ -26,67 +386,497 @@ impl Default for SearchRanker {
             observation_weight: 1.0,
             observation_count_weight: 0.5,
             connectivity_weight: 0.3,
+            connectivity_cap: Some(DEFAULT_CONNECTIVITY_CAP),
+            search_types: true,
+            count_occurrences: false,
+            near_duplicate_similarity: None,
+            term_filter: TermFilter {
+                min_length: DEFAULT_MIN_TERM_LENGTH,
+                stop_words: HashSet::new(),
+            },
         }
     }
 }
 
 impl SearchRanker {
-    /// Calculate the relevance of an entity for a text query
+    /// This ranker with `boosts` merged over its field weights
+    pub fn with_boosts(&self, boosts: &FieldBoosts) -> Self {
+        Self {
+            name_weight: boosts.name.unwrap_or(self.name_weight),
+            type_weight: boosts.entity_type.unwrap_or(self.type_weight),
+            observation_weight: boosts.observation.unwrap_or(self.observation_weight),
+            ..self.clone()
+        }
+    }
+
+    /// This ranker with the settings from `config` merged over its own
+    pub fn with_config(&self, config: &SearchConfig) -> Self {
+        Self {
+            name_weight: config.name_weight.unwrap_or(self.name_weight),
+            type_weight: config.type_weight.unwrap_or(self.type_weight),
+            observation_weight: config.observation_weight.unwrap_or(self.observation_weight),
+            observation_count_weight: config
+                .observation_count_weight
+                .unwrap_or(self.observation_count_weight),
+            connectivity_weight: config
+                .connectivity_weight
+                .unwrap_or(self.connectivity_weight),
+            connectivity_cap: config.connectivity_cap.or(self.connectivity_cap),
+            search_types: config.search_types.unwrap_or(self.search_types),
+            count_occurrences: config.count_occurrences.unwrap_or(self.count_occurrences),
+            near_duplicate_similarity: config
+                .near_duplicate_similarity
+                .or(self.near_duplicate_similarity),
+            term_filter: self.term_filter.clone(),
+        }
+    }
+
+    fn boosted(&self, boosts: Option<&FieldBoosts>) -> Cow<'_, SearchRanker> {
+        match boosts {
+            Some(boosts) => Cow::Owned(self.with_boosts(boosts)),
+            None => Cow::Borrowed(self),
+        }
+    }
+
+    /// Calculate the relevance of an entity for a text query,
+    /// recording the fields that matched in `matched`
     pub fn calculate_text_relevance(
         &self,
         entity: &Entity,
         query: &str,
         relations: &[Relation],
+        boosts: Option<&FieldBoosts>,
+        matched: &mut Vec<SearchField>,
+    ) -> f32 {
+        let ranker = self.boosted(boosts);
+        ranker.text_relevance(entity, query, relations, matched)
+    }
+
+    fn text_relevance(
+        &self,
+        entity: &Entity,
+        query: &str,
+        relations: &[Relation],
+        matched: &mut Vec<SearchField>,
     ) -> f32 {
         let query_lower = query.to_lowercase();
+        let Some(query_lower) = self.term_filter.query_for(entity, &query_lower) else {
+            return 0.0;
+        };
+        let query_lower = query_lower.as_ref();
+        let mut score = self.field_relevance(entity, query_lower, matched);
+
+        // Observation matches
+        let observation_matches = if self.count_occurrences {
+            entity
+                .observations
+                .iter()
+                .map(|obs| occurrence_density(&obs.content, query_lower))
+                .sum()
+        } else {
+            entity
+                .observations
+                .iter()
+                .filter(|obs| obs.content.to_lowercase().contains(query_lower))
+                .count() as f32
+        };
+
+        if observation_matches > 0.0 {
+            score += self.observation_weight * observation_matches;
+            matched.push(SearchField::Observation);
+        }
+
+        self.structural_bonus(entity, relations, score)
+    }
+
+    /// Calculate the relevance of an entity using BM25 over observation terms,
+    /// recording the fields that matched in `matched`
+    pub fn calculate_bm25_relevance(
+        &self,
+        entity: &Entity,
+        query: &str,
+        relations: &[Relation],
+        stats: &TermStats,
+        boosts: Option<&FieldBoosts>,
+        matched: &mut Vec<SearchField>,
+    ) -> f32 {
+        let ranker = self.boosted(boosts);
+        ranker.bm25_relevance(entity, query, relations, stats, matched)
+    }
+
+    fn bm25_relevance(
+        &self,
+        entity: &Entity,
+        query: &str,
+        relations: &[Relation],
+        stats: &TermStats,
+        matched: &mut Vec<SearchField>,
+    ) -> f32 {
+        let query_lower = query.to_lowercase();
+        let Some(query_lower) = self.term_filter.query_for(entity, &query_lower) else {
+            return 0.0;
+        };
+        let mut score = self.field_relevance(entity, &query_lower, matched);
+
+        let terms: Vec<String> = entity
+            .observations
+            .iter()
+            .flat_map(|o| tokenize(&o.content))
+            .collect();
+        let length_ratio = if stats.average_length > 0.0 {
+            terms.len() as f32 / stats.average_length
+        } else {
+            0.0
+        };
+
+        let query_terms = self.term_filter.query_terms(query);
+        let bm25: f32 = query_terms
+            .iter()
+            .map(|term| {
+                let tf = terms.iter().filter(|t| *t == term).count() as f32;
+                if tf == 0.0 {
+                    return 0.0;
+                }
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * length_ratio);
+                stats.idf(term) * tf * (BM25_K1 + 1.0) / (tf + norm)
+            })
+            .sum();
+
+        score += self.observation_weight * bm25;
+        if bm25 > 0.0 {
+            matched.push(SearchField::Observation);
+        }
+
+        self.structural_bonus(entity, relations, score)
+    }
+
+    /// Calculate how strongly a single observation matches a query.
+    /// The whole query as a phrase scores highest; otherwise the share of query terms present counts.
+    pub fn calculate_observation_relevance(
+        &self,
+        observation: &str,
+        query_lower: &str,
+        query_terms: &[String],
+    ) -> f32 {
+        let observation_lower = observation.to_lowercase();
+        let mut score = 0.0;
+
+        if !query_lower.is_empty() && observation_lower.contains(query_lower) {
+            score += self.observation_weight * 2.0;
+        }
+
+        if !query_terms.is_empty() {
+            let matched = query_terms
+                .iter()
+                .filter(|term| observation_lower.contains(term.as_str()))
+                .count();
+            score += self.observation_weight * matched as f32 / query_terms.len() as f32;
+        }
+
+        score
+    }
+
+    /// Score name and type matches against a lowercase query
+    fn field_relevance(
+        &self,
+        entity: &Entity,
+        query_lower: &str,
+        matched: &mut Vec<SearchField>,
+    ) -> f32 {
         let mut score = 0.0;
 
         // Name match (prefer exact match)
         if entity.name.to_lowercase() == query_lower {
             score += self.name_weight * 2.0;
-        } else if entity.name.to_lowercase().contains(&query_lower) {
+            matched.push(SearchField::Name);
+        } else if entity.name.to_lowercase().contains(query_lower) {
             score += self.name_weight;
+            matched.push(SearchField::Name);
         }
 
         // Type match
-        if entity.entity_type.to_lowercase().contains(&query_lower) {
+        if self.search_types && entity.entity_type.to_lowercase().contains(query_lower) {
             score += self.type_weight;
+            matched.push(SearchField::Type);
         }
 
-        // Observation matches
-        let observation_matches = entity
-            .observations
-            .iter()
-            .filter(|obs| obs.to_lowercase().contains(&query_lower))
-            .count();
-
-        if observation_matches > 0 {
-            score += self.observation_weight * observation_matches as f32;
-        }
+        score
+    }
 
+    /// Add the bonuses independent of the query, observation count and connectivity, to `score`
+    fn structural_bonus(&self, entity: &Entity, relations: &[Relation], score: f32) -> f32 {
         // Bonus for the number of observations
-        score += self.observation_count_weight * (entity.observations.len() as f32).ln_1p();
+        let score =
+            score + self.observation_count_weight * (entity.observations.len() as f32).ln_1p();
 
-        // Bonus for connectivity
+        // Bonus for connectivity, limited to its share of the final score
         let connection_count = relations
             .iter()
             .filter(|r| r.from == entity.name || r.to == entity.name)
             .count();
-        score += self.connectivity_weight * (connection_count as f32).ln_1p();
+        let mut connectivity = self.connectivity_weight * (connection_count as f32).ln_1p();
+        if let Some(cap) = self.connectivity_cap.filter(|cap| *cap < 1.0) {
+            connectivity = connectivity.min(score * cap.max(0.0) / (1.0 - cap));
+        }
 
-        score
+        score + connectivity
+    }
+}
+
+/// Entity count below which scoring runs on the calling thread, since dispatching a small
+/// graph to the pool costs more than scoring it. `benches/search_scoring.rs` puts the
+/// crossover between 1024 and 2048 entities on a single CPU, with sequential scoring up to
+/// 70% faster on smaller graphs. More cores move the crossover down, so tune it with
+/// `SEARCH_PARALLEL_THRESHOLD`.
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 2048;
+
+/// Number of results returned when a search doesn't set a limit
+pub const DEFAULT_SEARCH_LIMIT: usize = 10;
+
+/// Shortest query term that is scored when `SEARCH_MIN_TERM_LENGTH` is not set
+pub const DEFAULT_MIN_TERM_LENGTH: usize = 2;
+
+/// Query terms left out of scoring because they match almost everything
+#[derive(Debug, Clone, Default)]
+struct TermFilter {
+    /// Terms with fewer characters are ignored
+    min_length: usize,
+    /// Lowercase terms ignored whatever their length
+    stop_words: HashSet<String>,
+}
+
+impl TermFilter {
+    /// Read `SEARCH_MIN_TERM_LENGTH` and the comma-separated `SEARCH_STOP_WORDS`
+    fn from_env() -> Self {
+        let min_length = env::var("SEARCH_MIN_TERM_LENGTH")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_MIN_TERM_LENGTH);
+        let stop_words = env::var("SEARCH_STOP_WORDS")
+            .map(|v| {
+                v.split(',')
+                    .map(|w| w.trim().to_lowercase())
+                    .filter(|w| !w.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            min_length,
+            stop_words,
+        }
+    }
+
+    /// Whether a lowercase term counts towards scoring
+    fn keeps(&self, term: &str) -> bool {
+        term.chars().count() >= self.min_length && !self.stop_words.contains(term)
+    }
+
+    /// The distinct tokens of `query` that count towards scoring, in query order
+    fn query_terms(&self, query: &str) -> Vec<String> {
+        let mut seen = HashSet::new();
+        tokenize(query)
+            .into_iter()
+            .filter(|t| self.keeps(t))
+            .filter(|t| seen.insert(t.clone()))
+            .collect()
+    }
+
+    /// The text matched as a substring once ignored terms are removed from a lowercase
+    /// query: the query itself when none are, the remaining terms joined by spaces otherwise,
+    /// and `None` when only ignored terms remain
+    fn scoring_query<'a>(&self, query_lower: &'a str) -> Option<Cow<'a, str>> {
+        let tokens = tokenize(query_lower);
+        if tokens.iter().all(|t| self.keeps(t)) {
+            return Some(Cow::Borrowed(query_lower));
+        }
+        let kept: Vec<String> = tokens.into_iter().filter(|t| self.keeps(t)).collect();
+        (!kept.is_empty()).then(|| Cow::Owned(kept.join(" ")))
+    }
+
+    /// The text an entity's fields are matched against: the whole query when it names the
+    /// entity exactly, so such a match is never lost to the filter, otherwise `scoring_query`
+    fn query_for<'a>(&self, entity: &Entity, query_lower: &'a str) -> Option<Cow<'a, str>> {
+        if entity.name.to_lowercase() == query_lower {
+            Some(Cow::Borrowed(query_lower))
+        } else {
+            self.scoring_query(query_lower)
+        }
+    }
+}
+
+/// Searches remembered when `SEARCH_CACHE_SIZE` is not set
+pub const DEFAULT_QUERY_CACHE_SIZE: usize = 64;
+
+/// Name and matched fields of an entity returned by a cached search
+type CachedHit = (String, Vec<SearchField>);
+
+/// What a cached search returned
+#[derive(Debug, Clone)]
+struct CachedSearch {
+    hits: Vec<CachedHit>,
+    near_duplicates_suppressed: usize,
+}
+
+/// Entities found by a ranked search, best first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RankedEntities {
+    pub entities: Vec<Entity>,
+    /// Results dropped as near-duplicates of an entity named exactly like the query
+    pub near_duplicates_suppressed: usize,
+}
+
+/// Results of recent searches over one graph version, least recently used first
+#[derive(Debug, Default)]
+struct QueryCache {
+    capacity: usize,
+    version: u64,
+    entries: VecDeque<(String, CachedSearch)>,
+}
+
+impl QueryCache {
+    /// Forget everything cached for an older graph
+    fn sync(&mut self, version: u64) {
+        if self.version != version {
+            self.entries.clear();
+            self.version = version;
+        }
+    }
+
+    fn get(&mut self, version: u64, key: &str) -> Option<CachedSearch> {
+        self.sync(version);
+        let position = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(position)?;
+        let hits = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(hits)
+    }
+
+    fn insert(&mut self, version: u64, key: String, hits: CachedSearch) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.sync(version);
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, hits));
     }
 }
 
 /// Simple search engine with ranking
 pub struct SearchEngine {
     ranker: SearchRanker,
+    /// Links to found entities required to include a neighbor
+    related_entity_threshold: usize,
+    /// Graphs with fewer entities are scored sequentially
+    parallel_threshold: usize,
+    /// Dedicated pool when `SEARCH_THREADS` is set, otherwise rayon's global pool
+    thread_pool: Option<Arc<ThreadPool>>,
+    /// Recent results of searches that set `SearchOptions::graph_version`
+    query_cache: Mutex<QueryCache>,
+    /// BM25 term statistics of the last graph version searched
+    term_stats: Mutex<Option<(u64, Arc<TermStats>)>>,
 }
 
 impl SearchEngine {
+    /// Create a search engine, honouring `SEARCH_PARALLEL_THRESHOLD`, `SEARCH_THREADS`,
+    /// `SEARCH_MATCH_TYPES`, `SEARCH_MIN_TERM_LENGTH`, `SEARCH_STOP_WORDS` and `SEARCH_CACHE_SIZE`
     pub fn new() -> Self {
+        let parallel_threshold = env::var("SEARCH_PARALLEL_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_PARALLEL_THRESHOLD);
+
+        let thread_pool = env::var("SEARCH_THREADS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|threads| *threads > 0)
+            .and_then(|threads| ThreadPoolBuilder::new().num_threads(threads).build().ok())
+            .map(Arc::new);
+
+        let search_types = env::var("SEARCH_MATCH_TYPES")
+            .map(|v| !matches!(v.trim().to_lowercase().as_str(), "0" | "false" | "no"))
+            .unwrap_or(true);
+
+        let cache_size = env::var("SEARCH_CACHE_SIZE")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_QUERY_CACHE_SIZE);
+
         Self {
-            ranker: SearchRanker::default(),
+            ranker: SearchRanker {
+                search_types,
+                term_filter: TermFilter::from_env(),
+                ..Default::default()
+            },
+            related_entity_threshold: DEFAULT_RELATED_ENTITY_THRESHOLD,
+            parallel_threshold,
+            thread_pool,
+            query_cache: Mutex::new(QueryCache {
+                capacity: cache_size,
+                ..Default::default()
+            }),
+            term_stats: Mutex::new(None),
+        }
+    }
+
+    /// The ranker settings in effect once `config` is applied, with every field set
+    pub fn effective_config(&self, config: &SearchConfig) -> SearchConfig {
+        let ranker = self.ranker.with_config(config);
+        SearchConfig {
+            name_weight: Some(ranker.name_weight),
+            type_weight: Some(ranker.type_weight),
+            observation_weight: Some(ranker.observation_weight),
+            observation_count_weight: Some(ranker.observation_count_weight),
+            connectivity_weight: Some(ranker.connectivity_weight),
+            connectivity_cap: ranker.connectivity_cap,
+            search_types: Some(ranker.search_types),
+            count_occurrences: Some(ranker.count_occurrences),
+            near_duplicate_similarity: ranker.near_duplicate_similarity,
+        }
+    }
+
+    pub fn parallel_threshold(&self) -> usize {
+        self.parallel_threshold
+    }
+
+    pub fn min_term_length(&self) -> usize {
+        self.ranker.term_filter.min_length
+    }
+
+    /// The stop words, alphabetically
+    pub fn stop_words(&self) -> Vec<String> {
+        let mut words: Vec<String> = self.ranker.term_filter.stop_words.iter().cloned().collect();
+        words.sort();
+        words
+    }
+
+    /// Searches whose results are cached, 0 when caching is off
+    pub fn query_cache_size(&self) -> usize {
+        self.query_cache.lock().map_or(0, |cache| cache.capacity)
+    }
+
+    /// Size of the dedicated search pool, `None` when rayon's global pool is used
+    pub fn search_threads(&self) -> Option<usize> {
+        self.thread_pool
+            .as_ref()
+            .map(|pool| pool.current_num_threads())
+    }
+
+    /// Apply `f` to every entity, in parallel only for graphs above the threshold
+    fn map_entities<T, F>(&self, entities: &[&Entity], f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(&Entity) -> Option<T> + Sync + Send,
+    {
+        if entities.len() < self.parallel_threshold {
+            return entities.iter().filter_map(|entity| f(entity)).collect();
+        }
+
+        let scan = || entities.par_iter().filter_map(|entity| f(entity)).collect();
+        match &self.thread_pool {
+            Some(pool) => pool.install(scan),
+            None => scan(),
         }
     }
 
@@ -96,38 +886,350 @@ impl SearchEngine {
         graph: &KnowledgeGraph,
         query: &str,
         limit: Option<usize>,
+        options: &SearchOptions,
     ) -> Result<Vec<Entity>> {
+        Ok(self
+            .ranked_search(graph, query, limit, options)
+            .await?
+            .entities)
+    }
+
+    /// Term statistics of `graph`, reused across searches while `version` stays the same
+    fn term_stats(&self, graph: &KnowledgeGraph, version: Option<u64>) -> Arc<TermStats> {
+        let Some(version) = version else {
+            return Arc::new(TermStats::build(graph));
+        };
+        let Ok(mut cached) = self.term_stats.lock() else {
+            return Arc::new(TermStats::build(graph));
+        };
+        if let Some((built_at, stats)) = cached.as_ref() {
+            if *built_at == version {
+                return Arc::clone(stats);
+            }
+        }
+        let stats = Arc::new(TermStats::build(graph));
+        *cached = Some((version, Arc::clone(&stats)));
+        stats
+    }
+
+    /// Like `enhanced_text_search`, also reporting how many near-duplicates were dropped
+    pub async fn ranked_search(
+        &self,
+        graph: &KnowledgeGraph,
+        query: &str,
+        limit: Option<usize>,
+        options: &SearchOptions,
+    ) -> Result<RankedEntities> {
         let _start_time = std::time::Instant::now();
 
-        let _query_lower = query.to_lowercase();
+        // A quoted query only matches entities whose name or an observation contains the
+        // whole phrase; the phrase without quotes is then scored like any other query
+        let phrase = quoted_phrase(query).map(str::to_lowercase);
+        let query = quoted_phrase(query).unwrap_or(query);
+        // Results depend on nothing but the graph version and the request, unless they decay
+        // with time. Matching ignores case, so the key does too; quoting changes matching, so
+        // the key keeps the phrase apart from the same words unquoted.
+        let cache_key = options
+            .graph_version
+            .filter(|_| options.decay_half_life_days.is_none())
+            .map(|version| {
+                let normalized = query.to_lowercase();
+                let options = SearchOptions {
+                    graph_version: None,
+                    ..options.clone()
+                };
+                (
+                    version,
+                    format!("{normalized}\0{phrase:?}\0{limit:?}\0{options:?}"),
+                )
+            });
+        if let Some((version, key)) = &cache_key {
+            let hits = self
+                .query_cache
+                .lock()
+                .ok()
+                .and_then(|mut cache| cache.get(*version, key));
+            if let Some(cached) = hits {
+                return Ok(RankedEntities {
+                    entities: cached
+                        .hits
+                        .into_iter()
+                        .filter_map(|(name, matched_fields)| {
+                            let mut entity = graph.entities.get(&name)?.clone();
+                            entity.matched_fields = matched_fields;
+                            Some(entity)
+                        })
+                        .collect(),
+                    near_duplicates_suppressed: cached.near_duplicates_suppressed,
+                });
+            }
+        }
+
+        let query_lower = query.to_lowercase();
         let entities: Vec<_> = graph.entities.values().collect();
 
-        // Compute relevance scores in parallel
-        let mut scored_entities: Vec<_> = entities
-            .par_iter()
-            .filter_map(|entity| {
-                let relevance =
-                    self.ranker
-                        .calculate_text_relevance(entity, query, &graph.relations);
-                if relevance > 0.0 {
-                    Some(((*entity).clone(), relevance))
-                } else {
-                    None
+        // Relations whose observations match, counted once per endpoint entity
+        let mut relation_matches: HashMap<&str, usize> = HashMap::new();
+        if options.search_relation_observations {
+            if let Some(relation_query) = self.ranker.term_filter.scoring_query(&query_lower) {
+                for relation in graph.relations.iter().filter(|r| {
+                    r.observations
+                        .iter()
+                        .any(|o| o.to_lowercase().contains(relation_query.as_ref()))
+                }) {
+                    *relation_matches.entry(&relation.from).or_default() += 1;
+                    if relation.to != relation.from {
+                        *relation_matches.entry(&relation.to).or_default() += 1;
+                    }
                 }
-            })
-            .collect();
+            }
+        }
+
+        // Term statistics are built once per graph version and shared across entities
+        let term_stats = match options.ranker {
+            RankerKind::Weighted => None,
+            RankerKind::Bm25 => Some(self.term_stats(graph, options.graph_version)),
+        };
+
+        let now = now_timestamp();
+        let mut ranker = match &options.ranker_config {
+            Some(config) => Cow::Owned(self.ranker.with_config(config)),
+            None => Cow::Borrowed(&self.ranker),
+        };
+        if let Some(search_types) = options.search_types {
+            if search_types != ranker.search_types {
+                ranker.to_mut().search_types = search_types;
+            }
+        }
+
+        // Compute relevance scores, in parallel for large graphs
+        let mut scored_entities: Vec<_> = self.map_entities(&entities, |entity| {
+            if let Some(source) = &options.source {
+                if !entity
+                    .observations
+                    .iter()
+                    .any(|o| o.source.as_ref() == Some(source))
+                {
+                    return None;
+                }
+            }
+
+            if let Some(phrase) = &phrase {
+                if !entity.name.to_lowercase().contains(phrase.as_str())
+                    && !entity
+                        .observations
+                        .iter()
+                        .any(|o| o.content.to_lowercase().contains(phrase.as_str()))
+                {
+                    return None;
+                }
+            }
+
+            let boosts = options.boosts.as_ref();
+            let mut matched_fields = Vec::new();
+            let mut relevance = match &term_stats {
+                Some(stats) => ranker.calculate_bm25_relevance(
+                    entity,
+                    query,
+                    &graph.relations,
+                    stats,
+                    boosts,
+                    &mut matched_fields,
+                ),
+                None => ranker.calculate_text_relevance(
+                    entity,
+                    query,
+                    &graph.relations,
+                    boosts,
+                    &mut matched_fields,
+                ),
+            };
+            if let Some(&matches) = relation_matches.get(entity.name.as_str()) {
+                relevance += ranker.observation_weight * matches as f32;
+                matched_fields.push(SearchField::Relation);
+            }
+            relevance *= recency_decay(entity, now, options.decay_half_life_days);
+            if relevance > 0.0 {
+                let mut entity = entity.clone();
+                entity.matched_fields = matched_fields;
+                Some((entity, relevance))
+            } else {
+                None
+            }
+        });
 
         // Sort by relevance
         scored_entities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
 
-        let limit = limit.unwrap_or(10);
+        // Below an entity named exactly like the query, near-identical names can be clutter
+        let mut near_duplicates_suppressed = 0;
+        if let Some(threshold) = ranker.near_duplicate_similarity {
+            if let Some(exact) = scored_entities
+                .iter()
+                .position(|(entity, _)| entity.name.to_lowercase() == query_lower)
+            {
+                let exact_name = scored_entities[exact].0.name.clone();
+                let before = scored_entities.len();
+                let mut rank = 0;
+                scored_entities.retain(|(entity, _)| {
+                    rank += 1;
+                    rank <= exact + 1 || name_similarity(&entity.name, &exact_name) < threshold
+                });
+                near_duplicates_suppressed = before - scored_entities.len();
+            }
+        }
+
+        let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
         let results: Vec<Entity> = scored_entities
             .into_iter()
             .take(limit)
             .map(|(entity, _)| entity)
             .collect();
 
-        Ok(results)
+        if let Some((version, key)) = cache_key {
+            let hits = results
+                .iter()
+                .map(|e| (e.name.clone(), e.matched_fields.clone()))
+                .collect();
+            if let Ok(mut cache) = self.query_cache.lock() {
+                cache.insert(
+                    version,
+                    key,
+                    CachedSearch {
+                        hits,
+                        near_duplicates_suppressed,
+                    },
+                );
+            }
+        }
+
+        Ok(RankedEntities {
+            entities: results,
+            near_duplicates_suppressed,
+        })
+    }
+
+    /// Rank individual observations across all entities
+    pub fn observation_search(
+        &self,
+        graph: &KnowledgeGraph,
+        query: &str,
+        limit: Option<usize>,
+    ) -> Vec<ObservationMatch> {
+        let query_lower = query.to_lowercase();
+        let query_terms = self.ranker.term_filter.query_terms(query);
+        if query_terms.is_empty() && !tokenize(query).is_empty() {
+            return Vec::new();
+        }
+        let entities: Vec<_> = graph.entities.values().collect();
+
+        let mut matches: Vec<ObservationMatch> = self
+            .map_entities(&entities, |entity| {
+                let entity_matches: Vec<ObservationMatch> = entity
+                    .observations
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, observation)| {
+                        let score = self.ranker.calculate_observation_relevance(
+                            &observation.content,
+                            &query_lower,
+                            &query_terms,
+                        );
+                        (score > 0.0).then(|| ObservationMatch {
+                            entity_name: entity.name.clone(),
+                            observation_index: index,
+                            observation: observation.clone(),
+                            score,
+                        })
+                    })
+                    .collect();
+                Some(entity_matches)
+            })
+            .into_iter()
+            .flatten()
+            .collect();
+
+        matches.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.entity_name.cmp(&b.entity_name))
+                .then_with(|| a.observation_index.cmp(&b.observation_index))
+        });
+        matches.truncate(limit.unwrap_or(DEFAULT_SEARCH_LIMIT));
+
+        matches
+    }
+
+    /// Every entity with an observation containing `substring`, sorted by name.
+    /// Unlike the searches this is an exact filter, without ranking or a limit.
+    pub fn entities_with_observation(
+        &self,
+        graph: &KnowledgeGraph,
+        substring: &str,
+        case_sensitive: bool,
+    ) -> Vec<Entity> {
+        let needle = if case_sensitive {
+            substring.to_string()
+        } else {
+            substring.to_lowercase()
+        };
+        let entities: Vec<_> = graph.entities.values().collect();
+
+        let mut found = self.map_entities(&entities, |entity| {
+            entity
+                .observations
+                .iter()
+                .any(|o| {
+                    if case_sensitive {
+                        o.content.contains(&needle)
+                    } else {
+                        o.content.to_lowercase().contains(&needle)
+                    }
+                })
+                .then(|| entity.clone())
+        });
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+
+        found
+    }
+
+    /// Entities whose observations mention any of `terms` (ignoring case), those covering
+    /// the most terms first and then by name. Entities covering none are left out.
+    pub fn entities_covering(
+        &self,
+        graph: &KnowledgeGraph,
+        terms: &[String],
+    ) -> Vec<EntityCoverage> {
+        let needles: Vec<String> = terms.iter().map(|t| t.to_lowercase()).collect();
+        let entities: Vec<_> = graph.entities.values().collect();
+
+        let mut found = self.map_entities(&entities, |entity| {
+            let observations: Vec<String> = entity
+                .observations
+                .iter()
+                .map(|o| o.content.to_lowercase())
+                .collect();
+            let covered_terms: Vec<String> = terms
+                .iter()
+                .zip(&needles)
+                .filter(|(_, needle)| observations.iter().any(|o| o.contains(needle.as_str())))
+                .map(|(term, _)| term.clone())
+                .collect();
+            (!covered_terms.is_empty()).then(|| EntityCoverage {
+                entity: entity.clone(),
+                coverage: covered_terms.len() as f32 / terms.len() as f32,
+                covered_terms,
+            })
+        });
+        found.sort_by(|a, b| {
+            b.covered_terms
+                .len()
+                .cmp(&a.covered_terms.len())
+                .then_with(|| a.entity.name.cmp(&b.entity.name))
+        });
+
+        found
     }
 
     /// Filter relations based on the found entities
@@ -137,6 +1239,7 @@ impl SearchEngine {
         entity_names: &HashSet<String>,
         show_all_relations: bool,
         include_related_entities: bool,
+        related_entity_threshold: Option<usize>,
     ) -> Vec<Relation> {
         if show_all_relations {
             // Include all relations where at least one side was found
@@ -160,9 +1263,10 @@ impl SearchEngine {
                 }
             }
 
-            // Add highly connected entities (>=2 links)
+            // Add highly connected entities (>= threshold links)
+            let threshold = related_entity_threshold.unwrap_or(self.related_entity_threshold);
             for (entity, connections) in entity_connections {
-                if connections >= 2 {
+                if connections >= threshold {
                     related_entities.insert(entity);
                 }
             }
@@ -182,3 +1286,848 @@ impl SearchEngine {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(name: &str, entity_type: &str, observations: &[&str]) -> Entity {
+        Entity {
+            name: name.to_string(),
+            entity_type: entity_type.to_string(),
+            observations: observations.iter().map(|o| (*o).into()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn graph(entities: Vec<Entity>, relations: Vec<Relation>) -> KnowledgeGraph {
+        KnowledgeGraph {
+            entities: entities.into_iter().map(|e| (e.name.clone(), e)).collect(),
+            relations,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bm25_prefers_rare_terms() -> Result<()> {
+        let graph = graph(
+            vec![
+                entity("Rare", "Note", &["Spotted a zebra"]),
+                entity("Common", "Note", &["Drinks coffee daily"]),
+                entity("Filler1", "Note", &["Likes coffee"]),
+                entity("Filler2", "Note", &["Hates coffee"]),
+                entity("Filler3", "Note", &["Sells coffee"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+        let options = SearchOptions {
+            ranker: RankerKind::Bm25,
+            ..Default::default()
+        };
+
+        let results = engine
+            .enhanced_text_search(&graph, "coffee zebra", None, &options)
+            .await?;
+        let rare = results.iter().position(|e| e.name == "Rare").unwrap();
+        let common = results.iter().position(|e| e.name == "Common").unwrap();
+        assert!(rare < common, "rare term match should rank first");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_quoted_phrase_search() -> Result<()> {
+        let graph = graph(
+            vec![
+                entity("Kickoff", "Meeting", &["Planned Project Alpha milestones"]),
+                entity("Release", "Note", &["Alpha release of the other project"]),
+                entity("Project Alpha Team", "Team", &[]),
+                entity("Unrelated", "Note", &["Nothing to see"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+        // Structural bonuses score every entity, so only count those with a matching field
+        let names = |results: Vec<Entity>| {
+            let mut names: Vec<String> = results
+                .into_iter()
+                .filter(|e| !e.matched_fields.is_empty())
+                .map(|e| e.name)
+                .collect();
+            names.sort();
+            names
+        };
+
+        for ranker in [RankerKind::Weighted, RankerKind::Bm25] {
+            let options = SearchOptions {
+                ranker,
+                ..Default::default()
+            };
+            let results = engine
+                .enhanced_text_search(&graph, "\"project alpha\"", None, &options)
+                .await?;
+            assert_eq!(
+                names(results),
+                ["Kickoff", "Project Alpha Team"],
+                "{ranker:?}"
+            );
+        }
+
+        // Unquoted, BM25 matches the terms wherever they appear
+        let options = SearchOptions {
+            ranker: RankerKind::Bm25,
+            ..Default::default()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "project alpha", None, &options)
+            .await?;
+        assert_eq!(names(results), ["Kickoff", "Project Alpha Team", "Release"]);
+
+        assert_eq!(
+            match_snippet("Planned Project Alpha milestones", "\"project alpha\"", 13),
+            Some("…Project Alpha…".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_related_entity_threshold() {
+        let relation = |from: &str, to: &str| Relation {
+            from: from.to_string(),
+            to: to.to_string(),
+            relation_type: "knows".to_string(),
+            ..Default::default()
+        };
+        let relations = vec![relation("Alice", "Bob"), relation("Carol", "Alice")];
+        let found: HashSet<String> = ["Alice".to_string()].into_iter().collect();
+        let engine = SearchEngine::new();
+
+        let default = engine.filter_relations_smart(&relations, &found, false, true, None);
+        assert!(
+            default.is_empty(),
+            "neighbors linked once are excluded at 2"
+        );
+
+        let relaxed = engine.filter_relations_smart(&relations, &found, false, true, Some(1));
+        assert_eq!(relaxed.len(), 2);
+        assert!(relaxed.iter().any(|r| r.to == "Bob"));
+        assert!(relaxed.iter().any(|r| r.from == "Carol"));
+    }
+
+    #[test]
+    fn test_observation_search() {
+        let graph = graph(
+            vec![
+                entity("Alice", "Person", &["Likes black coffee", "Works remotely"]),
+                entity(
+                    "Bob",
+                    "Person",
+                    &["Drinks tea", "Prefers coffee that is black"],
+                ),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+
+        let matches = engine.observation_search(&graph, "black coffee", None);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].entity_name, "Alice");
+        assert_eq!(matches[0].observation_index, 0);
+        assert_eq!(matches[1].entity_name, "Bob");
+        assert_eq!(matches[1].observation_index, 1);
+        assert!(matches[0].score > matches[1].score);
+
+        let limited = engine.observation_search(&graph, "coffee", Some(1));
+        assert_eq!(limited.len(), 1);
+    }
+
+    #[test]
+    fn test_entities_with_observation() {
+        let graph = graph(
+            vec![
+                entity("Carol", "Person", &["Drinks Coffee"]),
+                entity("Alice", "Person", &["Likes coffee", "Works remotely"]),
+                entity("Bob", "Person", &["Drinks tea"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+        let names = |entities: Vec<Entity>| -> Vec<String> {
+            entities.into_iter().map(|e| e.name).collect()
+        };
+
+        assert_eq!(
+            names(engine.entities_with_observation(&graph, "coffee", false)),
+            vec!["Alice", "Carol"]
+        );
+        assert_eq!(
+            names(engine.entities_with_observation(&graph, "coffee", true)),
+            vec!["Alice"]
+        );
+        assert!(engine
+            .entities_with_observation(&graph, "juice", false)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_entities_covering() {
+        let graph = graph(
+            vec![
+                entity("Alice", "Person", &["Knows Rust", "Teaches SQL"]),
+                entity("Bob", "Person", &["Writes rust and Python"]),
+                entity("Carol", "Person", &["Uses SQL, Python and Rust daily"]),
+                entity("Dave", "Person", &["Drinks tea"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+        let terms: Vec<String> = ["rust", "python", "sql"].map(String::from).to_vec();
+
+        let found = engine.entities_covering(&graph, &terms);
+        let summary: Vec<(&str, Vec<&str>)> = found
+            .iter()
+            .map(|c| {
+                (
+                    c.entity.name.as_str(),
+                    c.covered_terms.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Carol", vec!["rust", "python", "sql"]),
+                ("Alice", vec!["rust", "sql"]),
+                ("Bob", vec!["rust", "python"]),
+            ]
+        );
+        assert_eq!(found[0].coverage, 1.0);
+        assert!((found[1].coverage - 2.0 / 3.0).abs() < f32::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_recency_decay_prefers_recent_entities() -> Result<()> {
+        let now = now_timestamp();
+        let mut old = entity("Old", "Note", &["Likes coffee"]);
+        old.updated_at = Some(now - 60 * SECONDS_PER_DAY as i64);
+        let mut recent = entity("Recent", "Note", &["Likes coffee"]);
+        recent.updated_at = Some(now);
+        let undated = entity("Undated", "Note", &["Likes coffee"]);
+        let graph = graph(vec![old.clone(), recent.clone(), undated.clone()], vec![]);
+        let engine = SearchEngine::new();
+
+        let options = SearchOptions {
+            decay_half_life_days: Some(30.0),
+            ..Default::default()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "coffee", None, &options)
+            .await?;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[2].name, "Old");
+
+        assert!((recency_decay(&old, now, Some(30.0)) - 0.25).abs() < 1e-3);
+        assert_eq!(recency_decay(&recent, now, Some(30.0)), 1.0);
+        assert_eq!(recency_decay(&undated, now, Some(30.0)), 1.0);
+        assert_eq!(recency_decay(&old, now, None), 1.0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sequential_and_parallel_search_agree() -> Result<()> {
+        let graph = graph(
+            (0..50)
+                .map(|i| entity(&format!("Entity{i}"), "Note", &[&format!("coffee {i}")]))
+                .collect(),
+            vec![],
+        );
+        let options = SearchOptions::default();
+
+        let sequential = SearchEngine {
+            parallel_threshold: usize::MAX,
+            ..SearchEngine::new()
+        };
+        let parallel = SearchEngine {
+            parallel_threshold: 0,
+            ..SearchEngine::new()
+        };
+
+        let mut a: Vec<String> = sequential
+            .enhanced_text_search(&graph, "coffee", Some(100), &options)
+            .await?
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        let mut b: Vec<String> = parallel
+            .enhanced_text_search(&graph, "coffee", Some(100), &options)
+            .await?
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        a.sort();
+        b.sort();
+        assert_eq!(a.len(), 50);
+        assert_eq!(a, b);
+
+        assert_eq!(
+            sequential.observation_search(&graph, "coffee 7", None),
+            parallel.observation_search(&graph, "coffee 7", None)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_source_filter() -> Result<()> {
+        let mut alice = entity("Alice", "Person", &["Likes coffee"]);
+        alice.observations[0].source = Some("chat-1".to_string());
+        let bob = entity("Bob", "Person", &["Likes coffee"]);
+        let graph = graph(vec![alice, bob], vec![]);
+        let engine = SearchEngine::new();
+
+        let options = SearchOptions {
+            source: Some("chat-1".to_string()),
+            ..Default::default()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "coffee", None, &options)
+            .await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "Alice");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_field_boosts_change_ordering() -> Result<()> {
+        let graph = graph(
+            vec![
+                entity("Rust Book", "Book", &[]),
+                entity("Alice", "Person", &["Loves rust", "Writes rust daily"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+
+        let results = engine
+            .enhanced_text_search(&graph, "rust", None, &SearchOptions::default())
+            .await?;
+        assert_eq!(results[0].name, "Alice");
+
+        let options = SearchOptions {
+            boosts: Some(FieldBoosts {
+                name: Some(5.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "rust", None, &options)
+            .await?;
+        assert_eq!(results[0].name, "Rust Book");
+
+        let options = SearchOptions {
+            boosts: Some(FieldBoosts {
+                observation: Some(0.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "rust", None, &options)
+            .await?;
+        assert_eq!(results[0].name, "Rust Book");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_types_toggle() -> Result<()> {
+        let graph = graph(
+            vec![
+                entity("Groceries", "Note", &[]),
+                entity("Todo", "Note", &[]),
+                entity("Meeting", "Event", &["Took a note about the budget"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+
+        let results = engine
+            .enhanced_text_search(&graph, "note", None, &SearchOptions::default())
+            .await?;
+        assert_eq!(results.len(), 3);
+
+        let options = SearchOptions {
+            search_types: Some(false),
+            ..Default::default()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "note", None, &options)
+            .await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "Meeting");
+
+        let engine = SearchEngine {
+            ranker: SearchRanker {
+                search_types: false,
+                ..Default::default()
+            },
+            ..SearchEngine::new()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "note", None, &SearchOptions::default())
+            .await?;
+        assert_eq!(results.len(), 1);
+        let options = SearchOptions {
+            search_types: Some(true),
+            ..Default::default()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "note", None, &options)
+            .await?;
+        assert_eq!(results.len(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ranker_config() -> Result<()> {
+        let graph = graph(
+            vec![
+                entity("Rust Book", "Book", &[]),
+                entity("Alice", "Person", &["Loves rust", "Writes rust daily"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+
+        let options = SearchOptions {
+            ranker_config: Some(SearchConfig {
+                name_weight: Some(5.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "rust", None, &options)
+            .await?;
+        assert_eq!(results[0].name, "Rust Book");
+
+        // Per-query boosts still win over the persisted config
+        let options = SearchOptions {
+            boosts: Some(FieldBoosts {
+                name: Some(1.0),
+                ..Default::default()
+            }),
+            ..options
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "rust", None, &options)
+            .await?;
+        assert_eq!(results[0].name, "Alice");
+
+        let merged = SearchConfig {
+            name_weight: Some(3.0),
+            search_types: Some(false),
+            ..Default::default()
+        }
+        .merged(SearchConfig {
+            name_weight: Some(4.0),
+            ..Default::default()
+        });
+        assert_eq!(merged.name_weight, Some(4.0));
+        assert_eq!(merged.search_types, Some(false));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_term_filter() -> Result<()> {
+        let graph = graph(
+            vec![
+                entity("Zebra", "Animal", &["Lives in the savanna"]),
+                entity("Tea", "Drink", &["Best with a biscuit in the afternoon"]),
+            ],
+            vec![],
+        );
+        let mut engine = SearchEngine::new();
+        engine.ranker.term_filter = TermFilter {
+            min_length: 2,
+            stop_words: ["the".to_string(), "in".to_string()].into(),
+        };
+        assert_eq!(
+            engine
+                .ranker
+                .term_filter
+                .query_terms("A zebra in the Zebra savanna"),
+            ["zebra", "savanna"]
+        );
+
+        // Single characters and stop words alone match nothing
+        for query in ["a", "in the"] {
+            let results = engine
+                .enhanced_text_search(&graph, query, None, &SearchOptions::default())
+                .await?;
+            assert!(results.is_empty(), "{query}");
+            assert!(engine.observation_search(&graph, query, None).is_empty());
+        }
+
+        // Only the remaining terms score with BM25
+        let options = SearchOptions {
+            ranker: RankerKind::Bm25,
+            ..Default::default()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "a zebra in the savanna", None, &options)
+            .await?;
+        let hits: Vec<&str> = results
+            .iter()
+            .filter(|e| !e.matched_fields.is_empty())
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(hits, ["Zebra"]);
+
+        // The default ranker matches what is left once ignored terms are dropped
+        let results = engine
+            .enhanced_text_search(&graph, "a savanna", None, &SearchOptions::default())
+            .await?;
+        let hits: Vec<&str> = results
+            .iter()
+            .filter(|e| !e.matched_fields.is_empty())
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(hits, ["Zebra"]);
+
+        // An exact name match is kept even when every term is filtered out
+        let graph = graph_with_languages();
+        for options in [SearchOptions::default(), options] {
+            let results = engine
+                .enhanced_text_search(&graph, "c", None, &options)
+                .await?;
+            let names: Vec<&str> = results.iter().map(|e| e.name.as_str()).collect();
+            assert_eq!(names, ["C"], "{:?}", options.ranker);
+            assert!(results[0].matched_fields.contains(&SearchField::Name));
+        }
+
+        Ok(())
+    }
+
+    fn graph_with_languages() -> KnowledgeGraph {
+        graph(
+            vec![
+                entity("C", "Language", &["Compiled systems language"]),
+                entity("Rust", "Language", &["Compiled with cargo"]),
+                entity("Go", "Language", &["Has a garbage collector"]),
+            ],
+            vec![],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_count_occurrences() -> Result<()> {
+        let graph = graph(
+            vec![
+                entity("Focused", "Note", &["rust rust rust"]),
+                entity(
+                    "Scattered",
+                    "Note",
+                    &["likes rust a lot", "uses rust at work"],
+                ),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+        let top_hit = |results: Vec<Entity>| results[0].name.clone();
+
+        // By default each observation containing the query counts once
+        let results = engine
+            .enhanced_text_search(&graph, "rust", None, &SearchOptions::default())
+            .await?;
+        assert_eq!(top_hit(results), "Scattered");
+
+        // Counting occurrences favours the observation that is mostly about the term
+        let options = SearchOptions {
+            ranker_config: Some(SearchConfig {
+                count_occurrences: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let results = engine
+            .enhanced_text_search(&graph, "rust", None, &options)
+            .await?;
+        assert_eq!(top_hit(results), "Focused");
+
+        assert_eq!(
+            occurrence_density("Rust and rust", "rust"),
+            2.0 / 3f32.sqrt()
+        );
+        assert_eq!(occurrence_density("Go", "rust"), 0.0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_near_duplicates_of_exact_match() -> Result<()> {
+        let graph = graph(
+            vec![
+                entity("Alice", "Person", &["Works at Acme"]),
+                entity("Alices", "Person", &["Duplicate of Alice"]),
+                entity("Alicex", "Person", &[]),
+                entity("Alice Smith", "Person", &["Lives in Berlin"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+        let names = |results: Vec<Entity>| -> Vec<String> {
+            results
+                .into_iter()
+                .filter(|e| !e.matched_fields.is_empty())
+                .map(|e| e.name)
+                .collect()
+        };
+
+        // Off by default, so similar names stay
+        let results = engine
+            .ranked_search(&graph, "alice", None, &SearchOptions::default())
+            .await?;
+        assert_eq!(results.near_duplicates_suppressed, 0);
+        assert_eq!(names(results.entities).len(), 4);
+
+        let options = SearchOptions {
+            ranker_config: Some(SearchConfig {
+                near_duplicate_similarity: Some(0.8),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let results = engine
+            .ranked_search(&graph, "alice", None, &options)
+            .await?;
+        assert_eq!(results.near_duplicates_suppressed, 2);
+        assert_eq!(names(results.entities), vec!["Alice", "Alice Smith"]);
+
+        // Without an exact match nothing is suppressed
+        let results = engine.ranked_search(&graph, "alic", None, &options).await?;
+        assert_eq!(results.near_duplicates_suppressed, 0);
+        assert_eq!(names(results.entities).len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_term_stats_cache() {
+        let small = graph(vec![entity("Alice", "Person", &["Drinks tea"])], vec![]);
+        let large = graph(
+            vec![
+                entity("Alice", "Person", &["Drinks tea"]),
+                entity("Bob", "Person", &["Drinks coffee"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+
+        // Built once per version, whatever graph is passed in the meantime
+        let first = engine.term_stats(&small, Some(1));
+        assert!(Arc::ptr_eq(&first, &engine.term_stats(&large, Some(1))));
+        assert_eq!(first.document_count, 1);
+        assert_eq!(engine.term_stats(&large, Some(2)).document_count, 2);
+        assert!(!Arc::ptr_eq(
+            &engine.term_stats(&large, None),
+            &engine.term_stats(&large, None)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_query_cache() -> Result<()> {
+        let before = graph(
+            vec![
+                entity("Alice", "Person", &["Drinks tea"]),
+                entity("Bob", "Person", &["Drinks coffee"]),
+            ],
+            vec![],
+        );
+        // The same graph after an edit the cache doesn't know about
+        let after = graph(
+            vec![
+                entity("Alice", "Person", &["Drinks water"]),
+                entity("Bob", "Person", &["Drinks tea"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+        let search = |graph, query, version| {
+            let options = SearchOptions {
+                graph_version: Some(version),
+                ..Default::default()
+            };
+            let engine = &engine;
+            async move {
+                let results = engine
+                    .enhanced_text_search(graph, query, None, &options)
+                    .await?;
+                anyhow::Ok(
+                    results
+                        .into_iter()
+                        .filter(|e| !e.matched_fields.is_empty())
+                        .map(|e| (e.name, e.observations[0].content.clone()))
+                        .collect::<Vec<_>>(),
+                )
+            }
+        };
+
+        assert_eq!(
+            search(&before, "tea", 1).await?,
+            vec![("Alice".to_string(), "Drinks tea".to_string())]
+        );
+        // A repeated query at the same version isn't scored again, but returns current entities
+        assert_eq!(
+            search(&after, "TEA", 1).await?,
+            vec![("Alice".to_string(), "Drinks water".to_string())]
+        );
+        // A new version invalidates the cache
+        assert_eq!(
+            search(&after, "tea", 2).await?,
+            vec![("Bob".to_string(), "Drinks tea".to_string())]
+        );
+
+        // Searches without a version are never cached
+        let results = engine
+            .enhanced_text_search(&before, "tea", None, &SearchOptions::default())
+            .await?;
+        assert_eq!(results[0].name, "Alice");
+
+        // A quoted phrase is cached apart from the same words unquoted. Only BM25 matches
+        // the words on their own, so it tells the two apart.
+        let phrases = graph(
+            vec![entity("Alice", "Person", &["Drinks green tea"])],
+            vec![],
+        );
+        let bm25 = SearchOptions {
+            ranker: RankerKind::Bm25,
+            graph_version: Some(3),
+            ..Default::default()
+        };
+        let found = engine
+            .enhanced_text_search(&phrases, "tea green", None, &bm25)
+            .await?;
+        assert_eq!(found.len(), 1);
+        let found = engine
+            .enhanced_text_search(&phrases, "\"tea green\"", None, &bm25)
+            .await?;
+        assert!(found.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_reports_matched_fields() -> Result<()> {
+        let graph = graph(
+            vec![
+                entity("Rust Book", "Book", &[]),
+                entity("Ferris", "Rust Mascot", &["The rust crab"]),
+                entity("Alice", "Person", &["Writes rust daily"]),
+            ],
+            vec![],
+        );
+        let engine = SearchEngine::new();
+
+        for ranker in [RankerKind::Weighted, RankerKind::Bm25] {
+            let options = SearchOptions {
+                ranker,
+                ..Default::default()
+            };
+            let results = engine
+                .enhanced_text_search(&graph, "rust", None, &options)
+                .await?;
+            let fields: HashMap<_, _> = results
+                .iter()
+                .map(|e| (e.name.as_str(), e.matched_fields.clone()))
+                .collect();
+            assert_eq!(fields["Rust Book"], vec![SearchField::Name]);
+            assert_eq!(
+                fields["Ferris"],
+                vec![SearchField::Type, SearchField::Observation]
+            );
+            assert_eq!(fields["Alice"], vec![SearchField::Observation]);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connectivity_cap_limits_hubs() -> Result<()> {
+        let hub_relations: Vec<Relation> = (0..1000)
+            .map(|i| Relation {
+                from: "Hub".to_string(),
+                to: format!("Node{i}"),
+                relation_type: "links".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let graph = graph(
+            vec![
+                entity("Hub", "Index", &["Mentions coffee once"]),
+                entity("Alice", "Person", &["Loves coffee", "Roasts coffee"]),
+            ],
+            hub_relations,
+        );
+
+        let uncapped = SearchRanker {
+            connectivity_cap: None,
+            ..Default::default()
+        };
+        let score = |ranker: &SearchRanker, name: &str| {
+            ranker.calculate_text_relevance(
+                &graph.entities[name],
+                "coffee",
+                &graph.relations,
+                None,
+                &mut Vec::new(),
+            )
+        };
+        assert!(score(&uncapped, "Hub") > score(&uncapped, "Alice"));
+
+        let ranker = SearchRanker::default();
+        assert!(score(&ranker, "Hub") < score(&ranker, "Alice"));
+        let results = SearchEngine::new()
+            .enhanced_text_search(&graph, "coffee", None, &SearchOptions::default())
+            .await?;
+        assert_eq!(results[0].name, "Alice");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_name_similarity() {
+        assert_eq!(name_similarity("Alice", "alice"), 1.0);
+        assert_eq!(name_similarity("", ""), 1.0);
+        assert_eq!(name_similarity("Alice", ""), 0.0);
+        assert_eq!(name_similarity("Alcie", "Alice"), 0.6);
+        assert_eq!(name_similarity("Alise", "Alice"), 0.8);
+        assert!(name_similarity("Alice", "Bob") < 0.5);
+    }
+
+    #[test]
+    fn test_match_snippet() {
+        assert_eq!(
+            match_snippet("Likes Coffee", "coffee", 40).as_deref(),
+            Some("Likes Coffee")
+        );
+        assert_eq!(match_snippet("Likes tea", "coffee", 40), None);
+
+        let text =
+            "Alice has lived in many places over the years and now lives in Berlin with her cat";
+        let snippet = match_snippet(text, "berlin", 20).unwrap();
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("Berlin"));
+        assert_eq!(snippet.chars().count(), 22);
+
+        // Falls back to individual terms when the whole query doesn't appear
+        let snippet = match_snippet(text, "cat berlin", 200).unwrap();
+        assert_eq!(snippet, text);
+    }
+}